@@ -0,0 +1,2555 @@
+//! The type checker, which runs between parsing and execution. It works out the type of every
+//! expression, gives number literals without a suffix the type they are used as and reports
+//! mismatches as diagnostics.
+
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+};
+
+use hug_lexer::literal::{self, LiteralError};
+use hug_lib::{diagnostic::Diagnostic, interner::Interner, span::Span, value::HugValue, Ident};
+
+use crate::{
+    HugTree, HugTreeBinaryOperator, HugTreeEntry, HugTreeEntryKind, HugTreeExpression,
+    HugTreeExpressionKind, HugTreeFormatSegment, HugTreeFunction, HugTreeGeneric, HugTreeMatch,
+    HugTreeParameter, HugTreePattern, HugTreePatternKind, HugTreePayload, HugTreeType,
+    HugTreeUnaryOperator, HugType,
+};
+
+/// Types of everything declared at the top level, kept between scripts so a script can use what
+/// an earlier one declared.
+#[derive(Debug, Default)]
+pub struct HugTypeGlobals {
+    /// Global variables and functions.
+    pub values: HashMap<Ident, HugType>,
+    /// Names that can be used as a type, apart from the builtin ones.
+    pub types: HashMap<Ident, HugType>,
+    /// The fields of every struct type, in the order they are declared in.
+    pub structs: HashMap<Ident, Vec<(Ident, HugType)>>,
+    /// The variants of every enum and what they carry, in the order they are declared in.
+    pub enums: HashMap<Ident, Vec<(Ident, HugTreePayload<HugType>)>>,
+    /// Items of modules that aren't `public`, they can only be used inside their module.
+    pub private: HashSet<Ident>,
+    /// Functions of `impl` blocks that take `self`, which can be called like `value.method()`.
+    pub methods: HashSet<Ident>,
+    /// The methods every trait needs, `Self` in their signatures is the trait itself.
+    pub traits: HashMap<Ident, Vec<(String, HugType)>>,
+    /// Which types implement which traits, as `(type, trait)`.
+    pub implementations: HashSet<(Ident, Ident)>,
+}
+
+/// Traits the language itself uses: format strings show values with `Display`, `+` adds them
+/// with `Add` and `==` compares them with `Eq`.
+const BUILTIN_TRAITS: [(&str, &str); 3] = [("Display", "format"), ("Add", "add"), ("Eq", "eq")];
+
+pub struct HugTypeChecker<'a> {
+    globals: &'a mut HugTypeGlobals,
+    idents: &'a Interner,
+    /// Types of the local slots of the function or top level being checked.
+    locals: Vec<HugType>,
+    /// Return type of the function being checked, `None` at the top level.
+    return_type: Option<HugType>,
+    /// The module the code being checked is in, `None` outside of modules.
+    module: Option<Ident>,
+    /// Type parameters of the generic functions and types the code being checked is in.
+    generics: Vec<HugType>,
+    /// The type of the `impl` block being checked, without type arguments it is the type with
+    /// its own type parameters.
+    impl_type: Option<Ident>,
+    /// Items this file declares and where, declaring one twice is an error. Scripts loaded later
+    /// can still declare them again.
+    declared: HashMap<Ident, Span>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> HugTypeChecker<'a> {
+    pub fn new(globals: &'a mut HugTypeGlobals, idents: &'a Interner) -> HugTypeChecker<'a> {
+        HugTypeChecker {
+            globals,
+            idents,
+            locals: Vec::new(),
+            return_type: None,
+            module: None,
+            generics: Vec::new(),
+            impl_type: None,
+            declared: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Checks the tree of a file that was loaded as `module`.
+    pub fn in_module(mut self, module: Ident) -> HugTypeChecker<'a> {
+        self.module = Some(module);
+        self
+    }
+
+    /// Checks `tree`, number literals are rewritten to the type they are used as. The tree
+    /// shouldn't be run if any of the returned diagnostics is an error.
+    pub fn check(mut self, tree: &mut HugTree) -> Vec<Diagnostic> {
+        // Functions can be called before the line that defines them
+        self.declare_visibility(&tree.entries);
+        self.declare_builtin_traits();
+        self.declare_types(&tree.entries);
+        self.declare_fields(&tree.entries);
+        self.declare_functions(&tree.entries);
+
+        self.locals = vec![HugType::Unknown; tree.locals];
+        self.top_level(&mut tree.entries);
+
+        // Checked last, so they can use globals defined after them
+        self.functions(&mut tree.entries);
+
+        self.diagnostics
+    }
+
+    fn declare_visibility(&mut self, entries: &[HugTreeEntry]) {
+        for entry in entries {
+            if let HugTreeEntryKind::Implementation { _type, methods, .. } = &entry.kind {
+                self.declare_method_visibility(*_type, methods);
+            }
+
+            // Only items of modules have qualified names
+            let Some(name) = entry.defines() else {
+                continue;
+            };
+            if entry.public || !self.idents.resolve(name).contains('.') {
+                self.globals.private.remove(&name);
+            } else {
+                self.globals.private.insert(name);
+            }
+
+            if let HugTreeEntryKind::ModuleDefinition { body, .. } = &entry.kind {
+                self.declare_visibility(body);
+            }
+        }
+    }
+
+    /// Methods are private to the module of their type, unless they are `public`.
+    fn declare_method_visibility(&mut self, _type: Ident, methods: &[HugTreeEntry]) {
+        let in_module = self.idents.resolve(_type).contains('.');
+        for method in methods {
+            let Some(name) = method.defines() else {
+                continue;
+            };
+            if method.public || !in_module {
+                self.globals.private.remove(&name);
+            } else {
+                self.globals.private.insert(name);
+            }
+        }
+    }
+
+    fn declare_types(&mut self, entries: &[HugTreeEntry]) {
+        for entry in entries {
+            let is_type = matches!(
+                entry.kind,
+                HugTreeEntryKind::ExternalTypeDefinition { .. }
+                    | HugTreeEntryKind::TypeDefinition { .. }
+                    | HugTreeEntryKind::EnumDefinition { .. }
+                    | HugTreeEntryKind::TraitDefinition { .. }
+            );
+            if is_type && !self.declare(entry.defines().unwrap(), entry.span) {
+                continue;
+            }
+
+            match &entry.kind {
+                HugTreeEntryKind::ExternalTypeDefinition { _type: name } => {
+                    // `core.hug` declares the builtin types too
+                    let builtin = HugType::builtin(self.idents.resolve(*name));
+                    let _type = builtin.unwrap_or(HugType::External(*name));
+                    self.globals.types.insert(*name, _type);
+                }
+                HugTreeEntryKind::TypeDefinition {
+                    _type: name,
+                    generics,
+                    ..
+                } => {
+                    let _type = HugType::Struct(*name, type_parameters(generics));
+                    self.globals.types.insert(*name, _type);
+                }
+                HugTreeEntryKind::EnumDefinition {
+                    _type: name,
+                    generics,
+                    ..
+                } => {
+                    let _type = HugType::Enum(*name, type_parameters(generics));
+                    self.globals.types.insert(*name, _type);
+                }
+                // Known before any type is resolved, so bounds can be checked
+                HugTreeEntryKind::Implementation {
+                    _type,
+                    _trait: Some(_trait),
+                    ..
+                } => {
+                    self.globals.implementations.insert((*_type, *_trait));
+                }
+                HugTreeEntryKind::TraitDefinition { _trait: name, .. } => {
+                    self.globals.types.insert(*name, HugType::Trait(*name));
+                }
+                HugTreeEntryKind::ExternalFunctionDefinition {
+                    function,
+                    parameters: None,
+                    ..
+                } => {
+                    self.globals.values.insert(*function, HugType::Unknown);
+                }
+                HugTreeEntryKind::ModuleDefinition { body, .. } => self.declare_types(body),
+                _ => (),
+            }
+        }
+    }
+
+    /// Declares the builtin traits a script mentions, unless it declares its own with that name.
+    /// Names that were never interned can't be in the script.
+    fn declare_builtin_traits(&mut self) {
+        for (name, method) in BUILTIN_TRAITS {
+            let Some(_trait) = self.idents.get(name) else {
+                continue;
+            };
+            if self.globals.types.contains_key(&_trait) {
+                continue;
+            }
+
+            let this = HugType::Trait(_trait);
+            let (parameters, return_type) = match name {
+                "Display" => (vec![this], HugType::String),
+                "Add" => (vec![this.clone(), this.clone()], this),
+                _ => (vec![this.clone(), this], HugType::Bool),
+            };
+            let signature = HugType::Function {
+                parameters,
+                return_type: Box::new(return_type),
+            };
+            self.globals.types.insert(_trait, HugType::Trait(_trait));
+            let methods = vec![(method.to_string(), signature)];
+            self.globals.traits.insert(_trait, methods);
+        }
+    }
+
+    /// Resolves the types of struct fields and enum payloads, once every type is declared so
+    /// they can use types declared after them.
+    fn declare_fields(&mut self, entries: &[HugTreeEntry]) {
+        for entry in entries {
+            if !self.first_declaration(entry) {
+                continue;
+            }
+            match &entry.kind {
+                HugTreeEntryKind::TypeDefinition {
+                    _type,
+                    generics,
+                    fields,
+                } => {
+                    let outer = self.enter_generics(generics);
+                    let fields = fields
+                        .iter()
+                        .map(|field| (field.name, self.resolve(&field._type)))
+                        .collect();
+                    self.generics.truncate(outer);
+                    self.globals.structs.insert(*_type, fields);
+                }
+                HugTreeEntryKind::EnumDefinition {
+                    _type,
+                    generics,
+                    variants,
+                } => {
+                    let outer = self.enter_generics(generics);
+                    let variants = variants
+                        .iter()
+                        .map(|variant| {
+                            let payload = match &variant.payload {
+                                HugTreePayload::Unit => HugTreePayload::Unit,
+                                HugTreePayload::Tuple(types) => HugTreePayload::Tuple(
+                                    types.iter().map(|_type| self.resolve(_type)).collect(),
+                                ),
+                                HugTreePayload::Struct(fields) => HugTreePayload::Struct(
+                                    fields
+                                        .iter()
+                                        .map(|(name, _type)| (*name, self.resolve(_type)))
+                                        .collect(),
+                                ),
+                            };
+                            (variant.name, payload)
+                        })
+                        .collect();
+                    self.generics.truncate(outer);
+                    self.globals.enums.insert(*_type, variants);
+                }
+                HugTreeEntryKind::TraitDefinition { _trait, methods } => {
+                    let methods = methods
+                        .iter()
+                        .map(|method| {
+                            let parameters = method
+                                .parameters
+                                .iter()
+                                .map(|parameter| self.resolve(&parameter._type))
+                                .collect();
+                            let return_type = match &method.return_type {
+                                Some(return_type) => self.resolve(return_type),
+                                None => HugType::Void,
+                            };
+                            let signature = HugType::Function {
+                                parameters,
+                                return_type: Box::new(return_type),
+                            };
+                            (self.idents.resolve(method.name).to_string(), signature)
+                        })
+                        .collect();
+                    self.globals.traits.insert(*_trait, methods);
+                }
+                HugTreeEntryKind::ModuleDefinition { module, body } => {
+                    let outer = self.module.replace(*module);
+                    self.declare_fields(body);
+                    self.module = outer;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn declare_functions(&mut self, entries: &[HugTreeEntry]) {
+        for entry in entries {
+            match &entry.kind {
+                HugTreeEntryKind::FunctionDefinition(function) => {
+                    let signature = self.signature(function);
+                    let first = match self.impl_type {
+                        Some(_type) => self.declare_method(_type, function.name, entry.span),
+                        None => self.declare(function.name, entry.span),
+                    };
+                    if first {
+                        self.globals.values.insert(function.name, signature);
+                    }
+                }
+                HugTreeEntryKind::ExternalFunctionDefinition {
+                    function,
+                    parameters: Some(parameters),
+                    return_type,
+                } => {
+                    let signature = self.function_type(parameters, return_type.as_ref());
+                    self.globals.values.insert(*function, signature);
+                }
+                HugTreeEntryKind::Implementation {
+                    _type,
+                    _trait,
+                    methods,
+                } => {
+                    let outer = self.enter_impl(*_type);
+                    self.declare_functions(methods);
+                    self.leave_impl(outer);
+                    for method in methods {
+                        let HugTreeEntryKind::FunctionDefinition(function) = &method.kind else {
+                            continue;
+                        };
+                        let takes_self = function
+                            .parameters
+                            .first()
+                            .is_some_and(|first| self.idents.resolve(first.name) == "self");
+                        if takes_self {
+                            self.globals.methods.insert(function.name);
+                        } else {
+                            self.globals.methods.remove(&function.name);
+                        }
+                    }
+
+                    if let Some(_trait) = _trait {
+                        self.implement(*_type, *_trait, methods, entry.span);
+                    }
+                }
+                HugTreeEntryKind::ModuleDefinition { module, body } => {
+                    let outer = self.module.replace(*module);
+                    self.declare_functions(body);
+                    self.module = outer;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Records that this file declares `name`, reporting it if it was already declared.
+    fn declare(&mut self, name: Ident, span: Span) -> bool {
+        if let Entry::Vacant(entry) = self.declared.entry(name) {
+            entry.insert(span);
+            return true;
+        }
+        let message = format!("`{}` is defined twice", self.idents.resolve(name));
+        self.error(span, message);
+        false
+    }
+
+    /// [`Self::declare`] for a method of `_type`. Methods of traits are named after the type like
+    /// the others, so a type can't have two methods with the same name, even from two traits.
+    fn declare_method(&mut self, _type: Ident, name: Ident, span: Span) -> bool {
+        if !self.declared.contains_key(&name) {
+            return self.declare(name, span);
+        }
+
+        let method = self.idents.resolve(name).rsplit('.').next().unwrap();
+        let message = format!(
+            "`{}` already has a method `{}`",
+            self.idents.resolve(_type),
+            method
+        );
+        self.diagnostics.push(
+            Diagnostic::error(span, message)
+                .with_hint("methods of traits share their names with the other methods of a type"),
+        );
+        false
+    }
+
+    /// Whether `entry` isn't a second declaration of its name, which was reported and is left out.
+    fn first_declaration(&self, entry: &HugTreeEntry) -> bool {
+        entry
+            .defines()
+            .and_then(|name| self.declared.get(&name))
+            .is_none_or(|span| *span == entry.span)
+    }
+
+    /// Checks that the methods of `impl _trait for _type` are the ones the trait needs, with
+    /// `Self` in their signatures being `_type`.
+    fn implement(&mut self, _type: Ident, _trait: Ident, methods: &[HugTreeEntry], span: Span) {
+        if !self.is_trait(_trait, span) {
+            return;
+        }
+        let trait_name = self.idents.resolve(_trait);
+        let required = self.globals.traits[&_trait].clone();
+
+        let implementor = match self.globals.types.get(&_type) {
+            Some(implementor @ (HugType::Struct(..) | HugType::Enum(..))) => implementor.clone(),
+            _ => {
+                let message = format!(
+                    "Only structs and enums can implement a trait, `{}` is neither",
+                    self.idents.resolve(_type)
+                );
+                self.error(span, message);
+                return;
+            }
+        };
+
+        let mut missing = Vec::new();
+        for (name, signature) in &required {
+            let method = methods.iter().find_map(|method| match &method.kind {
+                HugTreeEntryKind::FunctionDefinition(function)
+                    if self
+                        .idents
+                        .resolve(function.name)
+                        .rsplit_once('.')
+                        .unwrap()
+                        .1
+                        == name.as_str() =>
+                {
+                    Some((function.name, method.span))
+                }
+                _ => None,
+            });
+            let Some((function, method_span)) = method else {
+                missing.push(format!("`{}`", name));
+                continue;
+            };
+
+            let expected = signature.substitute(&HugType::Trait(_trait), &implementor);
+            let found = self.global(function);
+            if found != expected {
+                let message = format!(
+                    "`{}` should be `{}` to implement `{}`, found `{}`",
+                    name,
+                    self.name(&expected),
+                    trait_name,
+                    self.name(&found)
+                );
+                self.error(method_span, message);
+            }
+        }
+
+        for method in methods {
+            let Some(function) = method.defines() else {
+                continue;
+            };
+            let name = self.idents.resolve(function).rsplit_once('.').unwrap().1;
+            let required = required.iter().any(|(required, _)| required == name);
+            if !required {
+                let message = format!("`{}` is not a method of `{}`", name, trait_name);
+                self.diagnostics
+                    .push(Diagnostic::error(method.span, message).with_hint(format!(
+                        "move it to a separate `impl {}` block",
+                        self.name(&implementor)
+                    )));
+            }
+        }
+
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            let message = format!(
+                "Missing method{} {} in `impl {} for {}`",
+                plural,
+                missing.join(", "),
+                trait_name,
+                self.name(&implementor)
+            );
+            self.error(span, message);
+        }
+    }
+
+    /// Reports `name` not being a trait, where it is used as one.
+    fn is_trait(&mut self, name: Ident, span: Span) -> bool {
+        let message = match self.globals.types.get(&name) {
+            Some(HugType::Trait(_)) => {
+                self.visible(name, span);
+                return true;
+            }
+            Some(_) => format!("`{}` is a type, not a trait", self.idents.resolve(name)),
+            None if HugType::builtin(self.idents.resolve(name)).is_some() => {
+                format!("`{}` is a type, not a trait", self.idents.resolve(name))
+            }
+            None => format!("Unknown trait `{}`", self.idents.resolve(name)),
+        };
+        self.error(span, message);
+        false
+    }
+
+    /// Brings the type parameters of a generic function or type into scope, reporting bounds
+    /// that aren't traits. Gives back how many were in scope before, to truncate back to.
+    fn enter_generics(&mut self, generics: &[HugTreeGeneric]) -> usize {
+        for generic in generics {
+            if let Some(bound) = &generic.bound {
+                self.is_trait(bound.name, bound.span);
+            }
+        }
+        let outer = self.generics.len();
+        self.generics.extend(type_parameters(generics));
+        outer
+    }
+
+    /// Starts checking the methods of `impl _type`, the type parameters of `_type` are in scope
+    /// in them.
+    fn enter_impl(&mut self, _type: Ident) -> (Option<Ident>, usize) {
+        let outer = (self.impl_type.replace(_type), self.generics.len());
+        if let Some(HugType::Struct(_, parameters) | HugType::Enum(_, parameters)) =
+            self.globals.types.get(&_type)
+        {
+            self.generics.extend(parameters.iter().cloned());
+        }
+        outer
+    }
+
+    fn leave_impl(&mut self, (impl_type, generics): (Option<Ident>, usize)) {
+        self.impl_type = impl_type;
+        self.generics.truncate(generics);
+    }
+
+    /// Checks everything but the bodies of functions, in the order it runs.
+    fn top_level(&mut self, entries: &mut [HugTreeEntry]) {
+        for entry in entries {
+            match &mut entry.kind {
+                HugTreeEntryKind::FunctionDefinition(_)
+                | HugTreeEntryKind::Implementation { .. } => {}
+                HugTreeEntryKind::ModuleDefinition { module, body } => {
+                    let outer = self.module.replace(*module);
+                    self.top_level(body);
+                    self.module = outer;
+                }
+                _ => self.entry(entry),
+            }
+        }
+    }
+
+    fn functions(&mut self, entries: &mut [HugTreeEntry]) {
+        for entry in entries {
+            match &mut entry.kind {
+                HugTreeEntryKind::FunctionDefinition(function) => {
+                    self.function(function, entry.span)
+                }
+                HugTreeEntryKind::Implementation { _type, methods, .. } => {
+                    let outer = self.enter_impl(*_type);
+                    self.functions(methods);
+                    self.leave_impl(outer);
+                }
+                HugTreeEntryKind::ModuleDefinition { module, body } => {
+                    let outer = self.module.replace(*module);
+                    self.functions(body);
+                    self.module = outer;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Reports using `name` from outside of the module it is private to. The modules on the way
+    /// to an item have to be visible too, `a.b.f` can't be used if `b` is private to `a`.
+    fn visible(&mut self, name: Ident, span: Span) {
+        let name = self.idents.resolve(name);
+        let current = self.module.map_or("", |module| self.idents.resolve(module));
+
+        let ends = name.match_indices('.').skip(1).map(|(end, _)| end);
+        for end in ends.chain([name.len()]) {
+            let item = &name[..end];
+            if !self
+                .idents
+                .get(item)
+                .is_some_and(|item| self.globals.private.contains(&item))
+            {
+                continue;
+            }
+
+            let (mut module, short) = item.rsplit_once('.').unwrap();
+            // A method is private to the module of its type
+            if self
+                .idents
+                .get(module)
+                .is_some_and(|_type| self.globals.types.contains_key(&_type))
+            {
+                module = module.rsplit_once('.').map_or("", |(module, _)| module);
+            }
+            let inside = current
+                .strip_prefix(module)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+            if !inside {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        span,
+                        format!("`{}` is private to module `{}`", short, module),
+                    )
+                    .with_hint(format!(
+                        "mark it `public` to use it outside of `{}`",
+                        module
+                    )),
+                );
+                return;
+            }
+        }
+    }
+
+    fn error(&mut self, span: Span, message: impl Into<String>) -> HugType {
+        self.diagnostics.push(Diagnostic::error(span, message));
+        HugType::Unknown
+    }
+
+    fn name(&self, _type: &HugType) -> String {
+        _type.name(self.idents)
+    }
+
+    /// The type an annotation refers to.
+    fn resolve(&mut self, annotation: &HugTreeType) -> HugType {
+        let name = self.idents.resolve(annotation.name);
+        let parameter = self.generics.iter().rev().find(
+            |parameter| matches!(parameter, HugType::Parameter { name, .. } if *name == annotation.name),
+        );
+        let _type = if let Some(parameter) = parameter {
+            parameter.clone()
+        } else if let Some(builtin) = HugType::builtin(name) {
+            builtin
+        } else {
+            match self.globals.types.get(&annotation.name) {
+                Some(_type) => {
+                    let _type = _type.clone();
+                    self.visible(annotation.name, annotation.span);
+                    _type
+                }
+                None => {
+                    let message = format!("Unknown type `{}`", name);
+                    return self.error(annotation.span, message);
+                }
+            }
+        };
+
+        let (HugType::Struct(_, parameters) | HugType::Enum(_, parameters)) = &_type else {
+            if annotation.args.is_empty() {
+                return _type;
+            }
+            let message = format!("`{}` takes no type arguments", name);
+            return self.error(annotation.span, message);
+        };
+        if annotation.args.len() != parameters.len() {
+            // Inside its own `impl` a generic type can be written without its parameters
+            if annotation.args.is_empty() && self.impl_type == Some(annotation.name) {
+                return _type;
+            }
+            if parameters.is_empty() {
+                let message = format!("`{}` takes no type arguments", name);
+                return self.error(annotation.span, message);
+            }
+            let message = format!(
+                "`{}` takes {} type arguments but {} were given",
+                name,
+                parameters.len(),
+                annotation.args.len()
+            );
+            return self.error(annotation.span, message);
+        }
+
+        let mut args = Vec::new();
+        for (arg, parameter) in annotation.args.iter().zip(parameters) {
+            let _type = self.resolve(arg);
+            if let HugType::Parameter {
+                bound: Some(bound), ..
+            } = parameter
+            {
+                self.satisfies(&_type, *bound, arg.span);
+            }
+            args.push(_type);
+        }
+        match _type {
+            HugType::Struct(name, _) => HugType::Struct(name, args),
+            HugType::Enum(name, _) => HugType::Enum(name, args),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reports `_type` not implementing `bound`, where it is given for a type parameter.
+    fn satisfies(&mut self, _type: &HugType, bound: Ident, span: Span) {
+        if !self.implements(_type, bound) {
+            let message = format!(
+                "`{}` doesn't implement `{}`",
+                self.name(_type),
+                self.idents.resolve(bound)
+            );
+            self.error(span, message);
+        }
+    }
+
+    fn signature(&mut self, function: &HugTreeFunction) -> HugType {
+        let outer = self.enter_generics(&function.generics);
+        let signature = self.function_type(&function.parameters, function.return_type.as_ref());
+        self.generics.truncate(outer);
+        signature
+    }
+
+    fn function_type(
+        &mut self,
+        parameters: &[HugTreeParameter],
+        return_type: Option<&HugTreeType>,
+    ) -> HugType {
+        HugType::Function {
+            parameters: parameters.iter().map(|p| self.resolve(&p._type)).collect(),
+            return_type: Box::new(match return_type {
+                Some(return_type) => self.resolve(return_type),
+                None => HugType::Void,
+            }),
+        }
+    }
+
+    fn function(&mut self, function: &mut HugTreeFunction, span: Span) {
+        let HugType::Function {
+            parameters,
+            return_type,
+        } = self.global(function.name)
+        else {
+            // A global with the same name replaced it, which is reported where it is defined
+            return;
+        };
+
+        let outer = self.generics.len();
+        self.generics.extend(type_parameters(&function.generics));
+        self.locals = vec![HugType::Unknown; function.locals];
+        self.locals[..parameters.len()].clone_from_slice(&parameters);
+        self.return_type = Some(*return_type);
+        self.block(&mut function.body);
+
+        let return_type = self.return_type.take().unwrap();
+        if !matches!(return_type, HugType::Void | HugType::Unknown) && !diverges(&function.body) {
+            let message = format!(
+                "`{}` can end without returning a `{}`",
+                self.idents.resolve(function.name),
+                self.name(&return_type)
+            );
+            self.error(span, message);
+        }
+        self.generics.truncate(outer);
+    }
+
+    fn block(&mut self, entries: &mut [HugTreeEntry]) {
+        for entry in entries {
+            self.entry(entry);
+        }
+    }
+
+    fn entry(&mut self, entry: &mut HugTreeEntry) {
+        match &mut entry.kind {
+            HugTreeEntryKind::Use { .. }
+            | HugTreeEntryKind::ExternalTypeDefinition { .. }
+            | HugTreeEntryKind::TypeDefinition { .. }
+            | HugTreeEntryKind::EnumDefinition { .. }
+            | HugTreeEntryKind::TraitDefinition { .. }
+            | HugTreeEntryKind::ExternalModuleDefinition { .. }
+            | HugTreeEntryKind::ExternalFunctionDefinition { .. }
+            | HugTreeEntryKind::Break
+            | HugTreeEntryKind::Continue => (),
+            HugTreeEntryKind::FunctionDefinition(_)
+            | HugTreeEntryKind::ModuleDefinition { .. }
+            | HugTreeEntryKind::Implementation { .. } => {
+                unreachable!("Functions and modules can only be defined at the top level")
+            }
+            HugTreeEntryKind::VariableDefinition {
+                variable,
+                slot,
+                _type,
+                value,
+                ..
+            } => {
+                let _type = match _type {
+                    Some(annotation) => {
+                        let _type = self.resolve(annotation);
+                        self.expect(value, &_type);
+                        _type
+                    }
+                    None => self.value(value, None),
+                };
+
+                match slot {
+                    Some(slot) => self.locals[*slot] = _type,
+                    None if self.declared.contains_key(variable) => {
+                        let message =
+                            format!("`{}` is defined twice", self.idents.resolve(*variable));
+                        self.error(entry.span, message);
+                    }
+                    None => {
+                        self.globals.values.insert(*variable, _type);
+                    }
+                }
+            }
+            HugTreeEntryKind::Assignment {
+                variable,
+                slot,
+                fields,
+                operator,
+                value,
+            } => {
+                if slot.is_none() && !self.globals.values.contains_key(variable) {
+                    if let Some((global, path)) = self.split_fields(*variable) {
+                        *variable = global;
+                        *fields = path;
+                    }
+                }
+
+                let mut target = match slot {
+                    Some(slot) => self.locals[*slot].clone(),
+                    None => {
+                        self.visible(*variable, entry.span);
+                        self.known_global(*variable, "variable", entry.span)
+                    }
+                };
+                for field in fields.iter() {
+                    target = self.field(target, *field, entry.span);
+                }
+
+                match *operator {
+                    Some(operator) if operator.is_shift() => {
+                        let amount = self.value(value, None);
+                        self.operator(operator, target, amount, entry.span);
+                    }
+                    Some(binary) => {
+                        let found = self.value(value, Some(&target));
+                        let add = match binary == HugTreeBinaryOperator::Add && found == target {
+                            true => self.implementation(&target, "Add"),
+                            false => None,
+                        };
+                        let Some(function) = add else {
+                            self.operator(binary, target, found, entry.span);
+                            return;
+                        };
+
+                        // `a += b` is `a = a.add(b)` on types that implement `Add`
+                        let span = entry.span;
+                        let mut current = HugTreeExpression {
+                            kind: match slot {
+                                Some(slot) => HugTreeExpressionKind::Local {
+                                    variable: *variable,
+                                    slot: *slot,
+                                },
+                                None => HugTreeExpressionKind::Variable(*variable),
+                            },
+                            span,
+                        };
+                        for field in fields.iter() {
+                            current = HugTreeExpression {
+                                kind: HugTreeExpressionKind::Field {
+                                    value: Box::new(current),
+                                    field: *field,
+                                },
+                                span,
+                            };
+                        }
+                        let value_span = value.span;
+                        let added = std::mem::replace(value, placeholder(value_span));
+                        *value = HugTreeExpression {
+                            kind: HugTreeExpressionKind::Call {
+                                function,
+                                args: vec![current, added],
+                            },
+                            span: value_span,
+                        };
+                        *operator = None;
+                    }
+                    None => self.expect(value, &target),
+                }
+            }
+            HugTreeEntryKind::FunctionCall { function, args } => {
+                let Some((value, method)) = self.method_path(*function, entry.span) else {
+                    self.call(*function, args, None, entry.span);
+                    return;
+                };
+
+                entry.kind = HugTreeEntryKind::MethodCall {
+                    value,
+                    method,
+                    args: std::mem::take(args),
+                };
+                self.entry(entry);
+            }
+            HugTreeEntryKind::MethodCall {
+                value,
+                method,
+                args,
+            } => {
+                let (function, _) = self.method_call(value, *method, args, None, entry.span);
+                if let Some(function) = function {
+                    let value = std::mem::replace(value, placeholder(entry.span));
+                    let mut args = std::mem::take(args);
+                    args.insert(0, value);
+                    entry.kind = HugTreeEntryKind::FunctionCall { function, args };
+                }
+            }
+            HugTreeEntryKind::If {
+                condition,
+                body,
+                otherwise,
+            } => {
+                self.expect(condition, &HugType::Bool);
+                self.block(body);
+                if let Some(otherwise) = otherwise {
+                    self.block(otherwise);
+                }
+            }
+            HugTreeEntryKind::While { condition, body } => {
+                self.expect(condition, &HugType::Bool);
+                self.block(body);
+            }
+            HugTreeEntryKind::For {
+                slot,
+                iterable,
+                body,
+                ..
+            } => {
+                self.locals[*slot] = match self.value(iterable, None) {
+                    HugType::Range(item) => *item,
+                    HugType::String => HugType::Char,
+                    HugType::Unknown => HugType::Unknown,
+                    other => {
+                        let message = format!("Cannot iterate over a `{}`", self.name(&other));
+                        self.error(iterable.span, message)
+                    }
+                };
+                self.block(body);
+            }
+            HugTreeEntryKind::Loop { body } | HugTreeEntryKind::Block { body } => self.block(body),
+            HugTreeEntryKind::Match(HugTreeMatch { value, arms }) => {
+                let _type = self.value(value, None);
+                for arm in arms.iter_mut() {
+                    self.pattern(&mut arm.pattern, &_type);
+                    self.block(&mut arm.body);
+                }
+
+                let patterns = arms.iter().map(|arm| &arm.pattern).collect::<Vec<_>>();
+                self.exhaustive(&patterns, &_type, entry.span);
+            }
+            HugTreeEntryKind::Return { value } => {
+                let Some(return_type) = self.return_type.clone() else {
+                    // Returning from the top level stops the script, any value is ignored
+                    if let Some(value) = value {
+                        self.value(value, None);
+                    }
+                    return;
+                };
+
+                match (value, &return_type) {
+                    (Some(value), HugType::Void) => {
+                        let found = self.value(value, None);
+                        if found != HugType::Unknown {
+                            let found = self.name(&found);
+                            self.diagnostics.push(
+                                Diagnostic::error(
+                                    value.span,
+                                    format!("Mismatched types: expected `Void`, found `{}`", found),
+                                )
+                                .with_hint(format!("add a return type: `-> {}`", found)),
+                            );
+                        }
+                    }
+                    (Some(value), _) => self.expect(value, &return_type),
+                    (None, HugType::Void) => (),
+                    (None, _) => {
+                        let message = format!(
+                            "Mismatched types: expected `{}`, found `Void`",
+                            self.name(&return_type)
+                        );
+                        self.error(entry.span, message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Splits a path like `geometry.origin.x` into the global it starts with and the fields
+    /// after it, `None` if no part of it is a global.
+    fn split_fields(&self, path: Ident) -> Option<(Ident, Vec<Ident>)> {
+        let path = self.idents.resolve(path);
+        path.rmatch_indices('.').find_map(|(end, _)| {
+            let global = self.idents.get(&path[..end])?;
+            self.globals.values.contains_key(&global).then(|| {
+                let fields = path[end + 1..]
+                    .split('.')
+                    .map(|field| self.idents.get(field).expect("Fields are identifiers"))
+                    .collect();
+                (global, fields)
+            })
+        })
+    }
+
+    /// The type of `field` of a value of type `_type`.
+    fn field(&mut self, _type: HugType, field: Ident, span: Span) -> HugType {
+        let found = match &_type {
+            HugType::Unknown => return HugType::Unknown,
+            HugType::Struct(name, _) => self.globals.structs[name]
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, declared)| declared.instantiate(&self.type_bindings(&_type))),
+            _ => None,
+        };
+
+        found.unwrap_or_else(|| {
+            let message = format!(
+                "`{}` has no field `{}`",
+                self.name(&_type),
+                self.idents.resolve(field)
+            );
+            self.error(span, message)
+        })
+    }
+
+    /// The type of a global, anything not declared in hug comes from an external module.
+    fn global(&self, variable: Ident) -> HugType {
+        self.globals
+            .values
+            .get(&variable)
+            .cloned()
+            .unwrap_or(HugType::Unknown)
+    }
+
+    /// The type of the global `name`, reports it as an unknown `kind` if nothing declares it.
+    fn known_global(&mut self, name: Ident, kind: &str, span: Span) -> HugType {
+        match self.globals.values.get(&name) {
+            Some(_type) => _type.clone(),
+            None => {
+                let message = format!("Unknown {} `{}`", kind, self.idents.resolve(name));
+                self.error(span, message)
+            }
+        }
+    }
+
+    /// Checks that `expression` has type `expected`, or implements it if it is a trait.
+    fn expect(&mut self, expression: &mut HugTreeExpression, expected: &HugType) {
+        let found = self.value(expression, Some(expected));
+        if self.assignable(&found, expected) {
+            return;
+        }
+
+        let message = match expected {
+            HugType::Trait(_trait) => format!(
+                "`{}` doesn't implement `{}`",
+                self.name(&found),
+                self.idents.resolve(*_trait)
+            ),
+            _ => format!(
+                "Mismatched types: expected `{}`, found `{}`",
+                self.name(expected),
+                self.name(&found)
+            ),
+        };
+        self.error(expression.span, message);
+    }
+
+    /// Whether a value of type `found` can be used where `expected` is. `Unknown` fits anywhere,
+    /// also as a type argument, like in `Option<Unknown>` from an `Option.None` without a hint.
+    fn assignable(&self, found: &HugType, expected: &HugType) -> bool {
+        match (found, expected) {
+            (HugType::Unknown, _) | (_, HugType::Unknown) => true,
+            (_, HugType::Trait(_trait)) => self.implements(found, *_trait),
+            (HugType::Struct(name, args), HugType::Struct(expected, expected_args))
+            | (HugType::Enum(name, args), HugType::Enum(expected, expected_args)) => {
+                name == expected
+                    && args.len() == expected_args.len()
+                    && (args.iter())
+                        .zip(expected_args)
+                        .all(|(found, expected)| self.assignable(found, expected))
+            }
+            (HugType::Range(found), HugType::Range(expected)) => self.assignable(found, expected),
+            _ => found == expected,
+        }
+    }
+
+    /// Whether values of `_type` can be used where `_trait` is expected. The builtin types
+    /// implement the builtin traits for the operators they support.
+    fn implements(&self, _type: &HugType, _trait: Ident) -> bool {
+        let builtin = BUILTIN_TRAITS
+            .iter()
+            .find(|(name, _)| self.idents.get(name) == Some(_trait))
+            .map(|(name, _)| *name);
+        match (_type, builtin) {
+            (HugType::Unknown, _) => true,
+            (HugType::Trait(other), _) => *other == _trait,
+            (HugType::Parameter { bound, .. }, _) => *bound == Some(_trait),
+            (HugType::Struct(name, _) | HugType::Enum(name, _), _) => {
+                self.globals.implementations.contains(&(*name, _trait))
+            }
+            (HugType::Function { .. } | HugType::External(_) | HugType::Void, _) => false,
+            (_, Some("Display")) => true,
+            (_, Some("Add")) => _type.is_numeric() || *_type == HugType::String,
+            (_, Some("Eq")) => !matches!(_type, HugType::Range(_)),
+            _ => false,
+        }
+    }
+
+    /// Whether `_type` implements the builtin trait `_trait`.
+    fn implements_builtin(&self, _type: &HugType, _trait: &str) -> bool {
+        (self.idents.get(_trait)).is_some_and(|_trait| self.implements(_type, _trait))
+    }
+
+    /// The function of `_trait` that `_type` implements it with, `None` if the type doesn't
+    /// have one when the program is checked.
+    fn implementation(&self, _type: &HugType, _trait: &str) -> Option<Ident> {
+        let (_, method) = BUILTIN_TRAITS.iter().find(|(name, _)| *name == _trait)?;
+        let (HugType::Struct(name, _) | HugType::Enum(name, _)) = _type else {
+            return None;
+        };
+        if !self.implements(_type, self.idents.get(_trait)?) {
+            return None;
+        }
+        self.idents
+            .get(&format!("{}.{}", self.idents.resolve(*name), method))
+    }
+
+    /// The type of an expression that has to produce a value. `hint` is the type it should have,
+    /// if known, number literals get that type when they can.
+    fn value(&mut self, expression: &mut HugTreeExpression, hint: Option<&HugType>) -> HugType {
+        match self.expression(expression, hint) {
+            HugType::Void => {
+                self.diagnostics.push(
+                    Diagnostic::error(expression.span, "Expected a value, found `Void`")
+                        .with_hint("functions without a return type don't give back a value"),
+                );
+                HugType::Unknown
+            }
+            other => other,
+        }
+    }
+
+    fn expression(
+        &mut self,
+        expression: &mut HugTreeExpression,
+        hint: Option<&HugType>,
+    ) -> HugType {
+        let span = expression.span;
+        match &mut expression.kind {
+            HugTreeExpressionKind::Variable(variable) => {
+                if let Some((_type, variant)) = self.enum_variant(*variable) {
+                    expression.kind = HugTreeExpressionKind::Variant {
+                        _type,
+                        variant,
+                        payload: HugTreePayload::Unit,
+                    };
+                    return self.expression(expression, hint);
+                }
+
+                let split = match self.globals.values.contains_key(variable) {
+                    true => None,
+                    false => self.split_fields(*variable),
+                };
+                let Some((global, fields)) = split else {
+                    self.visible(*variable, span);
+                    return self.known_global(*variable, "variable", span);
+                };
+
+                // Checked again as the fields of the global
+                let mut value = HugTreeExpression {
+                    kind: HugTreeExpressionKind::Variable(global),
+                    span,
+                };
+                for field in fields {
+                    value = HugTreeExpression {
+                        kind: HugTreeExpressionKind::Field {
+                            value: Box::new(value),
+                            field,
+                        },
+                        span,
+                    };
+                }
+                *expression = value;
+                self.expression(expression, hint)
+            }
+            HugTreeExpressionKind::Local { slot, .. } => self.locals[*slot].clone(),
+            HugTreeExpressionKind::Value(value) => HugType::of(value),
+            HugTreeExpressionKind::Number { literal, value } => {
+                let is_float = HugType::of(value).is_float();
+                let suffix = match hint {
+                    Some(hint) if hint.is_float() == is_float => hint.suffix(),
+                    _ => None,
+                };
+
+                let Some(suffix) = suffix else {
+                    return HugType::of(value);
+                };
+                match parse_number(literal, suffix) {
+                    Ok(value) => {
+                        let _type = HugType::of(&value);
+                        expression.kind = HugTreeExpressionKind::Value(value);
+                        _type
+                    }
+                    Err(error) => self.error(span, error.message),
+                }
+            }
+            HugTreeExpressionKind::FormatString(segments) => {
+                for segment in segments {
+                    if let HugTreeFormatSegment::Expression { expression, .. } = segment {
+                        let _type = self.value(expression, None);
+                        if let Some(function) = self.implementation(&_type, "Display") {
+                            let span = expression.span;
+                            let value = std::mem::replace(expression, placeholder(span));
+                            *expression = HugTreeExpression {
+                                kind: HugTreeExpressionKind::Call {
+                                    function,
+                                    args: vec![value],
+                                },
+                                span,
+                            };
+                        }
+                    }
+                }
+                HugType::String
+            }
+            HugTreeExpressionKind::Call { function, args } => {
+                if let Some((value, method)) = self.method_path(*function, span) {
+                    expression.kind = HugTreeExpressionKind::MethodCall {
+                        value: Box::new(value),
+                        method,
+                        args: std::mem::take(args),
+                    };
+                    return self.expression(expression, hint);
+                }
+                let Some((_type, variant)) = self.enum_variant(*function) else {
+                    return self.call(*function, args, hint, span);
+                };
+
+                expression.kind = HugTreeExpressionKind::Variant {
+                    _type,
+                    variant,
+                    payload: HugTreePayload::Tuple(std::mem::take(args)),
+                };
+                self.expression(expression, hint)
+            }
+            HugTreeExpressionKind::Unary { operator, operand } => {
+                let operand = match operator {
+                    HugTreeUnaryOperator::Not => self.value(operand, Some(&HugType::Bool)),
+                    _ => self.value(operand, hint),
+                };
+
+                let valid = match operator {
+                    HugTreeUnaryOperator::Negate => {
+                        operand.is_signed_integer() || operand.is_float()
+                    }
+                    HugTreeUnaryOperator::Not => operand == HugType::Bool,
+                    HugTreeUnaryOperator::BinaryNot => operand.is_integer(),
+                };
+
+                if valid || operand == HugType::Unknown {
+                    operand
+                } else {
+                    let message = format!(
+                        "Cannot apply `{}` to a `{}`",
+                        operator.symbol(),
+                        self.name(&operand)
+                    );
+                    self.error(span, message)
+                }
+            }
+            HugTreeExpressionKind::Binary {
+                operator: HugTreeBinaryOperator::And | HugTreeBinaryOperator::Or,
+                left,
+                right,
+            } => {
+                self.expect(left, &HugType::Bool);
+                self.expect(right, &HugType::Bool);
+                HugType::Bool
+            }
+            HugTreeExpressionKind::Binary {
+                operator,
+                left,
+                right,
+            } if operator.is_comparison() => {
+                let operator = *operator;
+                let (left, right) = self.operands(left, right, None);
+                let equality = matches!(
+                    operator,
+                    HugTreeBinaryOperator::IsEqualTo | HugTreeBinaryOperator::IsNotEqualTo
+                );
+                let user_type = matches!(
+                    left,
+                    HugType::Struct(..)
+                        | HugType::Enum(..)
+                        | HugType::Trait(_)
+                        | HugType::Parameter { .. }
+                );
+                // Which `eq` a type parameter uses is only known when the program runs
+                let bounded = equality
+                    && left == right
+                    && matches!(left, HugType::Parameter { .. })
+                    && self.implements_builtin(&left, "Eq");
+
+                // `a == b` is `a.eq(b)` and `a != b` is `!a.eq(b)` on types that implement `Eq`
+                let eq = match equality && left == right {
+                    true => self.implementation(&left, "Eq"),
+                    false => None,
+                };
+                if let Some(function) = eq {
+                    operator_call(expression, function);
+                    if operator == HugTreeBinaryOperator::IsNotEqualTo {
+                        let call = std::mem::replace(expression, placeholder(span));
+                        expression.kind = HugTreeExpressionKind::Unary {
+                            operator: HugTreeUnaryOperator::Not,
+                            operand: Box::new(call),
+                        };
+                    }
+                } else if (left != right || user_type)
+                    && !bounded
+                    && left != HugType::Unknown
+                    && right != HugType::Unknown
+                {
+                    let message = format!(
+                        "Cannot compare `{}` to `{}`",
+                        self.name(&left),
+                        self.name(&right)
+                    );
+                    let diagnostic = Diagnostic::error(span, message);
+                    let diagnostic = match &left {
+                        _ if !equality || left != right => diagnostic,
+                        HugType::Struct(..) | HugType::Enum(..) => diagnostic.with_hint(format!(
+                            "implement `Eq` for `{}` to compare it with `{}`",
+                            self.name(&left),
+                            operator.symbol()
+                        )),
+                        HugType::Parameter { .. } => diagnostic.with_hint(format!(
+                            "add the bound `{}: Eq` to compare it with `{}`",
+                            self.name(&left),
+                            operator.symbol()
+                        )),
+                        _ => diagnostic,
+                    };
+                    self.diagnostics.push(diagnostic);
+                }
+                HugType::Bool
+            }
+            HugTreeExpressionKind::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let operator = *operator;
+                let (left, right) = if operator.is_shift() {
+                    (self.value(left, hint), self.value(right, None))
+                } else {
+                    self.operands(left, right, hint)
+                };
+
+                // `a + b` is `a.add(b)` on types that implement `Add`
+                let add = match operator == HugTreeBinaryOperator::Add && left == right {
+                    true => self.implementation(&left, "Add"),
+                    false => None,
+                };
+                if let Some(function) = add {
+                    operator_call(expression, function);
+                    return left;
+                }
+                self.operator(operator, left, right, span)
+            }
+            HugTreeExpressionKind::Range { start, end, .. } => {
+                let (start, end) = self.operands(start, end, None);
+                match (start, end) {
+                    (HugType::Unknown, HugType::Unknown) => HugType::Unknown,
+                    (item, HugType::Unknown) | (HugType::Unknown, item) if item.is_integer() => {
+                        HugType::Range(Box::new(item))
+                    }
+                    (start, end) if start == end && start.is_integer() => {
+                        HugType::Range(Box::new(start))
+                    }
+                    (start, end) => {
+                        let message = format!(
+                            "Ranges need two integers of the same type, found `{}` and `{}`",
+                            self.name(&start),
+                            self.name(&end)
+                        );
+                        self.error(span, message)
+                    }
+                }
+            }
+            HugTreeExpressionKind::Struct { _type, fields } => {
+                let Some((_type, variant)) = self.enum_variant(*_type) else {
+                    return self.struct_literal(*_type, fields, hint, span);
+                };
+
+                expression.kind = HugTreeExpressionKind::Variant {
+                    _type,
+                    variant,
+                    payload: HugTreePayload::Struct(std::mem::take(fields)),
+                };
+                self.expression(expression, hint)
+            }
+            HugTreeExpressionKind::Field { value, field } => {
+                let _type = self.value(value, None);
+                self.field(_type, *field, span)
+            }
+            HugTreeExpressionKind::Variant {
+                _type,
+                variant,
+                payload,
+            } => self.variant(*_type, *variant, payload, hint, span),
+            HugTreeExpressionKind::Match(HugTreeMatch { value, arms }) => {
+                let value = self.value(value, None);
+                let mut result: Option<HugType> = None;
+                for arm in arms.iter_mut() {
+                    self.pattern(&mut arm.pattern, &value);
+                    // Every arm has to give back the type of the first one
+                    match &result {
+                        Some(expected) => self.expect(&mut arm.body, &expected.clone()),
+                        None => result = Some(self.value(&mut arm.body, hint)),
+                    }
+                }
+
+                let patterns = arms.iter().map(|arm| &arm.pattern).collect::<Vec<_>>();
+                self.exhaustive(&patterns, &value, span);
+                result.unwrap_or(HugType::Unknown)
+            }
+            HugTreeExpressionKind::MethodCall {
+                value,
+                method,
+                args,
+            } => {
+                let (function, return_type) = self.method_call(value, *method, args, hint, span);
+                if let Some(function) = function {
+                    let value = std::mem::replace(&mut **value, placeholder(span));
+                    let mut args = std::mem::take(args);
+                    args.insert(0, value);
+                    expression.kind = HugTreeExpressionKind::Call { function, args };
+                }
+                return_type
+            }
+        }
+    }
+
+    /// Splits a call of a path like `origin.length` that isn't a function into the value it
+    /// starts with and the method. `None` if the path doesn't start with a global or variant.
+    fn method_path(&self, path: Ident, span: Span) -> Option<(HugTreeExpression, Ident)> {
+        if self.globals.values.contains_key(&path) {
+            return None;
+        }
+
+        // The value is checked again as a variable, which splits off its fields
+        let (value, method) = match self.split_fields(path) {
+            Some((global, mut fields)) => {
+                let method = fields.pop().unwrap();
+                let mut value = HugTreeExpressionKind::Variable(global);
+                for field in fields {
+                    value = HugTreeExpressionKind::Field {
+                        value: Box::new(HugTreeExpression { kind: value, span }),
+                        field,
+                    };
+                }
+                (value, method)
+            }
+            // `Shape.Empty.area()`
+            None => {
+                let (variant, method) = self.idents.resolve(path).rsplit_once('.')?;
+                let variant = self.idents.get(variant)?;
+                self.enum_variant(variant)?;
+                let method = self.idents.get(method).expect("Methods are identifiers");
+                (HugTreeExpressionKind::Variable(variant), method)
+            }
+        };
+        Some((HugTreeExpression { kind: value, span }, method))
+    }
+
+    /// Checks `value.method(args)`. Gives back the function the method is, `None` if the type of
+    /// `value` isn't known until it runs, and what it returns.
+    fn method_call(
+        &mut self,
+        value: &mut HugTreeExpression,
+        method: Ident,
+        args: &mut [HugTreeExpression],
+        hint: Option<&HugType>,
+        span: Span,
+    ) -> (Option<Ident>, HugType) {
+        let _type = self.value(value, None);
+        let name = self.idents.resolve(method);
+        // Methods of a trait are looked up on the type of the value when the program runs
+        let (function, signature, takes_self) = match &_type {
+            HugType::Struct(type_name, _) | HugType::Enum(type_name, _) => {
+                match self
+                    .idents
+                    .get(&format!("{}.{}", self.idents.resolve(*type_name), name))
+                    .filter(|function| self.globals.values.contains_key(function))
+                {
+                    Some(function) => {
+                        self.visible(function, span);
+                        let takes_self = self.globals.methods.contains(&function);
+                        (Some(function), Some(self.global(function)), takes_self)
+                    }
+                    None => (None, None, false),
+                }
+            }
+            HugType::Trait(_trait)
+            | HugType::Parameter {
+                bound: Some(_trait),
+                ..
+            } => {
+                let signature = (self.globals.traits.get(_trait).into_iter().flatten())
+                    .find(|(required, _)| required == name)
+                    .map(|(_, signature)| signature.substitute(&HugType::Trait(*_trait), &_type));
+                let takes_self = matches!(
+                    &signature,
+                    Some(HugType::Function { parameters, .. })
+                        if parameters.first() == Some(&_type)
+                );
+                (None, signature, takes_self)
+            }
+            _ => (None, None, false),
+        };
+
+        let Some(HugType::Function {
+            parameters,
+            return_type,
+        }) = signature
+        else {
+            for arg in args.iter_mut() {
+                self.value(arg, None);
+            }
+            if _type == HugType::Unknown {
+                return (None, HugType::Unknown);
+            }
+            let message = format!("`{}` has no method `{}`", self.name(&_type), name);
+            return (None, self.error(span, message));
+        };
+
+        if !takes_self {
+            let type_name = match &_type {
+                HugType::Struct(type_name, _) | HugType::Enum(type_name, _) => {
+                    self.idents.resolve(*type_name).to_string()
+                }
+                other => self.name(other),
+            };
+            let message = format!(
+                "`{}` doesn't take `self`, call it as `{}.{}(...)`",
+                name, type_name, name
+            );
+            return (None, self.error(span, message));
+        }
+
+        // The type parameters of a generic type are known from the value
+        let bindings = self.type_bindings(&_type);
+        let return_type = self.arguments(
+            name,
+            &parameters[1..],
+            &return_type,
+            args,
+            bindings,
+            hint,
+            span,
+        );
+        (function, return_type)
+    }
+
+    /// Splits a path like `Shape.Circle` into the enum and the variant, `None` if it doesn't
+    /// start with an enum.
+    fn enum_variant(&self, path: Ident) -> Option<(Ident, Ident)> {
+        if self.globals.values.contains_key(&path) {
+            return None;
+        }
+
+        let (_type, variant) = self.idents.resolve(path).rsplit_once('.')?;
+        let _type = self.idents.get(_type)?;
+        let variant = self.idents.get(variant)?;
+        self.globals
+            .enums
+            .contains_key(&_type)
+            .then_some((_type, variant))
+    }
+
+    /// The variant `_type.variant`, reports it if there is no such variant.
+    fn declared_variant(
+        &mut self,
+        _type: Ident,
+        variant: Ident,
+        span: Span,
+    ) -> Option<HugTreePayload<HugType>> {
+        self.visible(_type, span);
+        let declared = self.globals.enums[&_type]
+            .iter()
+            .find(|(name, _)| *name == variant)
+            .map(|(_, payload)| payload.clone());
+        if declared.is_none() {
+            let message = format!(
+                "`{}` has no variant `{}`",
+                self.idents.resolve(_type),
+                self.idents.resolve(variant)
+            );
+            self.error(span, message);
+        }
+        declared
+    }
+
+    /// Reports a variant written with a different kind of payload than it was declared with.
+    fn payload_mismatch<T, U>(
+        &mut self,
+        name: &str,
+        declared: &HugTreePayload<T>,
+        found: &HugTreePayload<U>,
+        span: Span,
+    ) -> bool {
+        let message = match (declared, found) {
+            (HugTreePayload::Unit, HugTreePayload::Unit)
+            | (HugTreePayload::Tuple(_), HugTreePayload::Tuple(_))
+            | (HugTreePayload::Struct(_), HugTreePayload::Struct(_)) => return false,
+            (HugTreePayload::Unit, _) => format!("`{}` carries no values", name),
+            (HugTreePayload::Tuple(_), _) => {
+                format!("`{}` needs values, like `{}(...)`", name, name)
+            }
+            (HugTreePayload::Struct(_), _) => {
+                format!("`{}` needs fields, like `{} {{ ... }}`", name, name)
+            }
+        };
+        self.error(span, message);
+        true
+    }
+
+    /// What a variant of the enum `_type` carries, with the type parameters of a generic enum
+    /// replaced by the types given for them.
+    fn payload(
+        &self,
+        declared: &HugTreePayload<HugType>,
+        _type: &HugType,
+    ) -> HugTreePayload<HugType> {
+        let bindings = self.type_bindings(_type);
+        let mut payload = declared.clone();
+        for declared in payload.iter_mut() {
+            *declared = declared.instantiate(&bindings);
+        }
+        payload
+    }
+
+    /// Checks building the variant `_type.variant` with `payload`. The type parameters of a
+    /// generic enum are inferred from the payload, or taken from `hint`.
+    fn variant(
+        &mut self,
+        _type: Ident,
+        variant: Ident,
+        payload: &mut HugTreePayload<HugTreeExpression>,
+        hint: Option<&HugType>,
+        span: Span,
+    ) -> HugType {
+        let name = format!(
+            "{}.{}",
+            self.idents.resolve(_type),
+            self.idents.resolve(variant)
+        );
+        let mut bindings = self.hinted_bindings(_type, hint);
+        let declared = self.declared_variant(_type, variant, span);
+        let mismatched = match &declared {
+            Some(declared) => self.payload_mismatch(&name, declared, payload, span),
+            None => true,
+        };
+        if mismatched {
+            for value in payload.iter_mut() {
+                self.value(value, None);
+            }
+            return self.instance(_type, &mut bindings, span);
+        }
+
+        match (declared.unwrap(), payload) {
+            (HugTreePayload::Tuple(types), HugTreePayload::Tuple(values)) => {
+                if types.len() != values.len() {
+                    let message = format!(
+                        "`{}` takes {} values but {} were given",
+                        name,
+                        types.len(),
+                        values.len()
+                    );
+                    self.error(span, message);
+                }
+                for (value, _type) in values.iter_mut().zip(&types) {
+                    self.infer(value, _type, &mut bindings);
+                }
+                for value in values.iter_mut().skip(types.len()) {
+                    self.value(value, None);
+                }
+            }
+            (HugTreePayload::Struct(declared), HugTreePayload::Struct(fields)) => {
+                self.fields(&name, &declared, fields, &mut bindings, span);
+            }
+            _ => (),
+        }
+        self.instance(_type, &mut bindings, span)
+    }
+
+    /// What the type parameters of the generic struct or enum `_type` are in `hint`, when it is
+    /// that type.
+    fn hinted_bindings(&self, _type: Ident, hint: Option<&HugType>) -> HashMap<Ident, HugType> {
+        let mut bindings = match hint {
+            Some(hint @ (HugType::Struct(name, _) | HugType::Enum(name, _))) if *name == _type => {
+                self.type_bindings(hint)
+            }
+            _ => HashMap::new(),
+        };
+        bindings.retain(|_, arg| *arg != HugType::Unknown);
+        bindings
+    }
+
+    /// The struct or enum `_type` with the types in `bindings` given for its type parameters.
+    fn instance(
+        &mut self,
+        _type: Ident,
+        bindings: &mut HashMap<Ident, HugType>,
+        span: Span,
+    ) -> HugType {
+        let declared = self.globals.types[&_type].clone();
+        self.bind(&declared.parameters(), bindings, span);
+        declared.instantiate(bindings)
+    }
+
+    /// Checks the fields of `Type { field: value, ... }` and puts them in the declared order.
+    fn struct_literal(
+        &mut self,
+        _type: Ident,
+        fields: &mut [(Ident, HugTreeExpression)],
+        hint: Option<&HugType>,
+        span: Span,
+    ) -> HugType {
+        let name = self.idents.resolve(_type);
+        let Some(declared) = self.globals.structs.get(&_type).cloned() else {
+            for (_, value) in fields.iter_mut() {
+                self.value(value, None);
+            }
+            let is_type =
+                self.globals.types.contains_key(&_type) || HugType::builtin(name).is_some();
+            let message = match is_type {
+                true => format!("`{}` is not a struct", name),
+                false => format!("Unknown type `{}`", name),
+            };
+            return self.error(span, message);
+        };
+        self.visible(_type, span);
+        let mut bindings = self.hinted_bindings(_type, hint);
+        self.fields(name, &declared, fields, &mut bindings, span);
+        self.instance(_type, &mut bindings, span)
+    }
+
+    /// Checks the fields given to the struct or variant `name` against the `declared` ones and
+    /// puts them in the declared order. Type parameters of a generic type are inferred into
+    /// `bindings`.
+    fn fields(
+        &mut self,
+        name: &str,
+        declared: &[(Ident, HugType)],
+        fields: &mut [(Ident, HugTreeExpression)],
+        bindings: &mut HashMap<Ident, HugType>,
+        span: Span,
+    ) {
+        for i in 0..fields.len() {
+            let (field, value) = &mut fields[i];
+            match declared.iter().find(|(name, _)| name == field) {
+                Some((_, _type)) => self.infer(value, _type, bindings),
+                None => {
+                    self.value(value, None);
+                    let message =
+                        format!("`{}` has no field `{}`", name, self.idents.resolve(*field));
+                    self.error(value.span, message);
+                }
+            }
+
+            let (field, value) = &fields[i];
+            if fields[..i].iter().any(|(earlier, _)| earlier == field) {
+                let message = format!("Field `{}` is given twice", self.idents.resolve(*field));
+                self.error(value.span, message);
+            }
+        }
+
+        let missing = declared
+            .iter()
+            .filter(|(name, _)| !fields.iter().any(|(field, _)| field == name))
+            .map(|(name, _)| format!("`{}`", self.idents.resolve(*name)))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            let message = format!(
+                "Missing field{} {} in `{}`",
+                plural,
+                missing.join(", "),
+                name
+            );
+            self.error(span, message);
+        }
+
+        fields.sort_by_key(|(field, _)| declared.iter().position(|(name, _)| name == field));
+    }
+
+    /// Checks a pattern that matches values of type `_type` and gives the names it binds their
+    /// types.
+    fn pattern(&mut self, pattern: &mut HugTreePattern, _type: &HugType) {
+        let span = pattern.span;
+        match &mut pattern.kind {
+            HugTreePatternKind::Wildcard => (),
+            HugTreePatternKind::Binding { slot, .. } => self.locals[*slot] = _type.clone(),
+            HugTreePatternKind::Literal(value) => self.expect(value, _type),
+            HugTreePatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.expect(start, _type);
+                self.expect(end, _type);
+                if !_type.is_integer() && !matches!(_type, HugType::Char | HugType::Unknown) {
+                    let message = format!(
+                        "Ranges in patterns need integers or characters, found `{}`",
+                        self.name(_type)
+                    );
+                    self.error(span, message);
+                    return;
+                }
+
+                let (Some(first), Some(last)) = (literal_value(start), literal_value(end)) else {
+                    return;
+                };
+                let empty = match first.compare(last) {
+                    Some(Ordering::Greater) => true,
+                    Some(Ordering::Equal) => !*inclusive,
+                    _ => false,
+                };
+                if empty {
+                    let operator = if *inclusive { "..=" } else { ".." };
+                    let message = format!(
+                        "Range `{}{}{}` is empty, it matches nothing",
+                        first, operator, last
+                    );
+                    self.error(span, message);
+                }
+            }
+            HugTreePatternKind::Variant {
+                _type: name,
+                variant,
+                payload,
+            } => {
+                let declared = match self.globals.enums.contains_key(name) {
+                    true => self.declared_variant(*name, *variant, span),
+                    false => {
+                        let is_type = self.globals.types.contains_key(name)
+                            || HugType::builtin(self.idents.resolve(*name)).is_some();
+                        let message = match is_type {
+                            true => format!("`{}` is not an enum", self.idents.resolve(*name)),
+                            false => format!("Unknown enum `{}`", self.idents.resolve(*name)),
+                        };
+                        self.error(span, message);
+                        None
+                    }
+                };
+
+                let matches_type = matches!(_type, HugType::Enum(found, _) if found == name);
+                if declared.is_some() && !matches_type && *_type != HugType::Unknown {
+                    let message = format!(
+                        "Mismatched types: expected `{}`, found `{}`",
+                        self.name(_type),
+                        self.idents.resolve(*name)
+                    );
+                    self.error(span, message);
+                }
+                let declared = declared.map(|declared| self.payload(&declared, _type));
+
+                let variant_name = format!(
+                    "{}.{}",
+                    self.idents.resolve(*name),
+                    self.idents.resolve(*variant)
+                );
+                let mismatched = match &declared {
+                    Some(declared) => self.payload_mismatch(&variant_name, declared, payload, span),
+                    None => true,
+                };
+                if mismatched {
+                    for pattern in payload.iter_mut() {
+                        self.pattern(pattern, &HugType::Unknown);
+                    }
+                    return;
+                }
+
+                match (declared.unwrap(), payload) {
+                    (HugTreePayload::Tuple(types), HugTreePayload::Tuple(patterns)) => {
+                        if types.len() != patterns.len() {
+                            let message = format!(
+                                "`{}` has {} values but the pattern has {}",
+                                variant_name,
+                                types.len(),
+                                patterns.len()
+                            );
+                            self.error(span, message);
+                        }
+                        for (i, pattern) in patterns.iter_mut().enumerate() {
+                            let _type = types.get(i).cloned().unwrap_or(HugType::Unknown);
+                            self.pattern(pattern, &_type);
+                        }
+                    }
+                    (HugTreePayload::Struct(fields), HugTreePayload::Struct(patterns)) => {
+                        for (field, pattern) in patterns.iter_mut() {
+                            let found = fields.iter().find(|(name, _)| name == field);
+                            let _type = match found {
+                                Some((_, _type)) => _type.clone(),
+                                None => {
+                                    let message = format!(
+                                        "`{}` has no field `{}`",
+                                        variant_name,
+                                        self.idents.resolve(*field)
+                                    );
+                                    self.error(pattern.span, message)
+                                }
+                            };
+                            self.pattern(pattern, &_type);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Reports a `match` on a value of type `_type` that has no arm for some values.
+    fn exhaustive(&mut self, patterns: &[&HugTreePattern], _type: &HugType, span: Span) {
+        if *_type == HugType::Unknown {
+            return;
+        }
+
+        let rows = patterns
+            .iter()
+            .map(|pattern| vec![self.simplify(pattern, _type)])
+            .collect::<Vec<_>>();
+        let Some(mut witness) = self.uncovered(&rows, std::slice::from_ref(_type)) else {
+            return;
+        };
+
+        let witness = witness.remove(0);
+        let diagnostic = match witness.as_str() {
+            "_" => Diagnostic::error(
+                span,
+                format!("Non-exhaustive `match` on a `{}`", self.name(_type)),
+            )
+            .with_hint("add a `_ =>` arm for the values no other arm covers"),
+            _ => Diagnostic::error(
+                span,
+                format!("Non-exhaustive `match`, `{}` is not covered", witness),
+            )
+            .with_hint("add an arm for it, or a `_ =>` arm"),
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The values of a type that has a known set of them, `true` and `false` or the variants of
+    /// an enum. Each is named and has the types of what it carries.
+    fn constructors(&self, _type: &HugType) -> Option<Vec<(String, HugTreePayload<HugType>)>> {
+        match _type {
+            HugType::Bool => Some(vec![
+                ("true".to_string(), HugTreePayload::Unit),
+                ("false".to_string(), HugTreePayload::Unit),
+            ]),
+            HugType::Enum(name, _) => {
+                let variants = self.globals.enums[name].iter().map(|(variant, payload)| {
+                    let variant = format!(
+                        "{}.{}",
+                        self.idents.resolve(*name),
+                        self.idents.resolve(*variant)
+                    );
+                    (variant, self.payload(payload, _type))
+                });
+                Some(variants.collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// A pattern reduced to what matters for exhaustiveness. Patterns with errors match
+    /// anything, so they don't cause more errors.
+    fn simplify(&self, pattern: &HugTreePattern, _type: &HugType) -> Simple {
+        match &pattern.kind {
+            HugTreePatternKind::Wildcard | HugTreePatternKind::Binding { .. } => Simple::Anything,
+            HugTreePatternKind::Literal(HugTreeExpression {
+                kind: HugTreeExpressionKind::Value(HugValue::Bool(value)),
+                ..
+            }) => Simple::Constructor(if *value { 0 } else { 1 }, Vec::new()),
+            HugTreePatternKind::Literal(value) => match integer_literal(value, _type) {
+                Some(value) => Simple::Interval(value, value),
+                None => Simple::Value,
+            },
+            HugTreePatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => match (integer_literal(start, _type), integer_literal(end, _type)) {
+                (Some(start), Some(end)) if *inclusive => Simple::Interval(start, end),
+                (Some(start), Some(end)) => end
+                    .checked_sub(1)
+                    .map_or(Simple::Value, |last| Simple::Interval(start, last)),
+                _ => Simple::Value,
+            },
+            HugTreePatternKind::Variant {
+                _type: name,
+                variant,
+                payload,
+            } => {
+                if !matches!(_type, HugType::Enum(found, _) if found == name) {
+                    return Simple::Anything;
+                }
+                let variants = &self.globals.enums[name];
+                let Some(index) = variants.iter().position(|(name, _)| name == variant) else {
+                    return Simple::Anything;
+                };
+
+                let declared = self.payload(&variants[index].1, _type);
+                let subpatterns = match (&declared, payload) {
+                    (HugTreePayload::Unit, HugTreePayload::Unit) => Vec::new(),
+                    (HugTreePayload::Tuple(types), HugTreePayload::Tuple(patterns))
+                        if types.len() == patterns.len() =>
+                    {
+                        patterns
+                            .iter()
+                            .zip(types)
+                            .map(|(pattern, _type)| self.simplify(pattern, _type))
+                            .collect()
+                    }
+                    (HugTreePayload::Struct(fields), HugTreePayload::Struct(patterns)) => fields
+                        .iter()
+                        .map(|(field, _type)| {
+                            match patterns.iter().find(|(name, _)| name == field) {
+                                Some((_, pattern)) => self.simplify(pattern, _type),
+                                None => Simple::Anything,
+                            }
+                        })
+                        .collect(),
+                    _ => return Simple::Anything,
+                };
+                Simple::Constructor(index, subpatterns)
+            }
+        }
+    }
+
+    /// Finds values of `types` that none of the `rows` of patterns match, and writes one of them
+    /// like a pattern. `None` when the rows cover everything.
+    fn uncovered(&self, rows: &[Vec<Simple>], types: &[HugType]) -> Option<Vec<String>> {
+        let Some((first, rest)) = types.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        if rows.is_empty() {
+            return Some(vec!["_".to_string(); types.len()]);
+        }
+
+        let constructors = self.constructors(first);
+        let used = |index: usize| {
+            rows.iter()
+                .any(|row| matches!(&row[0], Simple::Constructor(i, _) if *i == index))
+        };
+
+        // Every constructor has an arm, so each of them is checked with what it carries
+        if let Some(constructors) = constructors
+            .as_ref()
+            .filter(|constructors| (0..constructors.len()).all(used))
+        {
+            for (index, (name, payload)) in constructors.iter().enumerate() {
+                let arity = payload.iter().count();
+                let specialized = rows
+                    .iter()
+                    .filter_map(|row| {
+                        let head = match &row[0] {
+                            Simple::Anything => vec![Simple::Anything; arity],
+                            Simple::Constructor(i, subpatterns) if *i == index => {
+                                subpatterns.clone()
+                            }
+                            _ => return None,
+                        };
+                        Some(head.into_iter().chain(row[1..].iter().cloned()).collect())
+                    })
+                    .collect::<Vec<_>>();
+                let types = payload
+                    .iter()
+                    .cloned()
+                    .chain(rest.iter().cloned())
+                    .collect::<Vec<_>>();
+
+                if let Some(mut witness) = self.uncovered(&specialized, &types) {
+                    let values = witness.drain(..arity).collect::<Vec<_>>();
+                    witness.insert(0, self.write_constructor(name, payload, values));
+                    return Some(witness);
+                }
+            }
+            return None;
+        }
+
+        // Integers are split where the ranges of the arms start and end, so each piece is either
+        // in a range or not
+        let has_intervals = rows
+            .iter()
+            .any(|row| matches!(row[0], Simple::Interval(..)));
+        if let Some((min, max)) = first.integer_bounds().filter(|_| has_intervals) {
+            let mut starts = vec![min];
+            for row in rows {
+                if let Simple::Interval(start, end) = row[0] {
+                    starts.push(start);
+                    if end < max {
+                        starts.push(end + 1);
+                    }
+                }
+            }
+            starts.sort_unstable();
+            starts.dedup();
+
+            for (i, &start) in starts.iter().enumerate() {
+                let end = starts.get(i + 1).map_or(max, |next| next - 1);
+                let specialized = rows
+                    .iter()
+                    .filter(|row| match row[0] {
+                        Simple::Anything => true,
+                        Simple::Interval(first, last) => first <= start && end <= last,
+                        _ => false,
+                    })
+                    .map(|row| row[1..].to_vec())
+                    .collect::<Vec<_>>();
+
+                if let Some(mut witness) = self.uncovered(&specialized, rest) {
+                    let piece = match start == end {
+                        true => start.to_string(),
+                        false => format!("{}..={}", start, end),
+                    };
+                    witness.insert(0, piece);
+                    return Some(witness);
+                }
+            }
+            return None;
+        }
+
+        // Otherwise only the arms that match anything cover the missing values
+        let defaults = rows
+            .iter()
+            .filter(|row| matches!(row[0], Simple::Anything))
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<_>>();
+        let mut witness = self.uncovered(&defaults, rest)?;
+        let head = match constructors {
+            Some(constructors) => {
+                let index = (0..constructors.len()).find(|i| !used(*i)).unwrap();
+                let (name, payload) = &constructors[index];
+                let values = vec!["_".to_string(); payload.iter().count()];
+                self.write_constructor(name, payload, values)
+            }
+            None => "_".to_string(),
+        };
+        witness.insert(0, head);
+        Some(witness)
+    }
+
+    fn write_constructor(
+        &self,
+        name: &str,
+        payload: &HugTreePayload<HugType>,
+        values: Vec<String>,
+    ) -> String {
+        match payload {
+            HugTreePayload::Unit => name.to_string(),
+            HugTreePayload::Tuple(_) => format!("{}({})", name, values.join(", ")),
+            HugTreePayload::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .zip(values)
+                    .map(|((field, _), value)| {
+                        format!("{}: {}", self.idents.resolve(*field), value)
+                    })
+                    .collect::<Vec<_>>();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+        }
+    }
+
+    /// Types the operands of an operator that needs two values of the same type. A number literal
+    /// on the left takes the type of the right side, `1 + x` is fine when `x` is an `Int64`.
+    fn operands(
+        &mut self,
+        left: &mut HugTreeExpression,
+        right: &mut HugTreeExpression,
+        hint: Option<&HugType>,
+    ) -> (HugType, HugType) {
+        if matches!(left.kind, HugTreeExpressionKind::Number { .. })
+            && !matches!(right.kind, HugTreeExpressionKind::Number { .. })
+        {
+            let right = self.value(right, hint);
+            (self.value(left, Some(&right)), right)
+        } else {
+            let left = self.value(left, hint);
+            let right = match left {
+                HugType::Unknown => self.value(right, hint),
+                _ => self.value(right, Some(&left)),
+            };
+            (left, right)
+        }
+    }
+
+    /// The result of an arithmetic or bitwise operator.
+    fn operator(
+        &mut self,
+        operator: HugTreeBinaryOperator,
+        left: HugType,
+        right: HugType,
+        span: Span,
+    ) -> HugType {
+        if left == HugType::Unknown || right == HugType::Unknown {
+            return HugType::Unknown;
+        }
+
+        let valid = match operator {
+            _ if operator.is_shift() => left.is_integer() && right.is_integer(),
+            _ if left != right => false,
+            // Which `add` a type parameter uses is only known when the program runs
+            HugTreeBinaryOperator::Add if matches!(left, HugType::Parameter { .. }) => {
+                self.implements_builtin(&left, "Add")
+            }
+            HugTreeBinaryOperator::Add => left.is_numeric() || left == HugType::String,
+            HugTreeBinaryOperator::BinaryAnd
+            | HugTreeBinaryOperator::BinaryOr
+            | HugTreeBinaryOperator::BinaryXOr => left.is_integer() || left == HugType::Bool,
+            _ => left.is_numeric(),
+        };
+
+        if valid {
+            return left;
+        }
+
+        let message = format!(
+            "Cannot apply `{}` to `{}` and `{}`",
+            operator.symbol(),
+            self.name(&left),
+            self.name(&right)
+        );
+        let diagnostic = Diagnostic::error(span, message);
+        let diagnostic = match &left {
+            _ if operator != HugTreeBinaryOperator::Add || left != right => diagnostic,
+            HugType::Struct(..) | HugType::Enum(..) => diagnostic.with_hint(format!(
+                "implement `Add` for `{}` to use `+` on it",
+                self.name(&left)
+            )),
+            HugType::Parameter { .. } => diagnostic.with_hint(format!(
+                "add the bound `{}: Add` to use `+` on it",
+                self.name(&left)
+            )),
+            _ => diagnostic,
+        };
+        self.diagnostics.push(diagnostic);
+        HugType::Unknown
+    }
+
+    /// Checks the arguments of a call and gives back what the call returns. `hint` is the type
+    /// the result should have, it is used for type parameters no argument shows.
+    fn call(
+        &mut self,
+        function: Ident,
+        args: &mut [HugTreeExpression],
+        hint: Option<&HugType>,
+        span: Span,
+    ) -> HugType {
+        self.visible(function, span);
+        let (parameters, return_type) = match self.known_global(function, "function", span) {
+            HugType::Function {
+                parameters,
+                return_type,
+            } => (parameters, *return_type),
+            HugType::Unknown => {
+                for arg in args {
+                    self.value(arg, None);
+                }
+                return HugType::Unknown;
+            }
+            other => {
+                let message = format!(
+                    "`{}` is not a function, it is a `{}`",
+                    self.idents.resolve(function),
+                    self.name(&other)
+                );
+                return self.error(span, message);
+            }
+        };
+
+        let name = self.idents.resolve(function);
+        let bindings = HashMap::new();
+        self.arguments(name, &parameters, &return_type, args, bindings, hint, span)
+    }
+
+    /// Checks the `args` given to `name` against its `parameters`. The type parameters in them
+    /// are inferred from the arguments, on top of the ones in `bindings`, and `return_type` is
+    /// given back with them filled in.
+    #[allow(clippy::too_many_arguments)]
+    fn arguments(
+        &mut self,
+        name: &str,
+        parameters: &[HugType],
+        return_type: &HugType,
+        args: &mut [HugTreeExpression],
+        mut bindings: HashMap<Ident, HugType>,
+        hint: Option<&HugType>,
+        span: Span,
+    ) -> HugType {
+        if parameters.len() != args.len() {
+            let message = format!(
+                "`{}` takes {} arguments but {} were given",
+                name,
+                parameters.len(),
+                args.len()
+            );
+            self.error(span, message);
+        }
+
+        for (arg, parameter) in args.iter_mut().zip(parameters) {
+            self.infer(arg, parameter, &mut bindings);
+        }
+        if let Some(hint) = hint {
+            self.unify(return_type, hint, &mut bindings);
+        }
+
+        for (parameter, _) in return_type.parameters() {
+            if !bindings.contains_key(&parameter) {
+                let message = format!(
+                    "Cannot infer the type of `{}` in this call of `{}`",
+                    self.idents.resolve(parameter),
+                    name
+                );
+                self.diagnostics.push(
+                    Diagnostic::error(span, message)
+                        .with_hint("give the result a type, like `let x: Type = ...`"),
+                );
+            }
+        }
+        let signature = HugType::Function {
+            parameters: parameters.to_vec(),
+            return_type: Box::new(return_type.clone()),
+        };
+        self.bind(&signature.parameters(), &mut bindings, span);
+        return_type.instantiate(&bindings)
+    }
+
+    /// Checks `value` against `declared`, which can have type parameters that aren't known yet.
+    /// The ones the type of `value` shows are added to `bindings`.
+    fn infer(
+        &mut self,
+        value: &mut HugTreeExpression,
+        declared: &HugType,
+        bindings: &mut HashMap<Ident, HugType>,
+    ) {
+        let known = (declared.parameters().iter()).all(|(name, _)| bindings.contains_key(name));
+        if known {
+            return self.expect(value, &declared.instantiate(bindings));
+        }
+
+        let found = self.value(value, None);
+        if !self.unify(declared, &found, bindings) {
+            let message = format!(
+                "Mismatched types: expected `{}`, found `{}`",
+                self.name(&declared.instantiate(bindings)),
+                self.name(&found)
+            );
+            self.error(value.span, message);
+        }
+    }
+
+    /// Matches `found` against `declared` and binds the type parameters in `declared` to what
+    /// they are in `found`. False if the types don't fit.
+    fn unify(
+        &self,
+        declared: &HugType,
+        found: &HugType,
+        bindings: &mut HashMap<Ident, HugType>,
+    ) -> bool {
+        match (declared, found) {
+            (_, HugType::Unknown) => true,
+            (HugType::Parameter { name, .. }, _) => match bindings.get(name) {
+                Some(bound) => self.assignable(found, bound),
+                None => {
+                    bindings.insert(*name, found.clone());
+                    true
+                }
+            },
+            (HugType::Struct(name, args), HugType::Struct(found, found_args))
+            | (HugType::Enum(name, args), HugType::Enum(found, found_args)) => {
+                name == found
+                    && args.len() == found_args.len()
+                    && (args.iter())
+                        .zip(found_args)
+                        .all(|(declared, found)| self.unify(declared, found, bindings))
+            }
+            (
+                HugType::Function {
+                    parameters,
+                    return_type,
+                },
+                HugType::Function {
+                    parameters: found_parameters,
+                    return_type: found_return_type,
+                },
+            ) => {
+                parameters.len() == found_parameters.len()
+                    && (parameters.iter())
+                        .zip(found_parameters)
+                        .all(|(declared, found)| self.unify(declared, found, bindings))
+                    && self.unify(return_type, found_return_type, bindings)
+            }
+            (HugType::Range(declared), HugType::Range(found)) => {
+                self.unify(declared, found, bindings)
+            }
+            _ => self.assignable(found, declared),
+        }
+    }
+
+    /// Reports types given for `parameters` that don't implement their bound. Parameters nothing
+    /// was given for are `Unknown`.
+    fn bind(
+        &mut self,
+        parameters: &[(Ident, Option<Ident>)],
+        bindings: &mut HashMap<Ident, HugType>,
+        span: Span,
+    ) {
+        for (parameter, bound) in parameters {
+            let _type = bindings
+                .entry(*parameter)
+                .or_insert(HugType::Unknown)
+                .clone();
+            if let Some(bound) = bound {
+                self.satisfies(&_type, *bound, span);
+            }
+        }
+    }
+
+    /// What the type parameters of the generic struct or enum are in `_type`.
+    fn type_bindings(&self, _type: &HugType) -> HashMap<Ident, HugType> {
+        let (HugType::Struct(name, args) | HugType::Enum(name, args)) = _type else {
+            return HashMap::new();
+        };
+        let Some(HugType::Struct(_, parameters) | HugType::Enum(_, parameters)) =
+            self.globals.types.get(name)
+        else {
+            return HashMap::new();
+        };
+        (parameters.iter())
+            .zip(args)
+            .filter_map(|(parameter, arg)| match parameter {
+                HugType::Parameter { name, .. } => Some((*name, arg.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Replaces `expression`, a binary operator, with a call of `function` on its two operands.
+fn operator_call(expression: &mut HugTreeExpression, function: Ident) {
+    let kind = std::mem::replace(&mut expression.kind, placeholder(expression.span).kind);
+    let HugTreeExpressionKind::Binary { left, right, .. } = kind else {
+        unreachable!("Only binary operators are turned into calls");
+    };
+    expression.kind = HugTreeExpressionKind::Call {
+        function,
+        args: vec![*left, *right],
+    };
+}
+
+/// Whether control never gets past the end of `entries`, because every way through them returns.
+fn diverges(entries: &[HugTreeEntry]) -> bool {
+    entries.iter().any(|entry| match &entry.kind {
+        HugTreeEntryKind::Return { .. } => true,
+        HugTreeEntryKind::If {
+            body,
+            otherwise: Some(otherwise),
+            ..
+        } => diverges(body) && diverges(otherwise),
+        HugTreeEntryKind::Match(HugTreeMatch { arms, .. }) => {
+            arms.iter().all(|arm| diverges(&arm.body))
+        }
+        HugTreeEntryKind::Block { body } => diverges(body),
+        HugTreeEntryKind::Loop { body } => !breaks(body),
+        _ => false,
+    })
+}
+
+/// Whether a `break` in `entries` can end the loop they are the body of. Loops nested in them
+/// have their own `break`s.
+fn breaks(entries: &[HugTreeEntry]) -> bool {
+    entries.iter().any(|entry| match &entry.kind {
+        HugTreeEntryKind::Break => true,
+        HugTreeEntryKind::If {
+            body, otherwise, ..
+        } => breaks(body) || otherwise.as_deref().is_some_and(breaks),
+        HugTreeEntryKind::Match(HugTreeMatch { arms, .. }) => {
+            arms.iter().any(|arm| breaks(&arm.body))
+        }
+        HugTreeEntryKind::Block { body } => breaks(body),
+        _ => false,
+    })
+}
+
+/// The types the type parameters of a generic function or type stand for inside of it.
+fn type_parameters(generics: &[HugTreeGeneric]) -> Vec<HugType> {
+    generics
+        .iter()
+        .map(|generic| HugType::Parameter {
+            name: generic.name,
+            bound: generic.bound.as_ref().map(|bound| bound.name),
+        })
+        .collect()
+}
+
+/// The value of a literal in a pattern, `None` if it had an error.
+fn literal_value(expression: &HugTreeExpression) -> Option<&HugValue> {
+    match &expression.kind {
+        HugTreeExpressionKind::Value(value) | HugTreeExpressionKind::Number { value, .. } => {
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+/// The value of an integer literal in a pattern on a type with [`HugType::integer_bounds`].
+fn integer_literal(expression: &HugTreeExpression, _type: &HugType) -> Option<i128> {
+    _type.integer_bounds()?;
+    literal_value(expression)?.as_integer()
+}
+
+/// Stands in for an expression that is being moved somewhere else in the tree.
+fn placeholder(span: Span) -> HugTreeExpression {
+    HugTreeExpression {
+        kind: HugTreeExpressionKind::Value(HugValue::Void),
+        span,
+    }
+}
+
+/// A pattern as the exhaustiveness check sees it.
+#[derive(Debug, Clone)]
+enum Simple {
+    /// `_` or a binding.
+    Anything,
+    /// `true`, `false` or a variant, by its index in the declaration, with its subpatterns.
+    Constructor(usize, Vec<Simple>),
+    /// An integer literal or range, as the first and last value it matches.
+    Interval(i128, i128),
+    /// Any other literal or range, of a type with too many values to list.
+    Value,
+}
+
+/// Parses a number literal as the type of `suffix`. A leading `-` is part of the literal, so
+/// `-128` fits in an `Int8` even though `128` doesn't.
+fn parse_number(literal: &str, suffix: &str) -> Result<HugValue, LiteralError> {
+    match literal.strip_prefix('-') {
+        Some(digits) => literal::parse_signed_number(&format!("{}{}", digits, suffix), true),
+        None => literal::parse_number(&format!("{}{}", literal, suffix)),
+    }
+}
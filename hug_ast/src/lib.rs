@@ -1,60 +1,828 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
-use hug_lexer::parser::TokenPair;
-use hug_lib::{value::HugValue, Ident};
+use hug_lexer::{parser::TokenPair, tokenizer::TokenKind};
+use hug_lib::{
+    diagnostic::Diagnostic, format::FormatSpec, interner::Interner, span::Span, value::HugValue,
+    Ident,
+};
 use parser::HugTreeParser;
 
+pub mod checker;
 pub mod parser;
 
 #[derive(Debug, Clone)]
-pub enum HugTreeFunctionCallArg {
+pub struct HugTreeExpression {
+    pub kind: HugTreeExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum HugTreeExpressionKind {
+    /// A global variable.
     Variable(Ident),
+    /// A variable inside a block or function, stored in `slot` of the current frame.
+    Local {
+        variable: Ident,
+        slot: usize,
+    },
     Value(HugValue),
+    /// A number literal without a type suffix, the type checker picks its type from where it is
+    /// used. `value` is the default `Int32` or `Float32` value of `literal`.
+    Number {
+        literal: String,
+        value: HugValue,
+    },
+    FormatString(Vec<HugTreeFormatSegment>),
+    Call {
+        function: Ident,
+        args: Vec<HugTreeExpression>,
+    },
+    Unary {
+        operator: HugTreeUnaryOperator,
+        operand: Box<HugTreeExpression>,
+    },
+    Binary {
+        operator: HugTreeBinaryOperator,
+        left: Box<HugTreeExpression>,
+        right: Box<HugTreeExpression>,
+    },
+    /// `start..end` or `start..=end`, can only be iterated over by a `for` loop for now.
+    Range {
+        start: Box<HugTreeExpression>,
+        end: Box<HugTreeExpression>,
+        inclusive: bool,
+    },
+    /// `Point { x: 1.0, y: 2.0 }`, the type checker puts the fields in the declared order.
+    Struct {
+        _type: Ident,
+        fields: Vec<(Ident, HugTreeExpression)>,
+    },
+    /// `value.field`. Fields of globals are parsed as part of the global's path, the type checker
+    /// splits them off once it knows which part of the path is the global.
+    Field {
+        value: Box<HugTreeExpression>,
+        field: Ident,
+    },
+    /// `Shape.Circle(1.0)`, `Shape.Rect { width: 1.0, height: 2.0 }` or `Shape.Empty`. These are
+    /// parsed as a call, struct or global, the type checker turns them into variants once it
+    /// knows `Shape` is an enum.
+    Variant {
+        _type: Ident,
+        variant: Ident,
+        payload: HugTreePayload<HugTreeExpression>,
+    },
+    Match(HugTreeMatch<HugTreeExpression>),
+    /// `point.length()`, the type checker turns it into a call of `Point.length` with `point` as
+    /// the first argument when it knows the type of `point`.
+    MethodCall {
+        value: Box<HugTreeExpression>,
+        method: Ident,
+        args: Vec<HugTreeExpression>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugTreeUnaryOperator {
+    Negate,    // -
+    Not,       // !
+    BinaryNot, // ~
+}
+
+impl HugTreeUnaryOperator {
+    pub fn from_token(kind: TokenKind) -> Option<HugTreeUnaryOperator> {
+        Some(match kind {
+            TokenKind::Subtract => HugTreeUnaryOperator::Negate,
+            TokenKind::Not => HugTreeUnaryOperator::Not,
+            TokenKind::BinaryNot => HugTreeUnaryOperator::BinaryNot,
+            _ => return None,
+        })
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            HugTreeUnaryOperator::Negate => "-",
+            HugTreeUnaryOperator::Not => "!",
+            HugTreeUnaryOperator::BinaryNot => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugTreeBinaryOperator {
+    Multiply,            // *
+    Divide,              // /
+    Modulus,             // %
+    Add,                 // +
+    Subtract,            // -
+    ShiftLeft,           // <<
+    ShiftRight,          // >>
+    ShiftLeftOverflow,   // <<<
+    ShiftRightOverflow,  // >>>
+    BinaryAnd,           // &
+    BinaryXOr,           // ^
+    BinaryOr,            // |
+    IsEqualTo,           // ==
+    IsNotEqualTo,        // !=
+    LessThan,            // <
+    GreaterThan,         // >
+    LessThanOrEquals,    // <=
+    GreaterThanOrEquals, // >=
+    And,                 // &&
+    Or,                  // ||
+}
+
+impl HugTreeBinaryOperator {
+    pub fn from_token(kind: TokenKind) -> Option<HugTreeBinaryOperator> {
+        Some(match kind {
+            TokenKind::Multiply => HugTreeBinaryOperator::Multiply,
+            TokenKind::Divide => HugTreeBinaryOperator::Divide,
+            TokenKind::Modulus => HugTreeBinaryOperator::Modulus,
+            TokenKind::Add => HugTreeBinaryOperator::Add,
+            TokenKind::Subtract => HugTreeBinaryOperator::Subtract,
+            TokenKind::ShiftLeft => HugTreeBinaryOperator::ShiftLeft,
+            TokenKind::ShiftRight => HugTreeBinaryOperator::ShiftRight,
+            TokenKind::ShiftLeftOverflow => HugTreeBinaryOperator::ShiftLeftOverflow,
+            TokenKind::ShiftRightOverflow => HugTreeBinaryOperator::ShiftRightOverflow,
+            TokenKind::BinaryAnd => HugTreeBinaryOperator::BinaryAnd,
+            TokenKind::BinaryXOr => HugTreeBinaryOperator::BinaryXOr,
+            TokenKind::BinaryOr => HugTreeBinaryOperator::BinaryOr,
+            TokenKind::IsEqualTo => HugTreeBinaryOperator::IsEqualTo,
+            TokenKind::IsNotEqualTo => HugTreeBinaryOperator::IsNotEqualTo,
+            TokenKind::LessThan => HugTreeBinaryOperator::LessThan,
+            TokenKind::GreaterThan => HugTreeBinaryOperator::GreaterThan,
+            TokenKind::LessThanOrEquals => HugTreeBinaryOperator::LessThanOrEquals,
+            TokenKind::GreaterThanOrEquals => HugTreeBinaryOperator::GreaterThanOrEquals,
+            TokenKind::And => HugTreeBinaryOperator::And,
+            TokenKind::Or => HugTreeBinaryOperator::Or,
+            _ => return None,
+        })
+    }
+
+    /// The operator of a compound assignment like `+=`.
+    pub fn from_assign_token(kind: TokenKind) -> Option<HugTreeBinaryOperator> {
+        Some(match kind {
+            TokenKind::AddAssign => HugTreeBinaryOperator::Add,
+            TokenKind::SubtractAssign => HugTreeBinaryOperator::Subtract,
+            TokenKind::MultiplyAssign => HugTreeBinaryOperator::Multiply,
+            TokenKind::DivideAssign => HugTreeBinaryOperator::Divide,
+            TokenKind::ModulusAssign => HugTreeBinaryOperator::Modulus,
+            TokenKind::BinaryAndAssign => HugTreeBinaryOperator::BinaryAnd,
+            TokenKind::BinaryOrAssign => HugTreeBinaryOperator::BinaryOr,
+            TokenKind::BinaryXOrAssign => HugTreeBinaryOperator::BinaryXOr,
+            TokenKind::ShiftLeftAssign => HugTreeBinaryOperator::ShiftLeft,
+            TokenKind::ShiftRightAssign => HugTreeBinaryOperator::ShiftRight,
+            _ => return None,
+        })
+    }
+
+    /// How tightly the operator binds, higher goes first. Same order as Rust.
+    pub fn precedence(self) -> u8 {
+        match self {
+            HugTreeBinaryOperator::Multiply
+            | HugTreeBinaryOperator::Divide
+            | HugTreeBinaryOperator::Modulus => 10,
+            HugTreeBinaryOperator::Add | HugTreeBinaryOperator::Subtract => 9,
+            HugTreeBinaryOperator::ShiftLeft
+            | HugTreeBinaryOperator::ShiftRight
+            | HugTreeBinaryOperator::ShiftLeftOverflow
+            | HugTreeBinaryOperator::ShiftRightOverflow => 8,
+            HugTreeBinaryOperator::BinaryAnd => 7,
+            HugTreeBinaryOperator::BinaryXOr => 6,
+            HugTreeBinaryOperator::BinaryOr => 5,
+            HugTreeBinaryOperator::IsEqualTo
+            | HugTreeBinaryOperator::IsNotEqualTo
+            | HugTreeBinaryOperator::LessThan
+            | HugTreeBinaryOperator::GreaterThan
+            | HugTreeBinaryOperator::LessThanOrEquals
+            | HugTreeBinaryOperator::GreaterThanOrEquals => 4,
+            HugTreeBinaryOperator::And => 3,
+            HugTreeBinaryOperator::Or => 2,
+        }
+    }
+
+    /// Comparisons can't be chained, `a < b < c` needs parentheses.
+    pub fn is_comparison(self) -> bool {
+        self.precedence() == 4
+    }
+
+    pub fn is_shift(self) -> bool {
+        self.precedence() == 8
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            HugTreeBinaryOperator::Multiply => "*",
+            HugTreeBinaryOperator::Divide => "/",
+            HugTreeBinaryOperator::Modulus => "%",
+            HugTreeBinaryOperator::Add => "+",
+            HugTreeBinaryOperator::Subtract => "-",
+            HugTreeBinaryOperator::ShiftLeft => "<<",
+            HugTreeBinaryOperator::ShiftRight => ">>",
+            HugTreeBinaryOperator::ShiftLeftOverflow => "<<<",
+            HugTreeBinaryOperator::ShiftRightOverflow => ">>>",
+            HugTreeBinaryOperator::BinaryAnd => "&",
+            HugTreeBinaryOperator::BinaryXOr => "^",
+            HugTreeBinaryOperator::BinaryOr => "|",
+            HugTreeBinaryOperator::IsEqualTo => "==",
+            HugTreeBinaryOperator::IsNotEqualTo => "!=",
+            HugTreeBinaryOperator::LessThan => "<",
+            HugTreeBinaryOperator::GreaterThan => ">",
+            HugTreeBinaryOperator::LessThanOrEquals => "<=",
+            HugTreeBinaryOperator::GreaterThanOrEquals => ">=",
+            HugTreeBinaryOperator::And => "&&",
+            HugTreeBinaryOperator::Or => "||",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum HugTreeFormatSegment {
+    Text(String),
+    Expression {
+        expression: HugTreeExpression,
+        spec: FormatSpec,
+    },
+}
+
+/// A type written in the source, like the `Int32` in `let x: Int32 = 5`. `args` are the types
+/// given to a generic type, the `Int32` and `String` of `Pair<Int32, String>`.
+#[derive(Debug, Clone)]
+pub struct HugTreeType {
+    pub name: Ident,
+    pub args: Vec<HugTreeType>,
+    pub span: Span,
+}
+
+impl HugTreeType {
+    /// Calls `f` with the name of the type and of every type given to it.
+    pub fn globals_mut(&mut self, f: &mut impl FnMut(&mut Ident)) {
+        f(&mut self.name);
+        self.args.iter_mut().for_each(|arg| arg.globals_mut(f));
+    }
+}
+
+/// A type parameter of a generic function or type, the `T: Display` of `function show<T:
+/// Display>(value: T)`. `bound` is the trait the types given for it have to implement.
+#[derive(Debug, Clone)]
+pub struct HugTreeGeneric {
+    pub name: Ident,
+    pub bound: Option<HugTreeType>,
+    pub span: Span,
+}
+
+impl HugTreeGeneric {
+    /// Calls `f` with the name of the bound, the name of the parameter itself is never a global.
+    pub fn globals_mut(&mut self, f: &mut impl FnMut(&mut Ident)) {
+        if let Some(bound) = &mut self.bound {
+            bound.globals_mut(f);
+        }
+    }
+}
+
+/// What a variant of an enum carries. `T` is a type where the enum is declared, an expression
+/// where the variant is built and a pattern where it is matched.
+#[derive(Debug, Clone)]
+pub enum HugTreePayload<T> {
+    Unit,
+    Tuple(Vec<T>),
+    Struct(Vec<(Ident, T)>),
+}
+
+impl<T> HugTreePayload<T> {
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &T> + '_> {
+        match self {
+            HugTreePayload::Unit => Box::new(std::iter::empty()),
+            HugTreePayload::Tuple(values) => Box::new(values.iter()),
+            HugTreePayload::Struct(fields) => Box::new(fields.iter().map(|(_, value)| value)),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut T> + '_> {
+        match self {
+            HugTreePayload::Unit => Box::new(std::iter::empty()),
+            HugTreePayload::Tuple(values) => Box::new(values.iter_mut()),
+            HugTreePayload::Struct(fields) => Box::new(fields.iter_mut().map(|(_, value)| value)),
+        }
+    }
+}
+
+/// A variant of an enum, `Circle(Float64)`.
+#[derive(Debug, Clone)]
+pub struct HugTreeVariant {
+    pub name: Ident,
+    pub payload: HugTreePayload<HugTreeType>,
+    pub span: Span,
+}
+
+/// `match value { pattern => body, ... }`. `T` is an expression when the match gives back a
+/// value and a block when it is a statement.
+#[derive(Debug, Clone)]
+pub struct HugTreeMatch<T> {
+    pub value: Box<HugTreeExpression>,
+    pub arms: Vec<HugTreeMatchArm<T>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HugTreeMatchArm<T> {
+    pub pattern: HugTreePattern,
+    pub body: T,
+}
+
+#[derive(Debug, Clone)]
+pub struct HugTreePattern {
+    pub kind: HugTreePatternKind,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub enum HugTreeEntry {
+pub enum HugTreePatternKind {
+    /// `_`
+    Wildcard,
+    /// A name, which matches anything and stores it in a new local.
+    Binding { variable: Ident, slot: usize },
+    /// Matches values equal to the literal.
+    Literal(HugTreeExpression),
+    /// `0..10` or `'a'..='z'`, the ends are literals.
+    Range {
+        start: HugTreeExpression,
+        end: HugTreeExpression,
+        inclusive: bool,
+    },
+    /// `Shape.Circle(radius)`, `_type` is the enum. Fields left out of a struct variant match
+    /// anything.
+    Variant {
+        _type: Ident,
+        variant: Ident,
+        payload: HugTreePayload<HugTreePattern>,
+    },
+}
+
+impl HugTreePattern {
+    /// Calls `f` with every name of a global this pattern uses.
+    pub fn globals_mut(&mut self, f: &mut impl FnMut(&mut Ident)) {
+        match &mut self.kind {
+            HugTreePatternKind::Wildcard | HugTreePatternKind::Binding { .. } => (),
+            HugTreePatternKind::Literal(value) => value.globals_mut(f),
+            HugTreePatternKind::Range { start, end, .. } => {
+                start.globals_mut(f);
+                end.globals_mut(f);
+            }
+            HugTreePatternKind::Variant { _type, payload, .. } => {
+                f(_type);
+                payload
+                    .iter_mut()
+                    .for_each(|pattern| pattern.globals_mut(f));
+            }
+        }
+    }
+}
+
+/// A method a type needs to implement a trait, `function area(self) -> Float64`. `Self` in it is
+/// the trait, it stands for the type that implements it.
+#[derive(Debug, Clone)]
+pub struct HugTreeTraitMethod {
+    pub name: Ident,
+    pub parameters: Vec<HugTreeParameter>,
+    pub return_type: Option<HugTreeType>,
+    pub span: Span,
+    /// Contents of the `///` comments above the method.
+    pub docs: Option<String>,
+}
+
+/// A field of a struct type, `x: Float64`.
+#[derive(Debug, Clone)]
+pub struct HugTreeField {
+    pub name: Ident,
+    pub _type: HugTreeType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct HugTreeParameter {
+    pub name: Ident,
+    pub _type: HugTreeType,
+    pub span: Span,
+}
+
+/// A function written in hug, `function name(a: Int32) -> Int32 { ... }`.
+#[derive(Debug, Clone)]
+pub struct HugTreeFunction {
+    pub name: Ident,
+    pub generics: Vec<HugTreeGeneric>,
+    pub parameters: Vec<HugTreeParameter>,
+    pub return_type: Option<HugTreeType>,
+    pub body: Vec<HugTreeEntry>,
+    /// Number of local slots a call needs, the parameters take the first ones.
+    pub locals: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct HugTreeEntry {
+    pub kind: HugTreeEntryKind,
+    pub span: Span,
+    /// Contents of the `///` comments above a declaration.
+    pub docs: Option<String>,
+    /// Declarations marked `public` can be used outside of the module that defines them.
+    pub public: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum HugTreeEntryKind {
+    /// `module name { ... }`, the names defined in `body` are qualified with `module`.
     ModuleDefinition {
         module: Ident,
+        body: Vec<HugTreeEntry>,
+    },
+    /// `use utils`, makes `utils.helper` available by loading `utils.hug`.
+    Use {
+        module: Ident,
     },
     ExternalTypeDefinition {
         _type: Ident,
     },
+    /// `type Point { x: Float64, y: Float64 }` or `type Pair<A, B> { first: A, second: B }`
+    TypeDefinition {
+        _type: Ident,
+        generics: Vec<HugTreeGeneric>,
+        fields: Vec<HugTreeField>,
+    },
+    /// `enum Shape { Circle(Float64), Rect { width: Float64, height: Float64 }, Empty }`
+    EnumDefinition {
+        _type: Ident,
+        generics: Vec<HugTreeGeneric>,
+        variants: Vec<HugTreeVariant>,
+    },
+    /// `impl Point { ... }` or `impl Display for Point { ... }`, the functions in `methods` are
+    /// named after the type, like `Point.length`. Methods of traits are too, so they are called
+    /// by name on the type of the value and a type can't have two methods with the same name.
+    Implementation {
+        _type: Ident,
+        _trait: Option<Ident>,
+        methods: Vec<HugTreeEntry>,
+    },
+    /// `trait Shape { function area(self) -> Float64 }`
+    TraitDefinition {
+        _trait: Ident,
+        methods: Vec<HugTreeTraitMethod>,
+    },
     ExternalModuleDefinition {
         module: Ident,
         location: String,
     },
+    /// `@extern function add(a: Int32, b: Int32) -> Int32`, calls of one declared without
+    /// `parameters` aren't checked.
     ExternalFunctionDefinition {
         function: Ident,
+        parameters: Option<Vec<HugTreeParameter>>,
+        return_type: Option<HugTreeType>,
     },
+    FunctionDefinition(HugTreeFunction),
     VariableDefinition {
         variable: Ident,
-        value: HugValue,
+        /// `None` for globals.
+        slot: Option<usize>,
+        mutable: bool,
+        /// `let variable: Type = value`, inferred from `value` when `None`.
+        _type: Option<HugTreeType>,
+        value: HugTreeExpression,
+    },
+    /// `variable = value`, or `variable += value` etc. when there is an `operator`.
+    Assignment {
+        variable: Ident,
+        /// `None` for globals.
+        slot: Option<usize>,
+        /// The path to the field being assigned, `["x"]` for `point.x = 1.0`.
+        fields: Vec<Ident>,
+        operator: Option<HugTreeBinaryOperator>,
+        value: HugTreeExpression,
     },
     FunctionCall {
         function: Ident,
-        args: Vec<HugTreeFunctionCallArg>,
+        args: Vec<HugTreeExpression>,
+    },
+    If {
+        condition: HugTreeExpression,
+        body: Vec<HugTreeEntry>,
+        /// The `else` block, an `else if` is an `else` block containing only the next `If`.
+        otherwise: Option<Vec<HugTreeEntry>>,
+    },
+    While {
+        condition: HugTreeExpression,
+        body: Vec<HugTreeEntry>,
+    },
+    For {
+        variable: Ident,
+        slot: usize,
+        iterable: HugTreeExpression,
+        body: Vec<HugTreeEntry>,
+    },
+    Loop {
+        body: Vec<HugTreeEntry>,
+    },
+    /// `{ ... }` on its own, the locals declared in it end with it.
+    Block {
+        body: Vec<HugTreeEntry>,
+    },
+    Break,
+    Continue,
+    Return {
+        value: Option<HugTreeExpression>,
+    },
+    Match(HugTreeMatch<Vec<HugTreeEntry>>),
+    /// `point.move_by(1.0)` as a statement.
+    MethodCall {
+        value: HugTreeExpression,
+        method: Ident,
+        args: Vec<HugTreeExpression>,
     },
 }
 
-#[derive(Debug)]
+impl HugTreeEntryKind {
+    /// Whether this entry declares something that doc comments can be attached to.
+    pub fn is_declaration(&self) -> bool {
+        matches!(
+            self,
+            HugTreeEntryKind::ModuleDefinition { .. }
+                | HugTreeEntryKind::ExternalTypeDefinition { .. }
+                | HugTreeEntryKind::TypeDefinition { .. }
+                | HugTreeEntryKind::EnumDefinition { .. }
+                | HugTreeEntryKind::Implementation { .. }
+                | HugTreeEntryKind::TraitDefinition { .. }
+                | HugTreeEntryKind::ExternalModuleDefinition { .. }
+                | HugTreeEntryKind::ExternalFunctionDefinition { .. }
+                | HugTreeEntryKind::FunctionDefinition(_)
+                | HugTreeEntryKind::VariableDefinition { .. }
+        )
+    }
+}
+
+impl HugTreeEntry {
+    /// Calls `f` with every name of a global this entry defines or uses, including the ones in
+    /// nested blocks, functions and modules. `@extern function`s and `use`d modules are left out,
+    /// those names always refer to the top level.
+    pub fn globals_mut(&mut self, f: &mut impl FnMut(&mut Ident)) {
+        match &mut self.kind {
+            HugTreeEntryKind::ModuleDefinition { module, body } => {
+                f(module);
+                body.iter_mut().for_each(|entry| entry.globals_mut(f));
+            }
+            HugTreeEntryKind::ExternalTypeDefinition { _type } => f(_type),
+            HugTreeEntryKind::TypeDefinition {
+                _type,
+                generics,
+                fields,
+            } => {
+                f(_type);
+                generics
+                    .iter_mut()
+                    .for_each(|generic| generic.globals_mut(f));
+                for field in fields {
+                    field._type.globals_mut(f);
+                }
+            }
+            HugTreeEntryKind::EnumDefinition {
+                _type,
+                generics,
+                variants,
+            } => {
+                f(_type);
+                generics
+                    .iter_mut()
+                    .for_each(|generic| generic.globals_mut(f));
+                for variant in variants {
+                    variant
+                        .payload
+                        .iter_mut()
+                        .for_each(|_type| _type.globals_mut(f));
+                }
+            }
+            HugTreeEntryKind::Implementation {
+                _type,
+                _trait,
+                methods,
+            } => {
+                f(_type);
+                if let Some(_trait) = _trait {
+                    f(_trait);
+                }
+                methods.iter_mut().for_each(|entry| entry.globals_mut(f));
+            }
+            HugTreeEntryKind::TraitDefinition { _trait, methods } => {
+                f(_trait);
+                for method in methods {
+                    for parameter in &mut method.parameters {
+                        parameter._type.globals_mut(f);
+                    }
+                    if let Some(return_type) = &mut method.return_type {
+                        return_type.globals_mut(f);
+                    }
+                }
+            }
+            HugTreeEntryKind::ExternalModuleDefinition { module, .. } => f(module),
+            HugTreeEntryKind::ExternalFunctionDefinition {
+                parameters,
+                return_type,
+                ..
+            } => {
+                for parameter in parameters.iter_mut().flatten() {
+                    parameter._type.globals_mut(f);
+                }
+                if let Some(return_type) = return_type {
+                    return_type.globals_mut(f);
+                }
+            }
+            HugTreeEntryKind::Use { .. } | HugTreeEntryKind::Break | HugTreeEntryKind::Continue => {
+            }
+            HugTreeEntryKind::FunctionDefinition(function) => {
+                f(&mut function.name);
+                function
+                    .generics
+                    .iter_mut()
+                    .for_each(|generic| generic.globals_mut(f));
+                for parameter in &mut function.parameters {
+                    parameter._type.globals_mut(f);
+                }
+                if let Some(return_type) = &mut function.return_type {
+                    return_type.globals_mut(f);
+                }
+                function
+                    .body
+                    .iter_mut()
+                    .for_each(|entry| entry.globals_mut(f));
+            }
+            HugTreeEntryKind::VariableDefinition {
+                variable,
+                slot,
+                _type,
+                value,
+                ..
+            } => {
+                if slot.is_none() {
+                    f(variable);
+                }
+                if let Some(_type) = _type {
+                    _type.globals_mut(f);
+                }
+                value.globals_mut(f);
+            }
+            HugTreeEntryKind::Assignment {
+                variable,
+                slot,
+                value,
+                ..
+            } => {
+                if slot.is_none() {
+                    f(variable);
+                }
+                value.globals_mut(f);
+            }
+            HugTreeEntryKind::FunctionCall { function, args } => {
+                f(function);
+                args.iter_mut().for_each(|arg| arg.globals_mut(f));
+            }
+            HugTreeEntryKind::MethodCall { value, args, .. } => {
+                value.globals_mut(f);
+                args.iter_mut().for_each(|arg| arg.globals_mut(f));
+            }
+            HugTreeEntryKind::If {
+                condition,
+                body,
+                otherwise,
+            } => {
+                condition.globals_mut(f);
+                body.iter_mut().for_each(|entry| entry.globals_mut(f));
+                for entry in otherwise.iter_mut().flatten() {
+                    entry.globals_mut(f);
+                }
+            }
+            HugTreeEntryKind::While { condition, body } => {
+                condition.globals_mut(f);
+                body.iter_mut().for_each(|entry| entry.globals_mut(f));
+            }
+            HugTreeEntryKind::For { iterable, body, .. } => {
+                iterable.globals_mut(f);
+                body.iter_mut().for_each(|entry| entry.globals_mut(f));
+            }
+            HugTreeEntryKind::Loop { body } | HugTreeEntryKind::Block { body } => {
+                body.iter_mut().for_each(|entry| entry.globals_mut(f));
+            }
+            HugTreeEntryKind::Return { value } => {
+                if let Some(value) = value {
+                    value.globals_mut(f);
+                }
+            }
+            HugTreeEntryKind::Match(HugTreeMatch { value, arms }) => {
+                value.globals_mut(f);
+                for arm in arms {
+                    arm.pattern.globals_mut(f);
+                    arm.body.iter_mut().for_each(|entry| entry.globals_mut(f));
+                }
+            }
+        }
+    }
+
+    /// The global this entry defines, if any. Doesn't look inside modules.
+    pub fn defines(&self) -> Option<Ident> {
+        match &self.kind {
+            HugTreeEntryKind::ModuleDefinition { module: name, .. }
+            | HugTreeEntryKind::ExternalTypeDefinition { _type: name }
+            | HugTreeEntryKind::TypeDefinition { _type: name, .. }
+            | HugTreeEntryKind::EnumDefinition { _type: name, .. }
+            | HugTreeEntryKind::TraitDefinition { _trait: name, .. }
+            | HugTreeEntryKind::FunctionDefinition(HugTreeFunction { name, .. })
+            | HugTreeEntryKind::VariableDefinition {
+                variable: name,
+                slot: None,
+                ..
+            } => Some(*name),
+            _ => None,
+        }
+    }
+}
+
+impl HugTreeExpression {
+    /// Calls `f` with every name of a global this expression uses.
+    pub fn globals_mut(&mut self, f: &mut impl FnMut(&mut Ident)) {
+        match &mut self.kind {
+            HugTreeExpressionKind::Variable(variable) => f(variable),
+            HugTreeExpressionKind::Local { .. }
+            | HugTreeExpressionKind::Value(_)
+            | HugTreeExpressionKind::Number { .. } => (),
+            HugTreeExpressionKind::FormatString(segments) => {
+                for segment in segments {
+                    if let HugTreeFormatSegment::Expression { expression, .. } = segment {
+                        expression.globals_mut(f);
+                    }
+                }
+            }
+            HugTreeExpressionKind::Call { function, args } => {
+                f(function);
+                args.iter_mut().for_each(|arg| arg.globals_mut(f));
+            }
+            HugTreeExpressionKind::Unary { operand, .. } => operand.globals_mut(f),
+            HugTreeExpressionKind::Binary { left, right, .. }
+            | HugTreeExpressionKind::Range {
+                start: left,
+                end: right,
+                ..
+            } => {
+                left.globals_mut(f);
+                right.globals_mut(f);
+            }
+            HugTreeExpressionKind::Struct { _type, fields } => {
+                f(_type);
+                for (_, value) in fields {
+                    value.globals_mut(f);
+                }
+            }
+            HugTreeExpressionKind::Field { value, .. } => value.globals_mut(f),
+            HugTreeExpressionKind::Variant { _type, payload, .. } => {
+                f(_type);
+                payload.iter_mut().for_each(|value| value.globals_mut(f));
+            }
+            HugTreeExpressionKind::Match(HugTreeMatch { value, arms }) => {
+                value.globals_mut(f);
+                for arm in arms {
+                    arm.pattern.globals_mut(f);
+                    arm.body.globals_mut(f);
+                }
+            }
+            HugTreeExpressionKind::MethodCall { value, args, .. } => {
+                value.globals_mut(f);
+                args.iter_mut().for_each(|arg| arg.globals_mut(f));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct HugTree {
     pub entries: Vec<HugTreeEntry>,
+    /// Number of local slots needed by blocks at the top level.
+    pub locals: usize,
+    /// Contents of the `//!` comments describing the whole file.
+    pub docs: Option<String>,
 }
 
 impl HugTree {
     pub fn new() -> HugTree {
         HugTree {
             entries: Vec::new(),
+            locals: 0,
+            docs: None,
         }
     }
 
     pub fn merge_with(&mut self, other: HugTree) {
-        self.entries.extend(other.entries.into_iter());
+        self.entries.extend(other.entries);
+        self.locals = self.locals.max(other.locals);
     }
 
-    pub fn from_token_pairs(pairs: Vec<TokenPair>) -> HugTree {
-        HugTreeParser::new(pairs).parse()
+    pub fn from_token_pairs(
+        pairs: Vec<TokenPair<'_>>,
+        idents: &mut Interner,
+    ) -> (HugTree, Vec<Diagnostic>) {
+        HugTreeParser::new(pairs, idents).parse()
     }
 }
 
@@ -62,10 +830,235 @@ impl Display for HugTree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut buffer = String::new();
         for (i, entry) in self.entries.iter().enumerate() {
-            buffer.push_str(&format!("{:4}: {:?},\n", i, entry));
+            buffer.push_str(&format!("{:4}: {} {:?},\n", i, entry.span, entry.kind));
         }
         write!(f, "[\n{}]", buffer)
     }
 }
 
-pub struct HugType {}
+/// The type of a value, worked out by the `checker` before a program runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HugType {
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Int128,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    UInt128,
+    Float32,
+    Float64,
+    Bool,
+    Char,
+    String,
+    /// Declared with `@extern type`, only external functions can produce these.
+    External(Ident),
+    /// Declared with `type Name { ... }`, the fields are kept by the type checker. The types are
+    /// what was given for the type parameters of a generic type.
+    Struct(Ident, Vec<HugType>),
+    /// Declared with `enum Name { ... }`, the variants are kept by the type checker.
+    Enum(Ident, Vec<HugType>),
+    /// Any type that implements the trait, which one is only known when the program runs.
+    Trait(Ident),
+    /// A type parameter inside the generic function or type that declares it, it stands for
+    /// whatever type is given for it. Values of it can only use the methods of `bound`.
+    Parameter {
+        name: Ident,
+        bound: Option<Ident>,
+    },
+    Function {
+        parameters: Vec<HugType>,
+        return_type: Box<HugType>,
+    },
+    /// `start..end` over integers of the inner type.
+    Range(Box<HugType>),
+    /// What functions without a return type give back, it can't be used as a value.
+    Void,
+    /// Only known when the program runs, like the result of an `@extern function`. The checker
+    /// accepts it anywhere.
+    Unknown,
+}
+
+impl HugType {
+    /// The type that can be written as `name` without declaring it first.
+    pub fn builtin(name: &str) -> Option<HugType> {
+        Some(match name {
+            "Int8" => HugType::Int8,
+            "Int16" => HugType::Int16,
+            "Int32" => HugType::Int32,
+            "Int64" => HugType::Int64,
+            "Int128" => HugType::Int128,
+            "UInt8" => HugType::UInt8,
+            "UInt16" => HugType::UInt16,
+            "UInt32" => HugType::UInt32,
+            "UInt64" => HugType::UInt64,
+            "UInt128" => HugType::UInt128,
+            "Float32" => HugType::Float32,
+            "Float64" => HugType::Float64,
+            "Bool" => HugType::Bool,
+            "Char" => HugType::Char,
+            "String" => HugType::String,
+            _ => return None,
+        })
+    }
+
+    pub fn of(value: &HugValue) -> HugType {
+        match value {
+            HugValue::Void => HugType::Void,
+            other => HugType::builtin(other.type_name()).unwrap_or(HugType::Unknown),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_signed_integer()
+            || matches!(
+                self,
+                HugType::UInt8
+                    | HugType::UInt16
+                    | HugType::UInt32
+                    | HugType::UInt64
+                    | HugType::UInt128
+            )
+    }
+
+    /// The smallest and biggest value of an integer type, `None` for other types and `UInt128`,
+    /// whose values don't all fit in an `i128`.
+    pub fn integer_bounds(&self) -> Option<(i128, i128)> {
+        Some(match self {
+            HugType::Int8 => (i8::MIN as i128, i8::MAX as i128),
+            HugType::Int16 => (i16::MIN as i128, i16::MAX as i128),
+            HugType::Int32 => (i32::MIN as i128, i32::MAX as i128),
+            HugType::Int64 => (i64::MIN as i128, i64::MAX as i128),
+            HugType::Int128 => (i128::MIN, i128::MAX),
+            HugType::UInt8 => (0, u8::MAX as i128),
+            HugType::UInt16 => (0, u16::MAX as i128),
+            HugType::UInt32 => (0, u32::MAX as i128),
+            HugType::UInt64 => (0, u64::MAX as i128),
+            _ => return None,
+        })
+    }
+
+    pub fn is_signed_integer(&self) -> bool {
+        matches!(
+            self,
+            HugType::Int8 | HugType::Int16 | HugType::Int32 | HugType::Int64 | HugType::Int128
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, HugType::Float32 | HugType::Float64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// The suffix a number literal needs to have this type, like `u8` for `UInt8`.
+    pub fn suffix(&self) -> Option<&'static str> {
+        Some(match self {
+            HugType::Int8 => "i8",
+            HugType::Int16 => "i16",
+            HugType::Int32 => "i32",
+            HugType::Int64 => "i64",
+            HugType::Int128 => "i128",
+            HugType::UInt8 => "u8",
+            HugType::UInt16 => "u16",
+            HugType::UInt32 => "u32",
+            HugType::UInt64 => "u64",
+            HugType::UInt128 => "u128",
+            HugType::Float32 => "f32",
+            HugType::Float64 => "f64",
+            _ => return None,
+        })
+    }
+
+    /// How the type is written in hug, `idents` is needed for the names of external types.
+    pub fn name(&self, idents: &Interner) -> String {
+        match self {
+            HugType::External(name) | HugType::Trait(name) | HugType::Parameter { name, .. } => {
+                idents.resolve(*name).to_string()
+            }
+            HugType::Struct(name, args) | HugType::Enum(name, args) => match args.is_empty() {
+                true => idents.resolve(*name).to_string(),
+                false => {
+                    let args = args
+                        .iter()
+                        .map(|arg| arg.name(idents))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("{}<{}>", idents.resolve(*name), args)
+                }
+            },
+            HugType::Function {
+                parameters,
+                return_type,
+            } => {
+                let parameters = parameters
+                    .iter()
+                    .map(|p| p.name(idents))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match **return_type {
+                    HugType::Void => format!("function({})", parameters),
+                    ref other => format!("function({}) -> {}", parameters, other.name(idents)),
+                }
+            }
+            HugType::Range(inner) => format!("Range<{}>", inner.name(idents)),
+            other => format!("{:?}", other),
+        }
+    }
+
+    /// The type with every `from` in it replaced by `to`.
+    pub fn substitute(&self, from: &HugType, to: &HugType) -> HugType {
+        self.map(&mut |_type| (_type == from).then(|| to.clone()))
+    }
+
+    /// The type with the type parameters in `bindings` replaced by the types given for them.
+    pub fn instantiate(&self, bindings: &HashMap<Ident, HugType>) -> HugType {
+        self.map(&mut |_type| match _type {
+            HugType::Parameter { name, .. } => bindings.get(name).cloned(),
+            _ => None,
+        })
+    }
+
+    /// The type parameters in the type with their bounds, each once and in order.
+    pub fn parameters(&self) -> Vec<(Ident, Option<Ident>)> {
+        let mut parameters = Vec::new();
+        self.map(&mut |_type| {
+            if let HugType::Parameter { name, bound } = _type {
+                if !parameters.iter().any(|(found, _)| found == name) {
+                    parameters.push((*name, *bound));
+                }
+            }
+            None
+        });
+        parameters
+    }
+
+    /// Rebuilds the type, with the parts `f` gives a type for replaced by it.
+    fn map(&self, f: &mut impl FnMut(&HugType) -> Option<HugType>) -> HugType {
+        if let Some(replaced) = f(self) {
+            return replaced;
+        }
+        match self {
+            HugType::Struct(name, args) => {
+                HugType::Struct(*name, args.iter().map(|arg| arg.map(f)).collect())
+            }
+            HugType::Enum(name, args) => {
+                HugType::Enum(*name, args.iter().map(|arg| arg.map(f)).collect())
+            }
+            HugType::Function {
+                parameters,
+                return_type,
+            } => HugType::Function {
+                parameters: parameters.iter().map(|p| p.map(f)).collect(),
+                return_type: Box::new(return_type.map(f)),
+            },
+            HugType::Range(inner) => HugType::Range(Box::new(inner.map(f))),
+            other => other.clone(),
+        }
+    }
+}
@@ -1,15 +1,24 @@
-use std::{collections::HashMap, vec::IntoIter};
+use std::collections::{HashMap, HashSet};
 
 use hug_lexer::{
-    parser::TokenPair,
-    tokenizer::{AnnotationKind, KeywordKind, LiteralKind, TokenKind},
+    literal::{self, FormatPiece},
+    parser::{generate_pairs, TokenPair},
+    tokenizer::{AnnotationKind, KeywordKind, LiteralKind, TokenKind, Tokenizer},
     FilterUseless,
 };
-use hug_lib::{value::HugValue, Ident};
+use hug_lib::{
+    diagnostic::Diagnostic, format::FormatSpec, interner::Interner, span::Span, value::HugValue,
+    Ident,
+};
 
-use crate::{HugTree, HugTreeEntry, HugTreeFunctionCallArg};
+use crate::{
+    HugTree, HugTreeBinaryOperator, HugTreeEntry, HugTreeEntryKind, HugTreeExpression,
+    HugTreeExpressionKind, HugTreeField, HugTreeFormatSegment, HugTreeFunction, HugTreeGeneric,
+    HugTreeMatch, HugTreeMatchArm, HugTreeParameter, HugTreePattern, HugTreePatternKind,
+    HugTreePayload, HugTreeTraitMethod, HugTreeType, HugTreeUnaryOperator, HugTreeVariant,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct HugTreeAnnotationState {
     is_extern: bool,
     extern_location: String,
@@ -33,7 +42,7 @@ impl HugTreeAnnotationState {
 
     #[inline]
     pub fn push_custom(&mut self, key: Ident, value: HashMap<String, (LiteralKind, String)>) {
-        self.custom.insert(key, value).unwrap();
+        self.custom.insert(key, value);
     }
 
     #[inline]
@@ -59,235 +68,1981 @@ impl HugTreeAnnotationState {
     }
 }
 
+/// How a local was declared, which decides if it can be assigned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugTreeLocal {
+    /// `let x` or `let mut x`.
+    Variable { mutable: bool },
+    /// A parameter, the variable of a `for` loop or a name bound by a pattern. These can never
+    /// be assigned to.
+    Binding,
+}
+
+/// Local variables visible to the code being parsed. A local's slot is its position in `locals`,
+/// so slots are reused once the block that declared them ends.
+#[derive(Debug, Default)]
+pub struct HugTreeScopes {
+    /// Innermost last, a shadowed name is in here more than once.
+    locals: Vec<(Ident, HugTreeLocal)>,
+    /// Where each open scope starts in `locals`.
+    starts: Vec<usize>,
+    /// Most slots in use at once so far.
+    slots: usize,
+}
+
+impl HugTreeScopes {
+    pub fn push_scope(&mut self) {
+        self.starts.push(self.locals.len());
+    }
+
+    pub fn pop_scope(&mut self) {
+        let start = self.starts.pop().expect("No scope to pop");
+        self.locals.truncate(start);
+    }
+
+    /// Declares a local in the innermost scope and returns its slot, `None` if no scope is open
+    /// (which makes it a global).
+    pub fn declare(&mut self, name: Ident, local: HugTreeLocal) -> Option<usize> {
+        if self.starts.is_empty() {
+            return None;
+        }
+
+        self.locals.push((name, local));
+        self.slots = self.slots.max(self.locals.len());
+        Some(self.locals.len() - 1)
+    }
+
+    /// Slot of the innermost local called `name`.
+    pub fn resolve(&self, name: Ident) -> Option<usize> {
+        self.locals.iter().rposition(|(local, _)| *local == name)
+    }
+
+    pub fn local(&self, slot: usize) -> HugTreeLocal {
+        self.locals[slot].1
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+}
+
+/// What a name refers to, for checking assignments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HugTreeGlobal {
+    Variable {
+        mutable: bool,
+    },
+    /// A local [`HugTreeLocal::Binding`].
+    Binding,
+    /// A function, type or module, which can never be assigned to.
+    Item,
+}
+
+/// Returned when parsing fails, the error itself is already in `HugTreeParser::diagnostics`.
 #[derive(Debug)]
-pub struct HugTreeParser {
+struct HugTreeParseError;
+
+type HugTreeParseResult<T> = Result<T, HugTreeParseError>;
+
+#[derive(Debug)]
+pub struct HugTreeParser<'a> {
     tree: HugTree,
-    pairs: IntoIter<TokenPair>,
+    pairs: Vec<TokenPair<'a>>,
+    cursor: usize,
     annotation_state: HugTreeAnnotationState,
+    last_span: Span,
+    docs: Vec<String>,
+    /// How many blocks deep the parser is, a `}` only ends a block when this is not zero.
+    depth: usize,
+    /// How many loops deep the parser is, for rejecting `break` and `continue` outside of loops.
+    loop_depth: usize,
+    /// How many `module name { ... }` blocks deep the parser is, a `}` ends those too.
+    module_depth: usize,
+    /// Name of the module this file is, everything it defines is qualified with it.
+    module: Option<Ident>,
+    /// The type of the `impl` block, or the trait, being parsed. It is the type of `self` and
+    /// `Self`.
+    impl_type: Option<Ident>,
+    /// `>` left over from a `>>` that closed two lists of type arguments at once.
+    closing_angles: usize,
+    /// Set while parsing the condition of an `if` or `while` and what a `for` loops over, where a
+    /// `{` after a name starts the body instead of a struct.
+    no_struct_literals: bool,
+    pub scopes: HugTreeScopes,
+    globals: HashMap<Ident, HugTreeGlobal>,
+    /// Assignments to globals, checked once the whole file is parsed because the names in a
+    /// module are only qualified at the end of the module.
+    global_assignments: Vec<(Ident, Span)>,
+    diagnostics: Vec<Diagnostic>,
+    pub idents: &'a mut Interner,
 }
 
-impl HugTreeParser {
-    pub fn new(pairs: Vec<TokenPair>) -> HugTreeParser {
+impl<'a> HugTreeParser<'a> {
+    /// `idents` must be the interner the pairs were tokenized with, it's needed to tokenize the
+    /// interpolations inside format strings.
+    pub fn new(pairs: Vec<TokenPair<'a>>, idents: &'a mut Interner) -> HugTreeParser<'a> {
         HugTreeParser {
+            idents,
             annotation_state: HugTreeAnnotationState::new(),
-            pairs: pairs.filter_useless().into_iter(),
-            tree: HugTree {
-                entries: Vec::new(),
-            },
+            pairs: pairs.filter_useless(),
+            cursor: 0,
+            tree: HugTree::new(),
+            last_span: Span::default(),
+            docs: Vec::new(),
+            depth: 0,
+            loop_depth: 0,
+            module_depth: 0,
+            module: None,
+            impl_type: None,
+            closing_angles: 0,
+            no_struct_literals: false,
+            scopes: HugTreeScopes::default(),
+            globals: HashMap::new(),
+            global_assignments: Vec::new(),
+            diagnostics: Vec::new(),
         }
     }
 
-    pub fn next(&mut self) -> Option<TokenPair> {
-        self.pairs.next()
+    /// Parses the file as the module `module`, like a `module module { ... }` block.
+    pub fn in_module(mut self, module: Ident) -> HugTreeParser<'a> {
+        self.module = Some(module);
+        self
     }
 
-    pub fn peek_next(&mut self) -> Option<TokenPair> {
-        self.pairs.clone().next()
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<TokenPair<'a>> {
+        let pair = self.peek_next()?;
+        self.cursor += 1;
+        self.last_span = pair.span();
+        Some(pair)
     }
 
-    pub fn annotation(&mut self, kind: AnnotationKind) -> Option<HugTreeEntry> {
-        let mut vars: HashMap<String, (LiteralKind, String)> = HashMap::new();
+    pub fn peek_next(&self) -> Option<TokenPair<'a>> {
+        self.pairs.get(self.cursor).copied()
+    }
 
-        if self.peek_next().unwrap().token.kind == TokenKind::OpenParenthesis {
-            self.next(); // (
+    pub fn peek_kind(&self) -> Option<TokenKind> {
+        self.peek_next().map(|pair| pair.token.kind)
+    }
 
-            loop {
-                self.peek_next().unwrap().token.kind.expect_ident().unwrap();
-                let name = self.next().unwrap().text;
+    pub fn is_eof(&self) -> bool {
+        self.cursor >= self.pairs.len()
+    }
 
-                self.next()
-                    .unwrap()
-                    .token
-                    .kind
-                    .expect_kind(TokenKind::Assign)
-                    .unwrap();
+    fn error<T>(&mut self, span: Span, message: impl Into<String>) -> HugTreeParseResult<T> {
+        self.diagnostics.push(Diagnostic::error(span, message));
+        Err(HugTreeParseError)
+    }
 
-                let value_pair = self.next().unwrap();
-                let value_kind = value_pair.token.kind.expect_literal().unwrap();
-                let value = value_pair.text;
-                let value = value[1..value.len() - 1].to_string();
+    /// Reports that the next token isn't what was `expected`, without consuming it so the
+    /// statement it starts can still be parsed.
+    fn unexpected<T>(&mut self, expected: &str) -> HugTreeParseResult<T> {
+        let (span, found) = match self.peek_next() {
+            Some(pair) => (pair.span(), format!("`{}`", pair.text)),
+            None => (self.last_span, "end of file".to_string()),
+        };
+        self.error(span, format!("Expected {}, found {}", expected, found))
+    }
 
-                vars.insert(name, (value_kind, value));
+    /// Consumes the next token if it is `kind`. `expected` is how the token is shown in the error.
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> HugTreeParseResult<TokenPair<'a>> {
+        match self.peek_next() {
+            Some(pair) if pair.token.kind == kind => Ok(self.next().unwrap()),
+            _ => self.unexpected(&format!("`{}`", expected)),
+        }
+    }
 
-                if self.next().unwrap().token.kind == TokenKind::CloseParenthesis {
-                    break;
-                }
+    /// Parses an identifier, `what` describes it for the error message.
+    fn ident(&mut self, what: &str) -> HugTreeParseResult<Ident> {
+        match self.peek_kind() {
+            Some(TokenKind::Identifier(id)) => {
+                self.next();
+                Ok(id)
             }
+            _ => self.unexpected(what),
+        }
+    }
+
+    /// Parses a name, or a path like `utils.math.square` which is joined into one identifier.
+    fn path(&mut self, what: &str) -> HugTreeParseResult<Ident> {
+        let first = self.ident(what)?;
+        self.path_from(first)
+    }
+
+    /// The rest of a path after its first name. Locals aren't joined with what follows them, the
+    /// names after a local are fields.
+    fn path_from(&mut self, first: Ident) -> HugTreeParseResult<Ident> {
+        if self.peek_kind() != Some(TokenKind::Dot) {
+            return Ok(first);
+        }
+
+        // `point.x` is a field of a local, not a path
+        if self.scopes.resolve(first).is_some() {
+            return Ok(first);
         }
 
-        if vars.keys().len() > 0 {
-            match kind {
-                AnnotationKind::Extern => self
-                    .annotation_state
-                    .set_extern(vars.remove("location").unwrap().1),
-                AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars),
+        let mut path = self.idents.resolve(first).to_string();
+        while self.peek_kind() == Some(TokenKind::Dot) {
+            self.next(); // .
+            let name = self.ident("a name after `.`")?;
+            path.push('.');
+            path.push_str(self.idents.resolve(name));
+        }
+        Ok(self.idents.intern(&path))
+    }
+
+    /// Whether a `}` ends what is being parsed instead of being an error.
+    fn in_braces(&self) -> bool {
+        self.depth > 0 || self.module_depth > 0
+    }
+
+    /// Skips the rest of a statement that failed to parse: up to the first token on a new line
+    /// that isn't inside a nested block, or the `}` that ends the current block.
+    fn synchronize(&mut self) {
+        let line = self.last_span.end.line;
+        let mut nesting = 0;
+        while let Some(pair) = self.peek_next() {
+            match pair.token.kind {
+                TokenKind::CloseBrace if nesting == 0 && self.in_braces() => return,
+                TokenKind::OpenBrace => nesting += 1,
+                TokenKind::CloseBrace => nesting = usize::saturating_sub(nesting, 1),
+                _ if nesting == 0 && pair.span().start.line > line => return,
+                _ => (),
             }
-        } else {
-            match kind {
-                AnnotationKind::Extern => self.annotation_state.set_extern("".to_string()),
-                AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars),
+            self.next();
+        }
+    }
+
+    fn annotation(&mut self, kind: AnnotationKind) -> HugTreeParseResult<Option<HugTreeEntry>> {
+        let span = self.last_span;
+        let mut vars: HashMap<String, (LiteralKind, String)> = HashMap::new();
+
+        if self.peek_kind() == Some(TokenKind::OpenParenthesis) {
+            self.next(); // (
+
+            loop {
+                let name = self.ident("an annotation argument")?;
+                let name = self.idents.resolve(name).to_string();
+                self.expect(TokenKind::Assign, "=")?;
+
+                let value = match self.peek_next() {
+                    Some(pair) => match (pair.token.kind, pair.parse_literal()) {
+                        (TokenKind::Literal(kind), Some(HugValue::String(text))) => (kind, text),
+                        (TokenKind::Literal(kind), Some(_)) => (kind, pair.text.to_string()),
+                        _ => return self.unexpected("a literal"),
+                    },
+                    None => return self.unexpected("a literal"),
+                };
+                self.next(); // the literal
+                vars.insert(name, value);
+
+                match self.peek_kind() {
+                    Some(TokenKind::Comma) => self.next(),
+                    Some(TokenKind::CloseParenthesis) => {
+                        self.next();
+                        break;
+                    }
+                    _ => return self.unexpected("`,` or `)`"),
+                };
             }
         }
 
+        match kind {
+            AnnotationKind::Extern if vars.is_empty() => {
+                self.annotation_state.set_extern("".to_string())
+            }
+            AnnotationKind::Extern => match vars.remove("location") {
+                Some((_, location)) => self.annotation_state.set_extern(location),
+                None => return self.error(span, "`@extern` only takes a `location` argument"),
+            },
+            AnnotationKind::Other(id) => self.annotation_state.push_custom(id, vars),
+        }
+
         self.next_entry() // An annotation isn't an AST entry by itself, it supports the following entry
     }
 
-    pub fn keyword(&mut self, kind: KeywordKind) -> Option<HugTreeEntry> {
+    fn keyword(&mut self, kind: KeywordKind) -> HugTreeParseResult<HugTreeEntryKind> {
+        let span = self.last_span;
         match kind {
-            // KeywordKind::Enum => todo!(),
             KeywordKind::Function => {
                 if self.annotation_state.is_extern {
-                    Some(HugTreeEntry::ExternalFunctionDefinition {
-                        function: self.next().unwrap().token.kind.expect_ident().unwrap(),
-                    })
+                    self.external_function()
                 } else {
-                    todo!()
+                    self.function_definition()
                 }
             }
-            KeywordKind::Let => Some(self.variable_definition()),
+            KeywordKind::Let => self.variable_definition(),
             KeywordKind::Module => {
                 if let Some(location) = self.annotation_state.get_extern() {
-                    Some(HugTreeEntry::ExternalModuleDefinition {
+                    Ok(HugTreeEntryKind::ExternalModuleDefinition {
                         location,
-                        module: self.next().unwrap().token.kind.expect_ident().unwrap(),
+                        module: self.ident("a module name")?,
                     })
                 } else {
-                    todo!() // TODO: Non-@extern modules not implemented yet.
+                    self.module_definition()
                 }
             }
-            // TODO: KeywordKind::Private => todo!(),
-            // TODO: KeywordKind::Public => todo!(),
             KeywordKind::Type => {
                 if self.annotation_state.is_extern {
-                    Some(HugTreeEntry::ExternalTypeDefinition {
-                        _type: self.next().unwrap().token.kind.expect_ident().unwrap(),
+                    Ok(HugTreeEntryKind::ExternalTypeDefinition {
+                        _type: self.ident("a type name")?,
                     })
                 } else {
-                    todo!() // TODO: Write non-extern type
+                    self.type_definition()
+                }
+            }
+            KeywordKind::If => self.if_statement(),
+            KeywordKind::Else => self.error(span, "`else` without an `if`"),
+            KeywordKind::While => {
+                let condition = self.condition()?;
+                Ok(HugTreeEntryKind::While {
+                    condition,
+                    body: self.loop_body()?,
+                })
+            }
+            KeywordKind::For => self.for_loop(),
+            KeywordKind::Loop => Ok(HugTreeEntryKind::Loop {
+                body: self.loop_body()?,
+            }),
+            KeywordKind::Break | KeywordKind::Continue => {
+                let kind = if kind == KeywordKind::Break {
+                    HugTreeEntryKind::Break
+                } else {
+                    HugTreeEntryKind::Continue
+                };
+
+                if self.loop_depth == 0 {
+                    let text = self.pairs[self.cursor - 1].text;
+                    self.diagnostics.push(Diagnostic::error(
+                        span,
+                        format!("`{}` outside of a loop", text),
+                    ));
+                }
+                Ok(kind)
+            }
+            KeywordKind::Return => {
+                // The value has to start on the same line, `return` can also be followed by the
+                // next statement
+                let line = self.last_span.end.line;
+                let value = match self.peek_next() {
+                    Some(pair)
+                        if pair.token.kind != TokenKind::CloseBrace
+                            && pair.span().start.line == line =>
+                    {
+                        Some(self.expression()?)
+                    }
+                    _ => None,
+                };
+
+                Ok(HugTreeEntryKind::Return { value })
+            }
+            KeywordKind::Use => {
+                if self.depth > 0 {
+                    self.diagnostics.push(Diagnostic::error(
+                        span,
+                        "`use` can only be used at the top level or in a module",
+                    ));
+                }
+
+                Ok(HugTreeEntryKind::Use {
+                    module: self.path("a module name")?,
+                })
+            }
+            KeywordKind::Enum => self.enum_definition(),
+            KeywordKind::Impl => self.implementation(),
+            KeywordKind::Trait => self.trait_definition(),
+            KeywordKind::Match => Ok(HugTreeEntryKind::Match(
+                self.match_arms(Self::match_statement_arm)?,
+            )),
+            KeywordKind::Private | KeywordKind::Public => {
+                unreachable!("Visibility is parsed as part of the entry it is on")
+            }
+            KeywordKind::In | KeywordKind::Mut => {
+                let text = self.pairs[self.cursor - 1].text;
+                self.error(span, format!("Expected a statement, found `{}`", text))
+            }
+        }
+    }
+
+    /// Parses the type after a `:` or `->`.
+    fn type_annotation(&mut self) -> HugTreeParseResult<HugTreeType> {
+        let start = self.peek_next().map(|pair| pair.span());
+        let mut name = self.path("a type")?;
+        let span = start.map_or(self.last_span, |start| start.to(self.last_span));
+        if self.idents.resolve(name) == "Self" {
+            match self.impl_type {
+                Some(impl_type) => name = impl_type,
+                None => return self.error(span, "`Self` can only be used in an `impl` or `trait`"),
+            }
+        }
+
+        // `Pair<Int32, String>`
+        let mut args = Vec::new();
+        if self.peek_kind() == Some(TokenKind::LessThan) {
+            self.next(); // <
+            loop {
+                args.push(self.type_annotation()?);
+                if self.closing_angles == 0 && self.peek_kind() == Some(TokenKind::Comma) {
+                    self.next(); // ,
+                } else {
+                    break;
                 }
             }
-            // KeywordKind::Use => todo!(),
-            _ => None,
+            self.close_angle()?;
         }
+        let span = start.map_or(span, |start| start.to(self.last_span));
+        Ok(HugTreeType { name, args, span })
     }
 
-    pub fn identifier(&mut self, id: Ident) -> HugTreeEntry {
-        let next = self.next().unwrap();
-        match next.token.kind {
-            TokenKind::Dot => {
-                // TODO: Accessing fields
-                todo!()
+    /// The `>` that ends a list of type arguments or parameters. `>>` and `>>>` end the lists
+    /// they are nested in too, like in `Pair<Int32, Pair<Int32, Int32>>`.
+    fn close_angle(&mut self) -> HugTreeParseResult<()> {
+        if self.closing_angles > 0 {
+            self.closing_angles -= 1;
+            return Ok(());
+        }
+        match self.peek_kind() {
+            Some(TokenKind::ShiftRight) => self.closing_angles = 1,
+            Some(TokenKind::ShiftRightOverflow) => self.closing_angles = 2,
+            _ => {
+                self.expect(TokenKind::GreaterThan, ">")?;
+                return Ok(());
             }
-            TokenKind::OpenParenthesis => {
-                // TODO: Calling functions
-                let mut args = Vec::new();
+        }
+        self.next();
+        Ok(())
+    }
+
+    /// `<T, U: Display>` after the name of a generic function or type, empty if there is no `<`.
+    fn generics(&mut self) -> HugTreeParseResult<Vec<HugTreeGeneric>> {
+        let mut generics: Vec<HugTreeGeneric> = Vec::new();
+        if self.peek_kind() != Some(TokenKind::LessThan) {
+            return Ok(generics);
+        }
+        self.next(); // <
+
+        loop {
+            let name = self.ident("a type parameter name")?;
+            let span = self.last_span;
+            let bound = if self.peek_kind() == Some(TokenKind::Colon) {
+                self.next(); // :
+                Some(self.type_annotation()?)
+            } else {
+                None
+            };
+
+            if generics.iter().any(|generic| generic.name == name) {
+                self.diagnostics.push(Diagnostic::error(
+                    span,
+                    format!(
+                        "Type parameter `{}` is defined twice",
+                        self.idents.resolve(name)
+                    ),
+                ));
+            }
+            generics.push(HugTreeGeneric {
+                name,
+                bound,
+                span: span.to(self.last_span),
+            });
+
+            if self.closing_angles == 0 && self.peek_kind() == Some(TokenKind::Comma) {
+                self.next(); // ,
+            } else {
+                break;
+            }
+        }
+        self.close_angle()?;
+        Ok(generics)
+    }
+
+    /// `module name { ... }`, after the `module`.
+    fn module_definition(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        if self.depth > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                self.last_span,
+                "Modules can only be defined at the top level or in another module",
+            ));
+        }
+
+        let module = self.ident("a module name")?;
+        let open = self.expect(TokenKind::OpenBrace, "{")?;
+        let first_assignment = self.global_assignments.len();
+
+        // Docs of the module itself, dangling docs inside it are dropped
+        let docs = std::mem::take(&mut self.docs);
+
+        self.module_depth += 1;
+        let mut body = Vec::new();
+        loop {
+            self.annotation_state.reset();
+            match self.next_entry() {
+                Ok(Some(entry)) => body.push(entry),
+                Ok(None) => break,
+                Err(HugTreeParseError) => self.synchronize(),
+            }
+        }
+        self.module_depth -= 1;
+        self.docs = docs;
+
+        if self.next().map(|pair| pair.token.kind) != Some(TokenKind::CloseBrace) {
+            return self.error(open.span(), "Unclosed module, expected `}`");
+        }
+
+        self.qualify(module, &mut body, first_assignment);
+        Ok(HugTreeEntryKind::ModuleDefinition { module, body })
+    }
+
+    /// `impl Type { function method(self) { ... } ... }` or `impl Trait for Type { ... }`, after
+    /// the `impl`.
+    fn implementation(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        if self.depth > 0 || self.impl_type.is_some() {
+            self.diagnostics.push(Diagnostic::error(
+                self.last_span,
+                "`impl` blocks can only be at the top level or in a module",
+            ));
+        }
+
+        let mut _type = self.ident("a type name")?;
+        let mut _trait = None;
+        if self.peek_kind() == Some(TokenKind::Keyword(KeywordKind::For)) {
+            self.next(); // for
+            _trait = Some(_type);
+            _type = self.ident("a type name after `for`")?;
+        }
+        let open = self.expect(TokenKind::OpenBrace, "{")?;
+        let docs = std::mem::take(&mut self.docs);
+
+        // Methods are parsed like the body of a module, so they can be `public`
+        let outer = self.impl_type.replace(_type);
+        self.module_depth += 1;
+        let mut methods = Vec::new();
+        loop {
+            self.annotation_state.reset();
+            match self.next_entry() {
+                Ok(Some(mut entry)) => match &mut entry.kind {
+                    HugTreeEntryKind::FunctionDefinition(function) => {
+                        let name = format!(
+                            "{}.{}",
+                            self.idents.resolve(_type),
+                            self.idents.resolve(function.name)
+                        );
+                        function.name = self.idents.intern(&name);
+                        methods.push(entry);
+                    }
+                    _ => self.diagnostics.push(Diagnostic::error(
+                        entry.span,
+                        "Only functions can be defined in an `impl` block",
+                    )),
+                },
+                Ok(None) => break,
+                Err(HugTreeParseError) => self.synchronize(),
+            }
+        }
+        self.module_depth -= 1;
+        self.impl_type = outer;
+        self.docs = docs;
+
+        if self.next().map(|pair| pair.token.kind) != Some(TokenKind::CloseBrace) {
+            return self.error(open.span(), "Unclosed `impl`, expected `}`");
+        }
+
+        Ok(HugTreeEntryKind::Implementation {
+            _type,
+            _trait,
+            methods,
+        })
+    }
+
+    /// `trait Name { function method(self) -> Type ... }`, after the `trait`.
+    fn trait_definition(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        if self.depth > 0 || self.impl_type.is_some() {
+            self.diagnostics.push(Diagnostic::error(
+                self.last_span,
+                "Traits can only be defined at the top level or in a module",
+            ));
+        }
+
+        let _trait = self.ident("a trait name")?;
+        self.expect(TokenKind::OpenBrace, "{")?;
+
+        let outer = self.impl_type.replace(_trait);
+        let methods = self.trait_methods();
+        self.impl_type = outer;
+
+        Ok(HugTreeEntryKind::TraitDefinition {
+            _trait,
+            methods: methods?,
+        })
+    }
+
+    /// The methods of a trait, after the `{`.
+    fn trait_methods(&mut self) -> HugTreeParseResult<Vec<HugTreeTraitMethod>> {
+        let mut methods: Vec<HugTreeTraitMethod> = Vec::new();
+        let mut docs = Vec::new();
+        loop {
+            match self.peek_kind() {
+                Some(TokenKind::CloseBrace) => {
+                    self.next();
+                    break;
+                }
+                Some(TokenKind::DocComment) => {
+                    let pair = self.next().unwrap();
+                    docs.push(pair.doc_text().to_string());
+                    continue;
+                }
+                _ => (),
+            }
+
+            self.expect(TokenKind::Keyword(KeywordKind::Function), "function")?;
+            let start = self.last_span;
+            let outer = std::mem::take(&mut self.scopes);
+            self.scopes.push_scope();
+            let signature = self.signature();
+            self.scopes = outer;
+            let (name, generics, parameters, return_type) = signature?;
+            if let Some(generic) = generics.first() {
+                return self.error(generic.span, "Methods of a trait can't be generic");
+            }
+
+            if self.peek_kind() == Some(TokenKind::OpenBrace) {
+                let span = self.peek_next().unwrap().span();
+                return self.error(span, "Methods of a trait can't have a body");
+            }
+            if methods.iter().any(|method| method.name == name) {
+                self.diagnostics.push(Diagnostic::error(
+                    start,
+                    format!("Method `{}` is defined twice", self.idents.resolve(name)),
+                ));
+            }
+            methods.push(HugTreeTraitMethod {
+                name,
+                parameters,
+                return_type,
+                span: start.to(self.last_span),
+                docs: (!docs.is_empty()).then(|| docs.join("\n")),
+            });
+            docs.clear();
+
+            if self.after_item("a method")? {
+                break;
+            }
+        }
+        Ok(methods)
+    }
+
+    /// Prefixes the names `entries` define, and every use of them, with `module`. Assignments to
+    /// globals from `first_assignment` on are in `entries`, they are qualified too.
+    fn qualify(&mut self, module: Ident, entries: &mut [HugTreeEntry], first_assignment: usize) {
+        let defined = entries
+            .iter()
+            .filter_map(HugTreeEntry::defines)
+            .collect::<HashSet<_>>();
+        let module = self.idents.resolve(module).to_string();
+
+        let idents = &mut *self.idents;
+        let mut qualify = |name: &mut Ident| {
+            let path = idents.resolve(*name);
+            let first = path.split('.').next().unwrap();
+            if idents
+                .get(first)
+                .is_some_and(|first| defined.contains(&first))
+            {
+                *name = idents.intern(&format!("{}.{}", module, path));
+            }
+        };
+
+        for entry in entries {
+            entry.globals_mut(&mut qualify);
+        }
+        for (variable, _) in &mut self.global_assignments[first_assignment..] {
+            qualify(variable);
+        }
+    }
+
+    /// `type Name { field: Type, ... }`, after the `type`.
+    fn type_definition(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        if self.depth > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                self.last_span,
+                "Types can only be defined at the top level or in a module",
+            ));
+        }
+
+        let _type = self.ident("a type name")?;
+        let generics = self.generics()?;
+        self.expect(TokenKind::OpenBrace, "{")?;
+        let fields = self.fields()?;
+        Ok(HugTreeEntryKind::TypeDefinition {
+            _type,
+            generics,
+            fields,
+        })
+    }
+
+    /// `field: Type, ...}` of a type or struct variant, after the `{`.
+    fn fields(&mut self) -> HugTreeParseResult<Vec<HugTreeField>> {
+        let mut fields: Vec<HugTreeField> = Vec::new();
+        loop {
+            if self.peek_kind() == Some(TokenKind::CloseBrace) {
+                self.next();
+                break;
+            }
+
+            let name = self.ident("a field name")?;
+            let start = self.last_span;
+            self.expect(TokenKind::Colon, ":")?;
+            let field_type = self.type_annotation()?;
+            if fields.iter().any(|field| field.name == name) {
+                self.diagnostics.push(Diagnostic::error(
+                    start,
+                    format!("Field `{}` is defined twice", self.idents.resolve(name)),
+                ));
+            }
+            fields.push(HugTreeField {
+                name,
+                _type: field_type,
+                span: start.to(self.last_span),
+            });
+
+            if self.after_item("a field")? {
+                break;
+            }
+        }
+        Ok(fields)
+    }
+
+    /// `enum Name { Variant, Variant(Type, ...), Variant { field: Type, ... } }`, after the
+    /// `enum`.
+    fn enum_definition(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        if self.depth > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                self.last_span,
+                "Enums can only be defined at the top level or in a module",
+            ));
+        }
+
+        let _type = self.ident("an enum name")?;
+        let generics = self.generics()?;
+        self.expect(TokenKind::OpenBrace, "{")?;
+
+        let mut variants: Vec<HugTreeVariant> = Vec::new();
+        loop {
+            if self.peek_kind() == Some(TokenKind::CloseBrace) {
+                self.next();
+                break;
+            }
+
+            let name = self.ident("a variant name")?;
+            let start = self.last_span;
+            let payload = match self.peek_kind() {
+                Some(TokenKind::OpenParenthesis) => {
+                    self.next(); // (
+                    let mut types = Vec::new();
+                    while self.peek_kind() != Some(TokenKind::CloseParenthesis) {
+                        types.push(self.type_annotation()?);
+                        if self.peek_kind() != Some(TokenKind::CloseParenthesis) {
+                            self.expect(TokenKind::Comma, ",")?;
+                        }
+                    }
+                    self.next(); // )
+                    HugTreePayload::Tuple(types)
+                }
+                Some(TokenKind::OpenBrace) => {
+                    self.next(); // {
+                    let fields = self.fields()?;
+                    HugTreePayload::Struct(fields.into_iter().map(|f| (f.name, f._type)).collect())
+                }
+                _ => HugTreePayload::Unit,
+            };
+
+            if variants.iter().any(|variant| variant.name == name) {
+                self.diagnostics.push(Diagnostic::error(
+                    start,
+                    format!("Variant `{}` is defined twice", self.idents.resolve(name)),
+                ));
+            }
+            variants.push(HugTreeVariant {
+                name,
+                payload,
+                span: start.to(self.last_span),
+            });
+
+            if self.after_item("a variant")? {
+                break;
+            }
+        }
+
+        Ok(HugTreeEntryKind::EnumDefinition {
+            _type,
+            generics,
+            variants,
+        })
+    }
+
+    /// `match value { pattern => arm, ... }` after the `match`, `arm` parses what comes after
+    /// each `=>`.
+    fn match_arms<T>(
+        &mut self,
+        mut arm: impl FnMut(&mut Self) -> HugTreeParseResult<T>,
+    ) -> HugTreeParseResult<HugTreeMatch<T>> {
+        let value = self.condition()?;
+        let open = self.expect(TokenKind::OpenBrace, "{")?;
+
+        let mut arms = Vec::new();
+        loop {
+            match self.peek_kind() {
+                Some(TokenKind::CloseBrace) => {
+                    self.next();
+                    break;
+                }
+                None => return self.error(open.span(), "Unclosed `match`, expected `}`"),
+                _ => (),
+            }
+
+            // The names a pattern binds are only visible in its arm
+            self.scopes.push_scope();
+            let parsed = self.pattern().and_then(|pattern| {
+                self.expect(TokenKind::FatArrow, "=>")?;
+                Ok(HugTreeMatchArm {
+                    pattern,
+                    body: arm(self)?,
+                })
+            });
+            self.scopes.pop_scope();
+            arms.push(parsed?);
+
+            if self.after_item("a `match` arm")? {
+                break;
+            }
+        }
+
+        Ok(HugTreeMatch {
+            value: Box::new(value),
+            arms,
+        })
+    }
+
+    /// The body of an arm of a `match` statement, a block or a single statement.
+    fn match_statement_arm(&mut self) -> HugTreeParseResult<Vec<HugTreeEntry>> {
+        if self.peek_kind() == Some(TokenKind::OpenBrace) {
+            return self.block();
+        }
+
+        self.depth += 1;
+        let entry = self.next_entry();
+        self.depth -= 1;
+        match entry? {
+            Some(entry) => Ok(vec![entry]),
+            None => self.unexpected("a statement"),
+        }
+    }
+
+    fn pattern(&mut self) -> HugTreeParseResult<HugTreePattern> {
+        let Some(pair) = self.peek_next() else {
+            return self.unexpected("a pattern");
+        };
+
+        let start = pair.span();
+        let kind = match pair.token.kind {
+            TokenKind::Identifier(_) if pair.text == "_" => {
+                self.next();
+                HugTreePatternKind::Wildcard
+            }
+            TokenKind::Identifier(first) => {
+                self.next();
+                let mut path = vec![first];
+                while self.peek_kind() == Some(TokenKind::Dot) {
+                    self.next(); // .
+                    path.push(self.ident("a variant name after `.`")?);
+                }
+                self.variant_pattern(path, start)?
+            }
+            TokenKind::Literal(_) | TokenKind::Subtract => {
+                let start = self.literal_pattern()?;
+                match self.peek_kind() {
+                    Some(kind @ (TokenKind::Range | TokenKind::RangeInclusive)) => {
+                        self.next(); // .. or ..=
+                        HugTreePatternKind::Range {
+                            start,
+                            end: self.literal_pattern()?,
+                            inclusive: kind == TokenKind::RangeInclusive,
+                        }
+                    }
+                    _ => HugTreePatternKind::Literal(start),
+                }
+            }
+            _ => return self.unexpected("a pattern"),
+        };
+
+        Ok(HugTreePattern {
+            kind,
+            span: start.to(self.last_span),
+        })
+    }
+
+    /// A binding, or a variant like `Shape.Circle(radius)` after its path.
+    fn variant_pattern(
+        &mut self,
+        mut path: Vec<Ident>,
+        start: Span,
+    ) -> HugTreeParseResult<HugTreePatternKind> {
+        let has_payload = matches!(
+            self.peek_kind(),
+            Some(TokenKind::OpenParenthesis | TokenKind::OpenBrace)
+        );
+        let variant = path.pop().unwrap();
+        if path.is_empty() {
+            if has_payload {
+                let name = self.idents.resolve(variant);
+                let message = format!("`{}` needs its enum, like `Shape.{}`", name, name);
+                return self.error(start, message);
+            }
+
+            let slot = self.scopes.declare(variant, HugTreeLocal::Binding).unwrap();
+            return Ok(HugTreePatternKind::Binding {
+                variable: variant,
+                slot,
+            });
+        }
+
+        let _type = path
+            .iter()
+            .map(|name| self.idents.resolve(*name))
+            .collect::<Vec<_>>()
+            .join(".");
+        let _type = self.idents.intern(&_type);
+
+        let payload = match self.peek_kind() {
+            Some(TokenKind::OpenParenthesis) => {
+                self.next(); // (
+                let mut patterns = Vec::new();
+                while self.peek_kind() != Some(TokenKind::CloseParenthesis) {
+                    patterns.push(self.pattern()?);
+                    if self.peek_kind() != Some(TokenKind::CloseParenthesis) {
+                        self.expect(TokenKind::Comma, ",")?;
+                    }
+                }
+                self.next(); // )
+                HugTreePayload::Tuple(patterns)
+            }
+            Some(TokenKind::OpenBrace) => {
+                self.next(); // {
+                let mut fields = Vec::new();
                 loop {
-                    let _next = self.next().unwrap();
-                    if let Some(value) = _next.parse_literal() {
-                        args.push(HugTreeFunctionCallArg::Value(value));
-                    } else if let Some(value) = _next.token.kind.expect_ident() {
-                        args.push(HugTreeFunctionCallArg::Variable(value));
-                    } else if let TokenKind::CloseParenthesis = _next.token.kind {
+                    if self.peek_kind() == Some(TokenKind::CloseBrace) {
+                        self.next();
+                        break;
+                    }
+
+                    // `{ width }` is short for `{ width: width }`
+                    let field = self.ident("a field name")?;
+                    let pattern = if self.peek_kind() == Some(TokenKind::Colon) {
+                        self.next(); // :
+                        self.pattern()?
+                    } else {
+                        let span = self.last_span;
+                        HugTreePattern {
+                            kind: self.variant_pattern(vec![field], span)?,
+                            span,
+                        }
+                    };
+                    fields.push((field, pattern));
+
+                    if self.after_item("a field")? {
                         break;
                     }
                 }
+                HugTreePayload::Struct(fields)
+            }
+            _ => HugTreePayload::Unit,
+        };
+
+        Ok(HugTreePatternKind::Variant {
+            _type,
+            variant,
+            payload,
+        })
+    }
+
+    /// A literal in a pattern, including negative numbers.
+    fn literal_pattern(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let literal = self.unary()?;
+        match literal.kind {
+            HugTreeExpressionKind::Value(_) | HugTreeExpressionKind::Number { .. } => Ok(literal),
+            _ => self.error(literal.span, "Expected a literal in the pattern"),
+        }
+    }
+
+    fn function_definition(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        if self.depth > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                self.last_span,
+                "Functions can only be defined at the top level",
+            ));
+        }
+
+        // Functions can only see their own locals
+        let outer = std::mem::take(&mut self.scopes);
+        self.scopes.push_scope();
+        let function = self.function();
+        self.scopes = outer;
+        function.map(HugTreeEntryKind::FunctionDefinition)
+    }
+
+    /// Everything after `@extern function`, the signature is left out for functions whose calls
+    /// can't be checked.
+    fn external_function(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        let after_name = self.pairs.get(self.cursor + 1).map(|pair| pair.token.kind);
+        if !matches!(
+            after_name,
+            Some(TokenKind::OpenParenthesis | TokenKind::LessThan)
+        ) {
+            return Ok(HugTreeEntryKind::ExternalFunctionDefinition {
+                function: self.ident("a function name")?,
+                parameters: None,
+                return_type: None,
+            });
+        }
+
+        // The parameters only document what the native function takes
+        let outer = std::mem::take(&mut self.scopes);
+        self.scopes.push_scope();
+        let signature = self.signature();
+        self.scopes = outer;
+        let (function, generics, parameters, return_type) = signature?;
+        if let Some(generic) = generics.first() {
+            return self.error(generic.span, "External functions can't be generic");
+        }
+
+        Ok(HugTreeEntryKind::ExternalFunctionDefinition {
+            function,
+            parameters: Some(parameters),
+            return_type,
+        })
+    }
 
-                HugTreeEntry::FunctionCall { function: id, args }
+    /// Everything after `function`, the scopes of the function must already be set up.
+    fn function(&mut self) -> HugTreeParseResult<HugTreeFunction> {
+        let (name, generics, parameters, return_type) = self.signature()?;
+        Ok(HugTreeFunction {
+            name,
+            generics,
+            parameters,
+            return_type,
+            body: self.block()?,
+            locals: self.scopes.slots(),
+        })
+    }
+
+    /// The name, type parameters, parameters and return type of a function, after `function`.
+    #[allow(clippy::type_complexity)]
+    fn signature(
+        &mut self,
+    ) -> HugTreeParseResult<(
+        Ident,
+        Vec<HugTreeGeneric>,
+        Vec<HugTreeParameter>,
+        Option<HugTreeType>,
+    )> {
+        let name = self.ident("a function name")?;
+        let generics = self.generics()?;
+        self.expect(TokenKind::OpenParenthesis, "(")?;
+
+        let mut parameters = Vec::new();
+        while self.peek_kind() != Some(TokenKind::CloseParenthesis) {
+            let name = self.ident("a parameter name")?;
+            let start = self.last_span;
+            self.scopes.declare(name, HugTreeLocal::Binding);
+            let _type = if self.idents.resolve(name) == "self"
+                && self.peek_kind() != Some(TokenKind::Colon)
+            {
+                self.self_parameter(parameters.is_empty())?
+            } else {
+                self.expect(TokenKind::Colon, ":")?;
+                self.type_annotation()?
+            };
+            parameters.push(HugTreeParameter {
+                name,
+                _type,
+                span: start.to(self.last_span),
+            });
+
+            if self.peek_kind() == Some(TokenKind::Comma) {
+                self.next(); // ,
+            } else {
+                break;
             }
-            TokenKind::Assign => {
-                // TODO: Assigning values to existing variables
-                todo!()
+        }
+        self.expect(TokenKind::CloseParenthesis, ")")?;
+
+        let return_type = if self.peek_kind() == Some(TokenKind::Arrow) {
+            self.next(); // ->
+            Some(self.type_annotation()?)
+        } else {
+            None
+        };
+
+        Ok((name, generics, parameters, return_type))
+    }
+
+    /// The type of a `self` parameter, which is the type of the `impl` it is in.
+    fn self_parameter(&mut self, first: bool) -> HugTreeParseResult<HugTreeType> {
+        let span = self.last_span;
+        match self.impl_type {
+            Some(name) if first => Ok(HugTreeType {
+                name,
+                args: Vec::new(),
+                span,
+            }),
+            Some(_) => self.error(span, "`self` must be the first parameter"),
+            None => self.error(
+                span,
+                "`self` can only be a parameter of functions in an `impl` or `trait`",
+            ),
+        }
+    }
+
+    /// Parses `{ ... }` into its entries. Errors inside the block are recovered from, only a
+    /// missing `{` or `}` fails the whole block.
+    fn block(&mut self) -> HugTreeParseResult<Vec<HugTreeEntry>> {
+        let open = self.expect(TokenKind::OpenBrace, "{")?;
+
+        // Docs of the entry this block belongs to, dangling docs inside the block are dropped
+        let docs = std::mem::take(&mut self.docs);
+
+        self.depth += 1;
+        self.scopes.push_scope();
+        let mut entries = Vec::new();
+        loop {
+            self.annotation_state.reset();
+            match self.next_entry() {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => break,
+                Err(HugTreeParseError) => self.synchronize(),
             }
-            _ => panic!("Unexpected token after identifier: {:?}", next),
         }
+        self.scopes.pop_scope();
+        self.depth -= 1;
+        self.docs = docs;
+
+        match self.next() {
+            Some(pair) if pair.token.kind == TokenKind::CloseBrace => Ok(entries),
+            _ => self.error(open.span(), "Unclosed block, expected `}`"),
+        }
+    }
+
+    fn loop_body(&mut self) -> HugTreeParseResult<Vec<HugTreeEntry>> {
+        self.loop_depth += 1;
+        let body = self.block();
+        self.loop_depth -= 1;
+        body
     }
 
-    pub fn variable_definition(&mut self) -> HugTreeEntry {
-        let name = self.next().unwrap();
-        let name = name.token.kind.expect_ident().unwrap();
+    fn if_statement(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        let condition = self.condition()?;
+        let body = self.block()?;
+
+        let otherwise = if self.peek_kind() == Some(TokenKind::Keyword(KeywordKind::Else)) {
+            self.next(); // else
+
+            if self.peek_kind() == Some(TokenKind::Keyword(KeywordKind::If)) {
+                let start = self.next().unwrap().span();
+                let kind = self.if_statement()?;
+                Some(vec![HugTreeEntry {
+                    kind,
+                    span: start.to(self.last_span),
+                    docs: None,
+                    public: false,
+                }])
+            } else {
+                Some(self.block()?)
+            }
+        } else {
+            None
+        };
+
+        Ok(HugTreeEntryKind::If {
+            condition,
+            body,
+            otherwise,
+        })
+    }
+
+    fn for_loop(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        let variable = self.ident("a variable name after `for`")?;
+        self.expect(TokenKind::Keyword(KeywordKind::In), "in")?;
+        let iterable = self.condition()?;
+
+        self.scopes.push_scope();
+        let slot = self
+            .scopes
+            .declare(variable, HugTreeLocal::Binding)
+            .unwrap();
+        let body = self.loop_body();
+        self.scopes.pop_scope();
+
+        Ok(HugTreeEntryKind::For {
+            variable,
+            slot,
+            iterable,
+            body: body?,
+        })
+    }
+
+    fn identifier(&mut self, id: Ident) -> HugTreeParseResult<HugTreeEntryKind> {
+        let start = self.last_span;
+        let id = self.path_from(id)?;
+        let mut fields = Vec::new();
+        while self.peek_kind() == Some(TokenKind::Dot) {
+            self.next(); // .
+            fields.push(self.ident("a field name after `.`")?);
+        }
+        let span = start.to(self.last_span);
+        let Some(next) = self.peek_next() else {
+            return self.unexpected("`(` or `=`");
+        };
 
-        let next = self.next().unwrap();
         match next.token.kind {
-            TokenKind::Assign => {
-                let value = self.next().unwrap();
-                let value = value.parse_literal().unwrap();
-                HugTreeEntry::VariableDefinition {
-                    variable: name,
+            TokenKind::OpenParenthesis if fields.is_empty() => {
+                self.next(); // (
+                let args = self.arguments()?;
+                Ok(HugTreeEntryKind::FunctionCall { function: id, args })
+            }
+            TokenKind::OpenParenthesis => {
+                // `point.end.length()`, only locals have fields here
+                let method = fields.pop().unwrap();
+                let mut value = HugTreeExpression {
+                    kind: HugTreeExpressionKind::Local {
+                        variable: id,
+                        slot: self.scopes.resolve(id).unwrap(),
+                    },
+                    span: start,
+                };
+                for field in fields {
+                    value = HugTreeExpression {
+                        kind: HugTreeExpressionKind::Field {
+                            value: Box::new(value),
+                            field,
+                        },
+                        span: start.to(self.last_span),
+                    };
+                }
+
+                self.next(); // (
+                let args = self.arguments()?;
+                Ok(HugTreeEntryKind::MethodCall {
                     value,
+                    method,
+                    args,
+                })
+            }
+            TokenKind::BinaryNotAssign => {
+                self.next(); // ~=
+                self.diagnostics.push(
+                    Diagnostic::error(next.span(), "`~=` is not an assignment operator").with_hint(
+                        format!(
+                            "`~` only takes one value, use `{0} = ~{0}` to flip the bits",
+                            self.idents.resolve(id)
+                        ),
+                    ),
+                );
+                self.assignment(id, fields, span, None)
+            }
+            TokenKind::Assign => {
+                self.next(); // =
+                self.assignment(id, fields, span, None)
+            }
+            kind if HugTreeBinaryOperator::from_assign_token(kind).is_some() => {
+                let operator = HugTreeBinaryOperator::from_assign_token(kind);
+                self.next(); // += etc.
+                self.assignment(id, fields, span, operator)
+            }
+            _ if !fields.is_empty() => self.unexpected("`=` after a field"),
+            _ => self.unexpected(&format!("`(` or `=` after `{}`", self.idents.resolve(id))),
+        }
+    }
+
+    /// Parses the value of `variable = value` or `variable += value` etc., `span` is the span of
+    /// `variable` and the `fields` after it.
+    fn assignment(
+        &mut self,
+        variable: Ident,
+        fields: Vec<Ident>,
+        span: Span,
+        operator: Option<HugTreeBinaryOperator>,
+    ) -> HugTreeParseResult<HugTreeEntryKind> {
+        let slot = self.scopes.resolve(variable);
+        let mutable = slot.map(|slot| match self.scopes.local(slot) {
+            HugTreeLocal::Variable { mutable } => HugTreeGlobal::Variable { mutable },
+            HugTreeLocal::Binding => HugTreeGlobal::Binding,
+        });
+
+        match mutable {
+            Some(global) => self.check_assignment(variable, !fields.is_empty(), span, Some(global)),
+            None => self.global_assignments.push((variable, span)),
+        }
+
+        Ok(HugTreeEntryKind::Assignment {
+            variable,
+            slot,
+            fields,
+            operator,
+            value: self.expression()?,
+        })
+    }
+
+    /// Reports an error if `variable`, or a `field` of it, can't be assigned to. `global` is
+    /// `None` if it isn't defined at all.
+    fn check_assignment(
+        &mut self,
+        variable: Ident,
+        field: bool,
+        span: Span,
+        global: Option<HugTreeGlobal>,
+    ) {
+        let name = self.idents.resolve(variable);
+        let diagnostic = match global {
+            Some(HugTreeGlobal::Variable { mutable: true }) => return,
+            Some(HugTreeGlobal::Variable { mutable: false }) if field => Diagnostic::error(
+                span,
+                format!("Cannot assign to a field of immutable variable `{}`", name),
+            )
+            .with_hint(format!("make it mutable: `let mut {}`", name)),
+            Some(HugTreeGlobal::Variable { mutable: false }) => Diagnostic::error(
+                span,
+                format!("Cannot assign twice to immutable variable `{}`", name),
+            )
+            .with_hint(format!("make it mutable: `let mut {}`", name)),
+            Some(HugTreeGlobal::Binding) if field => Diagnostic::error(
+                span,
+                format!(
+                    "Cannot assign to a field of `{}`, it can't be changed",
+                    name
+                ),
+            )
+            .with_hint(format!("change a copy of it: `let mut {0} = {0}`", name)),
+            Some(HugTreeGlobal::Binding) => Diagnostic::error(
+                span,
+                format!("Cannot assign to `{}`, it can't be changed", name),
+            )
+            .with_hint(format!("change a copy of it: `let mut {0} = {0}`", name)),
+            Some(HugTreeGlobal::Item) => Diagnostic::error(
+                span,
+                format!("Cannot assign to `{}`, it is not a variable", name),
+            ),
+            None => Diagnostic::error(
+                span,
+                format!("Cannot assign to `{}`, it is not defined", name),
+            )
+            .with_hint(format!("define it first: `let mut {} = ...`", name)),
+        };
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn variable_definition(&mut self) -> HugTreeParseResult<HugTreeEntryKind> {
+        let mutable = self.peek_kind() == Some(TokenKind::Keyword(KeywordKind::Mut));
+        if mutable {
+            self.next(); // mut
+        }
+
+        let name = self.ident("a variable name")?;
+
+        let _type = if self.peek_kind() == Some(TokenKind::Colon) {
+            self.next(); // :
+            Some(self.type_annotation()?)
+        } else {
+            None
+        };
+
+        self.expect(TokenKind::Assign, "=")?;
+
+        // The value can still use a variable this shadows, `let x = x + 1`
+        let value = self.expression()?;
+        Ok(HugTreeEntryKind::VariableDefinition {
+            variable: name,
+            slot: self
+                .scopes
+                .declare(name, HugTreeLocal::Variable { mutable }),
+            mutable,
+            _type,
+            value,
+        })
+    }
+
+    /// An expression that is followed by a block, which can't be a struct unless it is in
+    /// parentheses.
+    fn condition(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let outer = std::mem::replace(&mut self.no_struct_literals, true);
+        let condition = self.expression();
+        self.no_struct_literals = outer;
+        condition
+    }
+
+    /// Parses an expression inside brackets, where structs can always be used.
+    fn nested_expression(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let outer = std::mem::replace(&mut self.no_struct_literals, false);
+        let expression = self.expression();
+        self.no_struct_literals = outer;
+        expression
+    }
+
+    /// Parses a full expression, including ranges.
+    fn expression(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let start = self.binary_expression(0)?;
+
+        match self.peek_kind() {
+            Some(kind @ (TokenKind::Range | TokenKind::RangeInclusive)) => {
+                self.next(); // .. or ..=
+                let end = self.binary_expression(0)?;
+                Ok(HugTreeExpression {
+                    span: start.span.to(end.span),
+                    kind: HugTreeExpressionKind::Range {
+                        start: Box::new(start),
+                        end: Box::new(end),
+                        inclusive: kind == TokenKind::RangeInclusive,
+                    },
+                })
+            }
+            _ => Ok(start),
+        }
+    }
+
+    /// Precedence climbing, parses all binary operators that bind tighter than `min_precedence`.
+    fn binary_expression(&mut self, min_precedence: u8) -> HugTreeParseResult<HugTreeExpression> {
+        let mut left = self.unary()?;
+        let mut is_comparison = false;
+
+        while let Some(operator) = self.peek_kind().and_then(HugTreeBinaryOperator::from_token) {
+            let precedence = operator.precedence();
+            if precedence <= min_precedence {
+                break;
+            }
+
+            let pair = self.next().unwrap();
+            if operator.is_comparison() && is_comparison {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        pair.span(),
+                        format!(
+                            "Comparison operators cannot be chained, found `{}` after a comparison",
+                            pair.text
+                        ),
+                    )
+                    .with_hint("use `&&` to combine comparisons"),
+                );
+            }
+            is_comparison = operator.is_comparison();
+
+            // Everything is left-associative, so the right side only takes tighter operators
+            let right = self.binary_expression(precedence)?;
+            left = HugTreeExpression {
+                span: left.span.to(right.span),
+                kind: HugTreeExpressionKind::Binary {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            };
+        }
+
+        Ok(left)
+    }
+
+    /// `-a`, `!a` and `~a`, which bind tighter than any binary operator.
+    fn unary(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let Some(operator) = self.peek_kind().and_then(HugTreeUnaryOperator::from_token) else {
+            return self.primary();
+        };
+
+        let start = self.next().unwrap().span();
+
+        // `-128` is a single literal, so it can fit in an `Int8` even though `128` doesn't
+        let is_number = matches!(
+            self.peek_kind(),
+            Some(TokenKind::Literal(
+                LiteralKind::Integer(_) | LiteralKind::Float(_)
+            ))
+        );
+        // `-1.format()` negates what the method gives back
+        let is_receiver =
+            self.pairs.get(self.cursor + 1).map(|pair| pair.token.kind) == Some(TokenKind::Dot);
+        if operator == HugTreeUnaryOperator::Negate && is_number && !is_receiver {
+            let pair = self.next().unwrap();
+            let span = start.to(pair.span());
+            let kind = self.number(&pair, true, span)?;
+            return Ok(HugTreeExpression { span, kind });
+        }
+
+        let operand = self.unary()?;
+        let span = start.to(operand.span);
+        if let (HugTreeUnaryOperator::Negate, HugTreeExpressionKind::Number { literal, value }) =
+            (operator, &operand.kind)
+        {
+            if let (false, Ok(value)) = (literal.starts_with('-'), value.negate()) {
+                return Ok(HugTreeExpression {
+                    span,
+                    kind: HugTreeExpressionKind::Number {
+                        literal: format!("-{}", literal),
+                        value,
+                    },
+                });
+            }
+        }
+
+        Ok(HugTreeExpression {
+            span,
+            kind: HugTreeExpressionKind::Unary {
+                operator,
+                operand: Box::new(operand),
+            },
+        })
+    }
+
+    /// A number literal, after a `-` when `negative`. Literals without a type suffix stay a
+    /// `Number` so the checker can give them the type they are used as.
+    fn number(
+        &mut self,
+        pair: &TokenPair<'a>,
+        negative: bool,
+        span: Span,
+    ) -> HugTreeParseResult<HugTreeExpressionKind> {
+        let value = match literal::parse_signed_number(pair.text, negative) {
+            Ok(value) => value,
+            Err(error) => {
+                let mut diagnostic = Diagnostic::error(span, error.message);
+                if let Some(hint) = error.hint {
+                    diagnostic = diagnostic.with_hint(hint);
                 }
+                self.diagnostics.push(diagnostic);
+                return Err(HugTreeParseError);
             }
-            TokenKind::Colon => todo!(),
-            _ => panic!("Unexpected token at variable definition: {:?}", next),
+        };
+
+        if !literal::split_number(pair.text).2.is_empty() {
+            return Ok(HugTreeExpressionKind::Value(value));
         }
+        let sign = if negative { "-" } else { "" };
+        Ok(HugTreeExpressionKind::Number {
+            literal: format!("{}{}", sign, pair.text),
+            value,
+        })
     }
 
-    pub fn next_entry(&mut self) -> Option<HugTreeEntry> {
-        if let Some(pair) = self.next() {
-            match pair.token.kind {
-                // TokenKind::Literal(_) => todo!(),
-                TokenKind::Keyword(kind) => self.keyword(kind),
-                TokenKind::Identifier(id) => Some(self.identifier(id)),
-                TokenKind::Annotation(kind) => self.annotation(kind),
-                // TokenKind::Dot => todo!(),
-                // TokenKind::OpenParenthesis => todo!(),
-                // TokenKind::CloseParenthesis => todo!(),
-                // TokenKind::OpenBrace => todo!(),
-                // TokenKind::CloseBrace => todo!(),
-                // TokenKind::OpenBracket => todo!(),
-                // TokenKind::CloseBracket => todo!(),
-                // TokenKind::Colon => todo!(),
-                // TokenKind::Assign => todo!(),
-                // TokenKind::Add => todo!(),
-                // TokenKind::Subtract => todo!(),
-                // TokenKind::Multiply => todo!(),
-                // TokenKind::Divide => todo!(),
-                // TokenKind::Modulus => todo!(),
-                // TokenKind::AddAssign => todo!(),
-                // TokenKind::SubtractAssign => todo!(),
-                // TokenKind::MultiplyAssign => todo!(),
-                // TokenKind::DivideAssign => todo!(),
-                // TokenKind::ModulusAssign => todo!(),
-                // TokenKind::Not => todo!(),
-                // TokenKind::And => todo!(),
-                // TokenKind::Or => todo!(),
-                // TokenKind::IsEqualTo => todo!(),
-                // TokenKind::IsNotEqualTo => todo!(),
-                // TokenKind::LessThan => todo!(),
-                // TokenKind::GreaterThan => todo!(),
-                // TokenKind::LessThanOrEquals => todo!(),
-                // TokenKind::GreaterThanOrEquals => todo!(),
-                // TokenKind::BinaryAnd => todo!(),
-                // TokenKind::BinaryOr => todo!(),
-                // TokenKind::BinaryNot => todo!(),
-                // TokenKind::BinaryXOr => todo!(),
-                // TokenKind::BinaryAndAssign => todo!(),
-                // TokenKind::BinaryOrAssign => todo!(),
-                // TokenKind::BinaryNotAssign => todo!(),
-                // TokenKind::BinaryXOrAssign => todo!(),
-                // TokenKind::ShiftLeft => todo!(),
-                // TokenKind::ShiftRight => todo!(),
-                // TokenKind::ShiftLeftOverflow => todo!(),
-                // TokenKind::ShiftRightOverflow => todo!(),
-                TokenKind::Unknown => panic!("Unknown token: {}!", pair.text),
-                _ => self.next_entry(),
-                // _ => unreachable!(),
+    /// Arguments of a call, after the `(`.
+    fn arguments(&mut self) -> HugTreeParseResult<Vec<HugTreeExpression>> {
+        let mut args = Vec::new();
+        loop {
+            if self.peek_kind() == Some(TokenKind::CloseParenthesis) {
+                self.next(); // )
+                break;
+            }
+
+            args.push(self.nested_expression()?);
+
+            match self.peek_kind() {
+                Some(TokenKind::Comma) => {
+                    self.next();
+                }
+                Some(TokenKind::CloseParenthesis) => {
+                    self.next();
+                    break;
+                }
+                _ => return self.unexpected("`,` or `)` after function argument"),
             }
-        } else {
-            self.next_entry()
         }
+
+        Ok(args)
     }
 
-    pub fn parse(mut self) -> HugTree {
-        self.annotation_state.reset();
-        while self.pairs.as_slice().len() > 0 {
-            self.annotation_state.reset();
-            if let Some(entry) = self.next_entry() {
-                self.tree.entries.push(entry);
-            } else {
+    /// An operand followed by any number of `.field`s and `.method()` calls.
+    fn primary(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let mut expression = self.operand()?;
+        while self.peek_kind() == Some(TokenKind::Dot) {
+            self.next(); // .
+            let field = self.ident("a field name after `.`")?;
+            if self.peek_kind() == Some(TokenKind::OpenParenthesis) {
+                self.next(); // (
+                let args = self.arguments()?;
+                expression = HugTreeExpression {
+                    span: expression.span.to(self.last_span),
+                    kind: HugTreeExpressionKind::MethodCall {
+                        value: Box::new(expression),
+                        method: field,
+                        args,
+                    },
+                };
+                continue;
+            }
+
+            expression = HugTreeExpression {
+                span: expression.span.to(self.last_span),
+                kind: HugTreeExpressionKind::Field {
+                    value: Box::new(expression),
+                    field,
+                },
+            };
+        }
+        Ok(expression)
+    }
+
+    /// A literal, variable, call, struct or parenthesized expression.
+    fn operand(&mut self) -> HugTreeParseResult<HugTreeExpression> {
+        let pair = match self.peek_next() {
+            Some(pair) if Self::starts_expression(pair.token.kind) => self.next().unwrap(),
+            _ => return self.unexpected("an expression"),
+        };
+
+        let kind = match pair.token.kind {
+            TokenKind::OpenParenthesis => {
+                let expression = self.nested_expression()?;
+                let close = self.expect(TokenKind::CloseParenthesis, ")")?;
+                return Ok(HugTreeExpression {
+                    span: pair.span().to(close.span()),
+                    ..expression
+                });
+            }
+            TokenKind::Keyword(KeywordKind::Match) => {
+                HugTreeExpressionKind::Match(self.match_arms(Self::nested_expression)?)
+            }
+            TokenKind::Literal(LiteralKind::FormatString) => self.format_string(&pair)?,
+            TokenKind::Literal(LiteralKind::Integer(_) | LiteralKind::Float(_)) => {
+                self.number(&pair, false, pair.span())?
+            }
+            TokenKind::Literal(_) => match pair.parse_literal() {
+                Some(value) => HugTreeExpressionKind::Value(value),
+                None => return self.error(pair.span(), format!("Invalid literal `{}`", pair.text)),
+            },
+            TokenKind::Identifier(id) => {
+                let id = self.path_from(id)?;
+                if self.peek_kind() == Some(TokenKind::OpenParenthesis) {
+                    self.next(); // (
+                    let args = self.arguments()?;
+                    return Ok(HugTreeExpression {
+                        kind: HugTreeExpressionKind::Call { function: id, args },
+                        span: pair.span().to(self.last_span),
+                    });
+                }
+
+                let kind = match self.scopes.resolve(id) {
+                    Some(slot) => HugTreeExpressionKind::Local { variable: id, slot },
+                    None if !self.no_struct_literals
+                        && self.peek_kind() == Some(TokenKind::OpenBrace) =>
+                    {
+                        self.struct_literal(id)?
+                    }
+                    None => HugTreeExpressionKind::Variable(id),
+                };
+                return Ok(HugTreeExpression {
+                    kind,
+                    span: pair.span().to(self.last_span),
+                });
+            }
+            _ => unreachable!("Checked by `starts_expression`"),
+        };
+
+        Ok(HugTreeExpression {
+            kind,
+            span: pair.span(),
+        })
+    }
+
+    /// The fields of `Type { field: value, ... }`, at the `{`.
+    fn struct_literal(&mut self, _type: Ident) -> HugTreeParseResult<HugTreeExpressionKind> {
+        self.next(); // {
+        let mut fields = Vec::new();
+        loop {
+            if self.peek_kind() == Some(TokenKind::CloseBrace) {
+                self.next();
                 break;
             }
+
+            let name = self.ident("a field name")?;
+            self.expect(TokenKind::Colon, ":")?;
+            fields.push((name, self.nested_expression()?));
+            if self.after_item("a field")? {
+                break;
+            }
+        }
+
+        Ok(HugTreeExpressionKind::Struct { _type, fields })
+    }
+
+    /// Consumes what comes after an item in braces, like a field or `match` arm, `true` at the
+    /// `}` that ends them. Items are separated by `,` or new lines.
+    fn after_item(&mut self, what: &str) -> HugTreeParseResult<bool> {
+        let line = self.last_span.end.line;
+        match self.peek_next() {
+            Some(pair) if pair.token.kind == TokenKind::CloseBrace => {
+                self.next();
+                Ok(true)
+            }
+            Some(pair) if pair.token.kind == TokenKind::Comma => {
+                self.next();
+                Ok(false)
+            }
+            Some(pair) if pair.span().start.line > line => Ok(false),
+            _ => self.unexpected(&format!("`,` or `}}` after {}", what)),
+        }
+    }
+
+    fn starts_expression(kind: TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::OpenParenthesis
+                | TokenKind::Literal(_)
+                | TokenKind::Identifier(_)
+                | TokenKind::Keyword(KeywordKind::Match)
+        )
+    }
+
+    /// Splits a format string literal into text and parsed interpolations.
+    fn format_string(&mut self, pair: &TokenPair<'a>) -> HugTreeParseResult<HugTreeExpressionKind> {
+        let body_start = 2; // f["]
+        let body = match pair.text.strip_suffix('"') {
+            Some(text) if text.len() >= body_start => &text[body_start..],
+            _ => return self.error(pair.span(), "Unterminated format string"),
+        };
+
+        let mut segments = Vec::new();
+        for piece in literal::split_format_string(body, |_, _| ()) {
+            match piece {
+                FormatPiece::Text(range) => segments.push(HugTreeFormatSegment::Text(
+                    literal::unescape(&body[range], |_, _| ()),
+                )),
+                FormatPiece::Interpolation { expression, spec } => {
+                    let mut position = pair.span().start;
+                    for c in pair.text[..body_start + expression.start].chars() {
+                        position.advance(c);
+                    }
+
+                    let source = &body[expression];
+                    let (tokens, _) = Tokenizer::new(source, self.idents)
+                        .starting_at(pair.span().file, position)
+                        .tokenize();
+
+                    let mut parser =
+                        HugTreeParser::new(generate_pairs(source, tokens), self.idents);
+                    parser.scopes = std::mem::take(&mut self.scopes);
+                    let mut expression = parser.expression();
+                    if expression.is_ok() && !parser.is_eof() {
+                        expression = parser.unexpected("`}` after the interpolated expression");
+                    }
+                    self.scopes = std::mem::take(&mut parser.scopes);
+                    self.diagnostics.append(&mut parser.diagnostics);
+
+                    // The lexer already reported invalid specs
+                    let spec = match spec.map(|spec| FormatSpec::parse(&body[spec])) {
+                        Some(Ok(spec)) => spec,
+                        Some(Err(_)) => return Err(HugTreeParseError),
+                        None => FormatSpec::default(),
+                    };
+
+                    segments.push(HugTreeFormatSegment::Expression {
+                        expression: expression?,
+                        spec,
+                    });
+                }
+            }
+        }
+
+        Ok(HugTreeExpressionKind::FormatString(segments))
+    }
+
+    /// The next statement, `None` at the end of the file or the current block.
+    fn next_entry(&mut self) -> HugTreeParseResult<Option<HugTreeEntry>> {
+        if self.in_braces() && self.peek_kind() == Some(TokenKind::CloseBrace) {
+            return Ok(None); // End of the block or module, which consumes the `}`
+        }
+
+        if let Some(open) = self
+            .peek_next()
+            .filter(|pair| pair.token.kind == TokenKind::OpenBrace)
+        {
+            let body = self.block()?;
+            self.docs.clear(); // Nothing to document
+            return Ok(Some(HugTreeEntry {
+                docs: None,
+                kind: HugTreeEntryKind::Block { body },
+                span: open.span().to(self.last_span),
+                public: false,
+            }));
+        }
+
+        let Some(pair) = self.next() else {
+            return Ok(None);
+        };
+
+        let start = pair.span();
+        let kind = match pair.token.kind {
+            TokenKind::Keyword(kind @ (KeywordKind::Public | KeywordKind::Private)) => {
+                return self.visibility(kind == KeywordKind::Public);
+            }
+            TokenKind::Keyword(kind) => self.keyword(kind)?,
+            TokenKind::Identifier(id) => self.identifier(id)?,
+            TokenKind::DocComment => {
+                self.docs.push(pair.doc_text().to_string());
+                return self.next_entry();
+            }
+            TokenKind::InnerDocComment => {
+                let docs = self.tree.docs.get_or_insert_with(String::new);
+                if !docs.is_empty() {
+                    docs.push('\n');
+                }
+                docs.push_str(pair.doc_text());
+                return self.next_entry();
+            }
+            TokenKind::Annotation(kind) => {
+                // The annotation is part of the entry it annotates
+                return Ok(self.annotation(kind)?.map(|entry| HugTreeEntry {
+                    span: start.to(entry.span),
+                    ..entry
+                }));
+            }
+            TokenKind::CloseBrace => return self.error(start, "Unmatched `}`"),
+            TokenKind::Unknown => {
+                return self.error(start, format!("Unknown token `{}`", pair.text))
+            }
+            _ => {
+                return self.error(
+                    start,
+                    format!("Expected a statement, found `{}`", pair.text),
+                )
+            }
+        };
+
+        let docs = std::mem::take(&mut self.docs);
+        Ok(Some(HugTreeEntry {
+            docs: (kind.is_declaration() && !docs.is_empty()).then(|| docs.join("\n")),
+            kind,
+            span: start.to(self.last_span),
+            public: false,
+        }))
+    }
+
+    /// `public` or `private` and the declaration it is on, after the keyword.
+    fn visibility(&mut self, public: bool) -> HugTreeParseResult<Option<HugTreeEntry>> {
+        let span = self.last_span;
+        let text = self.pairs[self.cursor - 1].text;
+        if self.depth > 0 {
+            self.diagnostics.push(Diagnostic::error(
+                span,
+                format!(
+                    "`{}` can only be used at the top level or in a module",
+                    text
+                ),
+            ));
+        }
+
+        match self.peek_kind() {
+            Some(
+                TokenKind::Keyword(
+                    KeywordKind::Function
+                    | KeywordKind::Let
+                    | KeywordKind::Module
+                    | KeywordKind::Type
+                    | KeywordKind::Enum
+                    | KeywordKind::Trait,
+                )
+                | TokenKind::Annotation(_),
+            ) => (),
+            _ => return self.unexpected(&format!("a declaration after `{}`", text)),
+        }
+
+        Ok(self.next_entry()?.map(|entry| HugTreeEntry {
+            span: span.to(entry.span),
+            public,
+            ..entry
+        }))
+    }
+
+    /// Parses the whole file. Statements with errors are left out of the tree, which shouldn't be
+    /// run if any of the diagnostics is an error.
+    pub fn parse(&mut self) -> (HugTree, Vec<Diagnostic>) {
+        while !self.is_eof() {
+            self.annotation_state.reset();
+            let entry = match self.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(HugTreeParseError) => {
+                    self.synchronize();
+                    continue;
+                }
+            };
+
+            self.tree.entries.push(entry);
+        }
+
+        if let Some(module) = self.module {
+            let mut entries = std::mem::take(&mut self.tree.entries);
+            self.qualify(module, &mut entries, 0);
+            self.tree.entries = entries;
         }
 
-        self.tree
+        declare_globals(&self.tree.entries, &mut self.globals);
+        for (variable, span) in std::mem::take(&mut self.global_assignments) {
+            // `point.x` assigns to a field of the global `point`
+            let path = self.idents.resolve(variable).to_string();
+            let ends = std::iter::once(path.len()).chain(path.rmatch_indices('.').map(|(i, _)| i));
+            let global = ends.into_iter().find_map(|end| {
+                let name = self.idents.get(&path[..end])?;
+                Some((name, *self.globals.get(&name)?, end < path.len()))
+            });
+
+            match global {
+                Some((name, global @ HugTreeGlobal::Variable { .. }, field)) => {
+                    self.check_assignment(name, field, span, Some(global))
+                }
+                Some((_, HugTreeGlobal::Item, true)) => {
+                    self.check_assignment(variable, false, span, None)
+                }
+                Some((name, global, _)) => self.check_assignment(name, false, span, Some(global)),
+                // Defined in another file, which this parser can't see
+                None if path.contains('.') => (),
+                None => self.check_assignment(variable, false, span, None),
+            }
+        }
+
+        // Assignments to globals are checked last, report everything in source order
+        self.diagnostics.sort_by_key(|d| d.span.start.offset);
+
+        self.tree.locals = self.scopes.slots();
+        (
+            std::mem::take(&mut self.tree),
+            std::mem::take(&mut self.diagnostics),
+        )
+    }
+}
+
+/// Collects the globals `entries` define, including the ones inside modules.
+fn declare_globals(entries: &[HugTreeEntry], globals: &mut HashMap<Ident, HugTreeGlobal>) {
+    for entry in entries {
+        match &entry.kind {
+            HugTreeEntryKind::VariableDefinition {
+                variable,
+                slot: None,
+                mutable,
+                ..
+            } => {
+                let global = HugTreeGlobal::Variable { mutable: *mutable };
+                globals.insert(*variable, global);
+            }
+            HugTreeEntryKind::ModuleDefinition { module, body } => {
+                globals.insert(*module, HugTreeGlobal::Item);
+                declare_globals(body, globals);
+            }
+            HugTreeEntryKind::Implementation { methods, .. } => declare_globals(methods, globals),
+            HugTreeEntryKind::FunctionDefinition(HugTreeFunction { name, .. })
+            | HugTreeEntryKind::ExternalFunctionDefinition { function: name, .. }
+            | HugTreeEntryKind::ExternalTypeDefinition { _type: name }
+            | HugTreeEntryKind::TypeDefinition { _type: name, .. }
+            | HugTreeEntryKind::EnumDefinition { _type: name, .. }
+            | HugTreeEntryKind::TraitDefinition { _trait: name, .. }
+            | HugTreeEntryKind::ExternalModuleDefinition { module: name, .. } => {
+                globals.insert(*name, HugTreeGlobal::Item);
+            }
+            _ => (),
+        }
     }
 }
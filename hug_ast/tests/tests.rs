@@ -0,0 +1,1623 @@
+use hug_ast::{
+    checker::{HugTypeChecker, HugTypeGlobals},
+    HugTree, HugTreeBinaryOperator, HugTreeEntry, HugTreeEntryKind, HugTreeExpression,
+    HugTreeExpressionKind, HugTreeFormatSegment, HugTreeMatch, HugTreePatternKind, HugTreePayload,
+};
+use hug_lib::{diagnostic::Diagnostic, interner::Interner};
+
+fn parse(program: &str) -> HugTree {
+    let mut idents = Interner::new();
+    let (pairs, diagnostics) = hug_lexer::lex(program, &mut idents);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    let (tree, diagnostics) = HugTree::from_token_pairs(pairs, &mut idents);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    tree
+}
+
+fn diagnostics(program: &str) -> Vec<Diagnostic> {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex(program, &mut idents);
+    HugTree::from_token_pairs(pairs, &mut idents).1
+}
+
+/// The line, column and message of every diagnostic.
+fn errors(program: &str) -> Vec<(usize, usize, String)> {
+    diagnostics(program)
+        .into_iter()
+        .map(|d| (d.span.start.line, d.span.start.column, d.message))
+        .collect()
+}
+
+const DOCS_PROGRAM: &str = r#"
+//! Some helpers.
+//! Second line.
+
+/// 8-bit Integer
+@extern type Int8
+
+/// The answer
+///
+/// Computed very carefully.
+let answer = 42
+
+/// Dangling docs on a call are dropped
+print(answer)
+
+let undocumented = 1
+
+/// Something with an area
+trait Shape {
+    /// How much space it takes
+    /// in square units
+    function area(self) -> Float64
+    function name(self) -> String
+}
+
+/// Bytes are shapes too
+impl Shape for Int8 {
+    function name(self) -> String {
+        return "byte"
+    }
+    /// Always empty
+    function area(self) -> Float64 {
+        return 0.0
+    }
+}
+"#;
+
+#[test]
+fn doc_comments() {
+    let tree = parse(DOCS_PROGRAM);
+
+    assert_eq!(tree.docs.as_deref(), Some("Some helpers.\nSecond line."));
+
+    let docs = tree
+        .entries
+        .iter()
+        .map(|e| e.docs.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        docs,
+        vec![
+            Some("8-bit Integer"),
+            Some("The answer\n\nComputed very carefully."),
+            None,
+            None,
+            Some("Something with an area"),
+            Some("Bytes are shapes too"),
+        ]
+    );
+    assert!(matches!(
+        tree.entries[0].kind,
+        HugTreeEntryKind::ExternalTypeDefinition { .. }
+    ));
+
+    // Methods keep their own docs, not the ones of their trait or `impl`
+    let HugTreeEntryKind::TraitDefinition { methods, .. } = &tree.entries[4].kind else {
+        panic!("Expected a trait, found {:?}", tree.entries[4]);
+    };
+    let docs = methods
+        .iter()
+        .map(|method| method.docs.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        docs,
+        vec![Some("How much space it takes\nin square units"), None]
+    );
+
+    let HugTreeEntryKind::Implementation { methods, .. } = &tree.entries[5].kind else {
+        panic!("Expected an impl, found {:?}", tree.entries[5]);
+    };
+    let docs = methods
+        .iter()
+        .map(|method| method.docs.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(docs, vec![None, Some("Always empty")]);
+}
+
+const CONTROL_FLOW_PROGRAM: &str = r#"
+if a == 1 && !b {
+    print(a)
+} else if a < 1 {
+} else {
+    loop {
+        break
+    }
+}
+
+while (a) {
+    return 1
+}
+
+for i in 0..=10 {
+    continue
+}
+"#;
+
+#[test]
+fn control_flow() {
+    let tree = parse(CONTROL_FLOW_PROGRAM);
+    assert_eq!(tree.entries.len(), 3);
+
+    let HugTreeEntryKind::If {
+        condition,
+        body,
+        otherwise: Some(otherwise),
+    } = &tree.entries[0].kind
+    else {
+        panic!("Expected an if statement, found {:?}", tree.entries[0]);
+    };
+    assert!(matches!(
+        condition.kind,
+        HugTreeExpressionKind::Binary {
+            operator: HugTreeBinaryOperator::And,
+            ..
+        }
+    ));
+    assert_eq!(body.len(), 1);
+
+    // else if is an else block containing another if
+    let HugTreeEntryKind::If {
+        otherwise: Some(otherwise),
+        ..
+    } = &otherwise[0].kind
+    else {
+        panic!("Expected an else if, found {:?}", otherwise);
+    };
+    let HugTreeEntryKind::Loop { body } = &otherwise[0].kind else {
+        panic!("Expected a loop, found {:?}", otherwise);
+    };
+    assert!(matches!(body[0].kind, HugTreeEntryKind::Break));
+
+    let HugTreeEntryKind::While { body, .. } = &tree.entries[1].kind else {
+        panic!("Expected a while loop, found {:?}", tree.entries[1]);
+    };
+    assert!(matches!(
+        body[0].kind,
+        HugTreeEntryKind::Return { value: Some(_) }
+    ));
+
+    assert!(matches!(
+        &tree.entries[2].kind,
+        HugTreeEntryKind::For {
+            iterable: HugTreeExpression {
+                kind: HugTreeExpressionKind::Range {
+                    inclusive: true,
+                    ..
+                },
+                ..
+            },
+            ..
+        }
+    ));
+    assert_eq!(tree.entries[2].span.start.line, 15);
+    assert_eq!(tree.entries[2].span.end.line, 17);
+}
+
+#[test]
+fn break_outside_loop() {
+    assert_eq!(
+        errors("if true { break }"),
+        vec![(1, 11, "`break` outside of a loop".to_string())]
+    );
+}
+
+/// Renders an expression with explicit grouping, like `(+ 1 (* 2 3))`.
+fn grouped(expression: &HugTreeExpression) -> String {
+    match &expression.kind {
+        HugTreeExpressionKind::Variable(_) => "x".to_string(),
+        HugTreeExpressionKind::Local { slot, .. } => format!("${}", slot),
+        HugTreeExpressionKind::Value(value) => value.to_string(),
+        HugTreeExpressionKind::Number { literal, .. } => literal.clone(),
+        HugTreeExpressionKind::Call { args, .. } => {
+            let args = args.iter().map(grouped).collect::<Vec<_>>();
+            format!("(call {})", args.join(" "))
+        }
+        HugTreeExpressionKind::Unary { operator, operand } => {
+            format!("({:?} {})", operator, grouped(operand))
+        }
+        HugTreeExpressionKind::Binary {
+            operator,
+            left,
+            right,
+        } => format!("({:?} {} {})", operator, grouped(left), grouped(right)),
+        HugTreeExpressionKind::Range { start, end, .. } => {
+            format!("(Range {} {})", grouped(start), grouped(end))
+        }
+        other => panic!("Unexpected expression {:?}", other),
+    }
+}
+
+fn parse_expression(source: &str) -> String {
+    let tree = parse(&format!("let x = {}", source));
+    match &tree.entries[0].kind {
+        HugTreeEntryKind::VariableDefinition { value, .. } => grouped(value),
+        other => panic!("Expected a variable definition, found {:?}", other),
+    }
+}
+
+#[test]
+fn precedence() {
+    let cases = [
+        ("1 + 2 * 3 - 4", "(Subtract (Add 1 (Multiply 2 3)) 4)"),
+        ("(1 + 2) * 3", "(Multiply (Add 1 2) 3)"),
+        ("8 / 4 / 2", "(Divide (Divide 8 4) 2)"),
+        ("-x * 2", "(Multiply (Negate x) 2)"),
+        ("!x || x && x", "(Or (Not x) (And x x))"),
+        (
+            "1 << 2 + 3 & 4 ^ 5 | 6",
+            "(BinaryOr (BinaryXOr (BinaryAnd (ShiftLeft 1 (Add 2 3)) 4) 5) 6)",
+        ),
+        (
+            "x == 1 | 2 && x < 3",
+            "(And (IsEqualTo x (BinaryOr 1 2)) (LessThan x 3))",
+        ),
+        ("~x % 3", "(Modulus (BinaryNot x) 3)"),
+        ("add(1, 2 * 3) + 1", "(Add (call 1 (Multiply 2 3)) 1)"),
+        ("0..x + 1", "(Range 0 (Add x 1))"),
+    ];
+
+    for (source, expected) in cases {
+        assert_eq!(parse_expression(source), expected, "{}", source);
+    }
+
+    let tree = parse("print(1 + 2, (3))");
+    let HugTreeEntryKind::FunctionCall { args, .. } = &tree.entries[0].kind else {
+        panic!("Expected a function call, found {:?}", tree.entries[0]);
+    };
+    assert_eq!(args.len(), 2);
+    assert_eq!(args[0].span.start.column, 7);
+    assert_eq!(args[0].span.end.column, 12);
+    assert_eq!(args[1].span.start.column, 14);
+}
+
+#[test]
+fn negative_literals() {
+    // The sign is part of the literal, so the smallest value of a type fits in it
+    assert_eq!(parse_expression("-128i8"), "-128");
+    assert_eq!(parse_expression("-2147483648"), "-2147483648");
+    assert_eq!(parse_expression("-x - 1"), "(Subtract (Negate x) 1)");
+    assert_eq!(
+        errors("let x = 128i8\nlet y = -2147483649"),
+        vec![
+            (
+                1,
+                9,
+                "Integer literal is out of range for `Int8`".to_string()
+            ),
+            (
+                2,
+                9,
+                "Integer literal is out of range for `Int32`".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn chained_comparison() {
+    assert_eq!(
+        errors("let x = 1 < 2 < 3"),
+        vec![(
+            1,
+            15,
+            "Comparison operators cannot be chained, found `<` after a comparison".to_string()
+        )]
+    );
+}
+
+const FUNCTIONS_PROGRAM: &str = r#"
+/// Adds two numbers
+function add(a: Int32, b: Int32) -> Int32 {
+    return a + b
+}
+
+function nothing() {}
+"#;
+
+#[test]
+fn functions() {
+    let tree = parse(FUNCTIONS_PROGRAM);
+
+    let HugTreeEntryKind::FunctionDefinition(add) = &tree.entries[0].kind else {
+        panic!("Expected a function, found {:?}", tree.entries[0]);
+    };
+    assert_eq!(tree.entries[0].docs.as_deref(), Some("Adds two numbers"));
+    assert_eq!(add.parameters.len(), 2);
+    assert_eq!(add.parameters[1].span.start.column, 24);
+    assert_eq!(add.parameters[1].span.end.column, 32);
+    assert!(add.return_type.is_some());
+    assert!(matches!(
+        add.body[0].kind,
+        HugTreeEntryKind::Return { value: Some(_) }
+    ));
+
+    let HugTreeEntryKind::FunctionDefinition(nothing) = &tree.entries[1].kind else {
+        panic!("Expected a function, found {:?}", tree.entries[1]);
+    };
+    assert!(nothing.parameters.is_empty() && nothing.return_type.is_none());
+    assert!(nothing.body.is_empty());
+}
+
+#[test]
+fn nested_function() {
+    assert_eq!(
+        errors("loop { function inner() {} }"),
+        vec![(
+            1,
+            8,
+            "Functions can only be defined at the top level".to_string()
+        )]
+    );
+}
+
+#[test]
+fn local_slots() {
+    let tree = parse(
+        r#"
+let global = 1
+function f(a: Int32, b: Int32) -> Int32 {
+    let c = a + b
+    if c > 1 {
+        let d = c
+        let c = d + global
+    }
+    let e = c
+}
+"#,
+    );
+
+    let HugTreeEntryKind::FunctionDefinition(f) = &tree.entries[1].kind else {
+        panic!("Expected a function, found {:?}", tree.entries[1]);
+    };
+    // a, b, c, d and the shadowing c are alive at the same time
+    assert_eq!(f.locals, 5);
+
+    let slot = |entry: &HugTreeEntry| match &entry.kind {
+        HugTreeEntryKind::VariableDefinition { slot, value, .. } => (*slot, grouped(value)),
+        other => panic!("Expected a variable definition, found {:?}", other),
+    };
+    assert_eq!(slot(&f.body[0]), (Some(2), "(Add $0 $1)".to_string()));
+
+    let HugTreeEntryKind::If { body, .. } = &f.body[1].kind else {
+        panic!("Expected an if statement, found {:?}", f.body[1]);
+    };
+    assert_eq!(slot(&body[0]), (Some(3), "$2".to_string()));
+    assert_eq!(slot(&body[1]), (Some(4), "(Add $3 x)".to_string()));
+
+    // The block is over, so `d`'s slot is free again and `c` is the outer one
+    assert_eq!(slot(&f.body[2]), (Some(3), "$2".to_string()));
+    assert_eq!(slot(&tree.entries[0]).0, None);
+}
+
+#[test]
+fn assignments() {
+    let tree = parse(
+        r#"
+let mut total = 0
+function f(a: Int32) {
+    let mut b = a
+    b <<= 2
+    total += b
+}
+"#,
+    );
+
+    let HugTreeEntryKind::FunctionDefinition(f) = &tree.entries[1].kind else {
+        panic!("Expected a function, found {:?}", tree.entries[1]);
+    };
+    let assignment = |entry: &HugTreeEntry| match &entry.kind {
+        HugTreeEntryKind::Assignment {
+            slot,
+            operator,
+            value,
+            ..
+        } => (*slot, *operator, grouped(value)),
+        other => panic!("Expected an assignment, found {:?}", other),
+    };
+    assert_eq!(
+        assignment(&f.body[1]),
+        (
+            Some(1),
+            Some(HugTreeBinaryOperator::ShiftLeft),
+            "2".to_string()
+        )
+    );
+    assert_eq!(
+        assignment(&f.body[2]),
+        (None, Some(HugTreeBinaryOperator::Add), "$1".to_string())
+    );
+}
+
+#[test]
+fn immutable_assignment() {
+    let diagnostics = diagnostics(
+        r#"
+function f() {
+    let x = 1
+    x = 2
+    later -= 1
+}
+let later = 3
+let mut fine = 1
+fine = 2
+f = 4
+"#,
+    );
+
+    let messages = diagnostics
+        .iter()
+        .map(|d| (d.span.start.line, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (4, "Cannot assign twice to immutable variable `x`"),
+            (5, "Cannot assign twice to immutable variable `later`"),
+            (10, "Cannot assign to `f`, it is not a variable"),
+        ]
+    );
+    assert_eq!(diagnostics[0].span.start.column, 5);
+    assert_eq!(
+        diagnostics[0].hint.as_deref(),
+        Some("make it mutable: `let mut x`")
+    );
+}
+
+#[test]
+fn immutable_bindings() {
+    // Parameters and other bindings can't be made mutable, but a copy of them can
+    let bindings = diagnostics(
+        r#"
+type Point { x: Int32 }
+impl Point {
+    function move(self) -> Int32 {
+        self.x = 1
+        let mut self = self
+        self.x = 2
+        return self.x
+    }
+}
+for i in 0..3 {
+    i += 1
+}
+"#,
+    );
+
+    let messages = bindings
+        .iter()
+        .map(|d| (d.span.start.line, d.message.as_str(), d.hint.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                5,
+                "Cannot assign to a field of `self`, it can't be changed",
+                Some("change a copy of it: `let mut self = self`")
+            ),
+            (
+                12,
+                "Cannot assign to `i`, it can't be changed",
+                Some("change a copy of it: `let mut i = i`")
+            ),
+        ]
+    );
+}
+
+fn type_diagnostics(program: &str) -> Vec<Diagnostic> {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex(program, &mut idents);
+    let (mut tree, _) = HugTree::from_token_pairs(pairs, &mut idents);
+    let mut globals = HugTypeGlobals::default();
+    HugTypeChecker::new(&mut globals, &idents).check(&mut tree)
+}
+
+/// Type checks `program`, giving back the line and message of every diagnostic.
+fn type_errors(program: &str) -> Vec<(usize, String)> {
+    type_diagnostics(program)
+        .into_iter()
+        .map(|d| (d.span.start.line, d.message))
+        .collect()
+}
+
+#[test]
+fn type_checking() {
+    let errors = type_errors(
+        r#"
+function add(a: Int32, b: Int32) -> Int32 {
+    return a + b
+}
+function nothing() {}
+
+let small: UInt16 = 5
+let wide = small + 1
+let fine = add(1, 2) * 3
+let text = add("one", 2)
+let missing = add(1)
+let void = nothing()
+let bad: Bool = 1 < 2.5
+let mut total: Float64 = 0.5
+total += 1
+for c in "hug" {
+    let upper: Char = c
+}
+let huge: UInt8 = 300
+let what: Thing = 1
+"#,
+    );
+
+    assert_eq!(
+        errors,
+        vec![
+            (
+                10,
+                "Mismatched types: expected `Int32`, found `String`".to_string()
+            ),
+            (11, "`add` takes 2 arguments but 1 were given".to_string()),
+            (12, "Expected a value, found `Void`".to_string()),
+            (13, "Cannot compare `Int32` to `Float32`".to_string()),
+            (15, "Cannot apply `+` to `Float64` and `Int32`".to_string()),
+            (
+                19,
+                "Integer literal is out of range for `UInt8`".to_string()
+            ),
+            (20, "Unknown type `Thing`".to_string()),
+        ]
+    );
+    assert_eq!(
+        type_errors("function g() {}\nfunction g() {}"),
+        vec![(2, "`g` is defined twice".to_string())]
+    );
+    assert_eq!(
+        type_errors("function f() {\n}\nlet f = 1"),
+        vec![(3, "`f` is defined twice".to_string())]
+    );
+}
+
+#[test]
+fn return_types() {
+    let errors = type_errors(
+        r#"
+function a() -> Int32 {
+    if true {
+        return
+    }
+    return 1.5
+}
+function b() {
+    return 1
+}
+function c() -> UInt64 {
+    return 1 << 3
+}
+"#,
+    );
+
+    assert_eq!(
+        errors,
+        vec![
+            (
+                4,
+                "Mismatched types: expected `Int32`, found `Void`".to_string()
+            ),
+            (
+                6,
+                "Mismatched types: expected `Int32`, found `Float32`".to_string()
+            ),
+            (
+                9,
+                "Mismatched types: expected `Void`, found `Int32`".to_string()
+            ),
+        ]
+    );
+
+    let errors = type_errors(
+        r#"
+enum Light { Red, Green }
+function branches(n: Int32) -> Int32 {
+    if n > 0 {
+        return 1
+    } else if n < 0 {
+        return -1
+    } else {
+        return 0
+    }
+}
+function arms(light: Light) -> Int32 {
+    match light {
+        Light.Red => { return 1 }
+        Light.Green => { return 2 }
+    }
+}
+function forever() -> Int32 {
+    loop {
+        let x = 1
+    }
+}
+function nested() -> Int32 {
+    {
+        return 1
+    }
+}
+function empty() -> Int32 {}
+function one_branch(n: Int32) -> Int32 {
+    if n > 0 {
+        return 1
+    }
+}
+function missing_arm(light: Light) -> Int32 {
+    match light {
+        Light.Red => { return 1 }
+        Light.Green => {}
+    }
+}
+function stops() -> Int32 {
+    loop {
+        break
+    }
+}
+"#,
+    );
+
+    assert_eq!(
+        errors,
+        vec![
+            (
+                28,
+                "`empty` can end without returning a `Int32`".to_string()
+            ),
+            (
+                29,
+                "`one_branch` can end without returning a `Int32`".to_string()
+            ),
+            (
+                34,
+                "`missing_arm` can end without returning a `Int32`".to_string()
+            ),
+            (
+                40,
+                "`stops` can end without returning a `Int32`".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn unknown_names() {
+    let errors = type_errors(
+        r#"
+@extern function print(value: Int32)
+use utils
+
+print(undefined)
+foo()
+if true {
+    let y = 2
+}
+print(y)
+print(early)
+let early = 1
+helper(1)
+early = missing
+function f() {
+    print(early)
+    print(later)
+}
+let later = 2
+"#,
+    );
+
+    assert_eq!(
+        errors,
+        vec![
+            (5, "Unknown variable `undefined`".to_string()),
+            (6, "Unknown function `foo`".to_string()),
+            (10, "Unknown variable `y`".to_string()),
+            (11, "Unknown variable `early`".to_string()),
+            (13, "Unknown function `helper`".to_string()),
+            (14, "Unknown variable `missing`".to_string()),
+        ]
+    );
+}
+
+const RECOVERY_PROGRAM: &str = r#"
+let a = (1 + 2
+let b = 3
+function f(x Int32) {
+    print(b)
+}
+function g() {
+    let c = * 2
+    print(c)
+    let d 5
+}
+print(a, b
+else
+let e = 4
+}
+function h() {
+"#;
+
+#[test]
+fn error_recovery() {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex(RECOVERY_PROGRAM, &mut idents);
+    let (tree, diagnostics) = HugTree::from_token_pairs(pairs, &mut idents);
+
+    let errors = diagnostics
+        .iter()
+        .map(|d| (d.span.start.line, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            (3, "Expected `)`, found `let`"),
+            (4, "Expected `:`, found `Int32`"),
+            (8, "Expected an expression, found `*`"),
+            (10, "Expected `=`, found `5`"),
+            (
+                13,
+                "Expected `,` or `)` after function argument, found `else`"
+            ),
+            (13, "`else` without an `if`"),
+            (15, "Unmatched `}`"),
+            (16, "Unclosed block, expected `}`"),
+        ]
+    );
+
+    // Everything around the errors is still parsed
+    let names = tree
+        .entries
+        .iter()
+        .map(|entry| match &entry.kind {
+            HugTreeEntryKind::VariableDefinition { variable, .. } => idents.resolve(*variable),
+            HugTreeEntryKind::FunctionDefinition(f) => idents.resolve(f.name),
+            other => panic!("Unexpected entry {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["b", "g", "e"]);
+
+    let HugTreeEntryKind::FunctionDefinition(g) = &tree.entries[1].kind else {
+        unreachable!()
+    };
+    assert_eq!(g.body.len(), 1);
+}
+
+#[test]
+fn unterminated_format_string() {
+    // The tokenizer reports it too, but the parser still sees the token
+    assert_eq!(
+        errors("function f() -> String {\n    return f\""),
+        vec![
+            (1, 24, "Unclosed block, expected `}`".to_string()),
+            (2, 12, "Unterminated format string".to_string()),
+        ]
+    );
+    assert_eq!(
+        errors("let x = f\"abc"),
+        vec![(1, 9, "Unterminated format string".to_string())]
+    );
+}
+
+const MODULES_PROGRAM: &str = r#"
+use utils
+module math {
+    function double(x: Int32) -> Int32 {
+        return add(x, x)
+    }
+    function add(a: Int32, b: Int32) -> Int32 {
+        return a + b
+    }
+}
+let four = math.double(2)
+"#;
+
+#[test]
+fn modules() {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex(MODULES_PROGRAM, &mut idents);
+    let (tree, diagnostics) = HugTree::from_token_pairs(pairs, &mut idents);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let HugTreeEntryKind::Use { module } = &tree.entries[0].kind else {
+        panic!("Expected a use, found {:?}", tree.entries[0]);
+    };
+    assert_eq!(idents.resolve(*module), "utils");
+
+    // Names inside the module are qualified, including calls between its functions
+    let HugTreeEntryKind::ModuleDefinition { module, body } = &tree.entries[1].kind else {
+        panic!("Expected a module, found {:?}", tree.entries[1]);
+    };
+    assert_eq!(idents.resolve(*module), "math");
+    let HugTreeEntryKind::FunctionDefinition(double) = &body[0].kind else {
+        unreachable!()
+    };
+    assert_eq!(idents.resolve(double.name), "math.double");
+    let HugTreeEntryKind::Return {
+        value:
+            Some(HugTreeExpression {
+                kind: HugTreeExpressionKind::Call { function, .. },
+                ..
+            }),
+    } = &double.body[0].kind
+    else {
+        panic!("Expected a return, found {:?}", double.body[0]);
+    };
+    assert_eq!(idents.resolve(*function), "math.add");
+
+    let HugTreeEntryKind::VariableDefinition { value, .. } = &tree.entries[2].kind else {
+        unreachable!()
+    };
+    let HugTreeExpressionKind::Call { function, .. } = &value.kind else {
+        panic!("Expected a call, found {:?}", value);
+    };
+    assert_eq!(idents.resolve(*function), "math.double");
+}
+
+#[test]
+fn module_errors() {
+    assert_eq!(
+        errors("function f() {\n    use utils\n    public let y = 2\n}\npublic x = 1\nmodule m {\n    let x = 1\n"),
+        vec![
+            (
+                2,
+                5,
+                "`use` can only be used at the top level or in a module".to_string()
+            ),
+            (
+                3,
+                5,
+                "`public` can only be used at the top level or in a module".to_string()
+            ),
+            (5, 8, "Expected a declaration after `public`, found `x`".to_string()),
+            (6, 10, "Unclosed module, expected `}`".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn private_types() {
+    // Fields and signatures inside a module can use its private types
+    let errors = type_errors(
+        r#"
+module m {
+    type P { x: Int32 }
+    public type Q { p: P }
+    public function mk() -> P {
+        return P { x: 1 }
+    }
+}
+let p: m.P = m.mk()
+"#,
+    );
+
+    assert_eq!(
+        errors,
+        vec![(9, "`P` is private to module `m`".to_string())]
+    );
+}
+
+const STRUCTS_PROGRAM: &str = r#"
+type Point {
+    x: Float64
+    y: Float64,
+}
+type Line { start: Point, end: Point }
+
+let mut origin = Point { y: 0.0, x: 0.0 }
+function length(line: Line) -> Float64 {
+    let mut end = line.end
+    end.x -= line.start.x
+    return end.x
+}
+if origin.x == 0.0 {
+    origin.y = 1.0
+}
+"#;
+
+#[test]
+fn structs() {
+    let tree = parse(STRUCTS_PROGRAM);
+
+    let HugTreeEntryKind::TypeDefinition { fields, .. } = &tree.entries[0].kind else {
+        panic!("Expected a type, found {:?}", tree.entries[0]);
+    };
+    assert_eq!(fields.len(), 2);
+
+    let HugTreeEntryKind::VariableDefinition { value, .. } = &tree.entries[2].kind else {
+        unreachable!()
+    };
+    assert!(matches!(
+        &value.kind,
+        HugTreeExpressionKind::Struct { fields, .. } if fields.len() == 2
+    ));
+
+    let HugTreeEntryKind::FunctionDefinition(length) = &tree.entries[3].kind else {
+        unreachable!()
+    };
+    assert!(matches!(
+        &length.body[1].kind,
+        HugTreeEntryKind::Assignment { slot: Some(1), fields, .. } if fields.len() == 1
+    ));
+    let HugTreeEntryKind::Assignment { value, .. } = &length.body[1].kind else {
+        unreachable!()
+    };
+    assert!(matches!(
+        &value.kind,
+        HugTreeExpressionKind::Field { value, .. }
+            if matches!(value.kind, HugTreeExpressionKind::Field { .. })
+    ));
+
+    // The `{` after `origin.x == 0.0` starts the body, not a struct
+    assert!(matches!(
+        &tree.entries[4].kind,
+        HugTreeEntryKind::If { body, .. } if body.len() == 1
+    ));
+}
+
+#[test]
+fn struct_errors() {
+    let found = type_errors(
+        r#"
+type Point { x: Float64, y: Float64 }
+let p = Point { x: 1.0, z: 2.0 }
+let q = Point { x: 1.0, x: 2.0, y: 1.0 }
+let r: Int32 = p.x
+let s = p.z
+let t = p == q
+let u = Int32 { x: 1 }
+"#,
+    );
+
+    assert_eq!(
+        found,
+        vec![
+            (3, "`Point` has no field `z`".to_string()),
+            (3, "Missing field `y` in `Point`".to_string()),
+            (4, "Field `x` is given twice".to_string()),
+            (
+                5,
+                "Mismatched types: expected `Int32`, found `Float64`".to_string()
+            ),
+            (6, "`Point` has no field `z`".to_string()),
+            (7, "Cannot compare `Point` to `Point`".to_string()),
+            (8, "`Int32` is not a struct".to_string()),
+        ]
+    );
+    assert_eq!(
+        errors("type Point { x: Float64, x: Int32 }\nlet p = Point { x: 1.0 }\np.x = 2.0"),
+        vec![
+            (1, 26, "Field `x` is defined twice".to_string()),
+            (
+                3,
+                1,
+                "Cannot assign to a field of immutable variable `p`".to_string()
+            ),
+        ]
+    );
+    // The first declaration is the one that is used
+    assert_eq!(
+        type_errors("type P { x: Int32 }\ntype P { y: Int32 }\nlet p = P { x: 1 }"),
+        vec![(2, "`P` is defined twice".to_string())]
+    );
+}
+
+const ENUMS_PROGRAM: &str = r#"
+enum Shape {
+    Circle(Float64),
+    Rect { width: Float64, height: Float64 }
+    Empty
+}
+
+function area(shape: Shape) -> Float64 {
+    return match shape {
+        Shape.Circle(radius) => radius * radius * 3.14,
+        Shape.Rect { width, height: h } => width * h,
+        Shape.Empty => 0.0,
+    }
+}
+
+match 4 {
+    0 => print("none")
+    1..=3 => { print("few") }
+    _ => print("many")
+}
+"#;
+
+#[test]
+fn enums() {
+    let tree = parse(ENUMS_PROGRAM);
+
+    let HugTreeEntryKind::EnumDefinition { variants, .. } = &tree.entries[0].kind else {
+        panic!("Expected an enum, found {:?}", tree.entries[0]);
+    };
+    assert!(matches!(&variants[0].payload, HugTreePayload::Tuple(types) if types.len() == 1));
+    assert!(matches!(&variants[1].payload, HugTreePayload::Struct(fields) if fields.len() == 2));
+    assert!(matches!(&variants[2].payload, HugTreePayload::Unit));
+
+    let HugTreeEntryKind::FunctionDefinition(area) = &tree.entries[1].kind else {
+        unreachable!()
+    };
+    let HugTreeEntryKind::Return { value: Some(value) } = &area.body[0].kind else {
+        unreachable!()
+    };
+    let HugTreeExpressionKind::Match(HugTreeMatch { arms, .. }) = &value.kind else {
+        panic!("Expected a match, found {:?}", value);
+    };
+    assert_eq!(arms.len(), 3);
+    // Bindings only live in their arm, so `width` reuses the slot of `radius`
+    assert_eq!(area.locals, 3);
+    let HugTreePatternKind::Variant { payload, .. } = &arms[1].pattern.kind else {
+        unreachable!()
+    };
+    assert!(matches!(
+        payload,
+        HugTreePayload::Struct(fields)
+            if fields.iter().all(|(_, pattern)| matches!(pattern.kind, HugTreePatternKind::Binding { .. }))
+    ));
+
+    let HugTreeEntryKind::Match(HugTreeMatch { arms, .. }) = &tree.entries[2].kind else {
+        panic!("Expected a match, found {:?}", tree.entries[2]);
+    };
+    assert!(matches!(
+        arms[0].pattern.kind,
+        HugTreePatternKind::Literal(_)
+    ));
+    assert!(matches!(
+        arms[1].pattern.kind,
+        HugTreePatternKind::Range {
+            inclusive: true,
+            ..
+        }
+    ));
+    assert!(matches!(arms[2].pattern.kind, HugTreePatternKind::Wildcard));
+}
+
+#[test]
+fn match_errors() {
+    let found = type_errors(
+        r#"
+enum Shape { Circle(Float64), Rect { width: Float64, height: Float64 }, Empty }
+let a = Shape.Circle(1.0, 2.0)
+let b = Shape.Rect { width: 1.0 }
+let c = Shape.Circle
+let d = Shape.Square
+let e = match a {
+    Shape.Circle(_) => 1, Shape.Empty => 3,
+    Shape.Rect { width: 0.0 } => 2,
+}
+let f = match a {
+    Shape.Circle(r) => r,
+    Shape.Rect { width } => 1,
+    _ => 0.0,
+}
+let g = match true {
+    true => 1,
+}
+let h = match 3 {
+    0..10 => 1,
+    10 => 2,
+}
+let i = match a {
+    Shape.Empty => 1,
+    Shape.Circle(2) => 1,
+    Shape.Square => 1,
+    _ => 1,
+}
+let j = a == b
+"#,
+    );
+
+    assert_eq!(
+        found,
+        vec![
+            (
+                3,
+                "`Shape.Circle` takes 1 values but 2 were given".to_string()
+            ),
+            (4, "Missing field `height` in `Shape.Rect`".to_string()),
+            (
+                5,
+                "`Shape.Circle` needs values, like `Shape.Circle(...)`".to_string()
+            ),
+            (6, "`Shape` has no variant `Square`".to_string()),
+            (
+                7,
+                "Non-exhaustive `match`, `Shape.Rect { width: _, height: _ }` is not covered"
+                    .to_string()
+            ),
+            (
+                13,
+                "Mismatched types: expected `Float64`, found `Int32`".to_string()
+            ),
+            (
+                16,
+                "Non-exhaustive `match`, `false` is not covered".to_string()
+            ),
+            (
+                19,
+                "Non-exhaustive `match`, `-2147483648..=-1` is not covered".to_string()
+            ),
+            (
+                25,
+                "Mismatched types: expected `Float64`, found `Int32`".to_string()
+            ),
+            (26, "`Shape` has no variant `Square`".to_string()),
+            (29, "Cannot compare `Shape` to `Shape`".to_string()),
+        ]
+    );
+    assert_eq!(
+        errors("enum A { X, X }\nmatch 1 {\n    X(y) => print(y)\n}"),
+        vec![
+            (1, 13, "Variant `X` is defined twice".to_string()),
+            (3, 5, "`X` needs its enum, like `Shape.X`".to_string()),
+        ]
+    );
+    assert_eq!(
+        errors("match 1 {\n    1 print(1)\n}"),
+        vec![(2, 7, "Expected `=>`, found `print`".to_string())]
+    );
+}
+
+#[test]
+fn integer_ranges() {
+    let found = type_errors(
+        r#"
+let byte: UInt8 = 7
+let small: Int8 = 7
+let a = match byte {
+    0..=255 => 1,
+}
+let b = match byte {
+    0..=127 => 1,
+    128..=255 => 2,
+}
+let c = match small {
+    -128..0 => 1,
+    0 => 2,
+    1..=127 => 3,
+}
+let d = match byte {
+    0..=100 => 1,
+    102..=255 => 2,
+}
+let e = match byte {
+    0..128 => 1,
+}
+let f = match byte {
+    10..1 => 1,
+    5..5 => 2,
+    _ => 3,
+}
+"#,
+    );
+
+    assert_eq!(
+        found,
+        vec![
+            (
+                16,
+                "Non-exhaustive `match`, `101` is not covered".to_string()
+            ),
+            (
+                20,
+                "Non-exhaustive `match`, `128..=255` is not covered".to_string()
+            ),
+            (24, "Range `10..1` is empty, it matches nothing".to_string()),
+            (25, "Range `5..5` is empty, it matches nothing".to_string()),
+        ]
+    );
+}
+
+const METHODS_PROGRAM: &str = r#"
+type Point { x: Float64, y: Float64 }
+
+impl Point {
+    /// A point at `x`, `y`
+    function new(x: Float64, y: Float64) -> Point {
+        return Point { x: x, y: y }
+    }
+
+    function length(self) -> Float64 {
+        return self.x + self.y
+    }
+}
+
+let origin = Point.new(0.0, 0.0)
+function show(point: Point) {
+    point.length()
+    let sum = point.length() + origin.length()
+}
+"#;
+
+#[test]
+fn methods() {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex(METHODS_PROGRAM, &mut idents);
+    let (tree, diagnostics) = HugTree::from_token_pairs(pairs, &mut idents);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let HugTreeEntryKind::Implementation { methods, .. } = &tree.entries[1].kind else {
+        panic!("Expected an impl, found {:?}", tree.entries[1]);
+    };
+    let names = methods
+        .iter()
+        .map(|method| match &method.kind {
+            HugTreeEntryKind::FunctionDefinition(function) => idents.resolve(function.name),
+            other => panic!("Expected a method, found {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Point.new", "Point.length"]);
+    assert_eq!(methods[0].docs.as_deref(), Some("A point at `x`, `y`"));
+
+    let HugTreeEntryKind::FunctionDefinition(length) = &methods[1].kind else {
+        unreachable!()
+    };
+    assert_eq!(idents.resolve(length.parameters[0]._type.name), "Point");
+
+    let HugTreeEntryKind::FunctionDefinition(show) = &tree.entries[3].kind else {
+        unreachable!()
+    };
+    assert!(matches!(
+        show.body[0].kind,
+        HugTreeEntryKind::MethodCall { .. }
+    ));
+    let HugTreeEntryKind::VariableDefinition { value, .. } = &show.body[1].kind else {
+        unreachable!()
+    };
+    let HugTreeExpressionKind::Binary { left, right, .. } = &value.kind else {
+        unreachable!()
+    };
+    assert!(matches!(
+        left.kind,
+        HugTreeExpressionKind::MethodCall { .. }
+    ));
+    // `origin` is a global, so it is a path until the checker splits it
+    assert!(matches!(right.kind, HugTreeExpressionKind::Call { .. }));
+}
+
+#[test]
+fn method_errors() {
+    let found = type_errors(
+        r#"
+type Point { x: Float64, y: Float64 }
+impl Point {
+    function new() -> Point { return Point { x: 0.0, y: 0.0 } }
+    function scaled(self, by: Float64) -> Point { return Point.new() }
+}
+let p = Point.new()
+let a = p.scaled()
+let b = p.scaled(2)
+let c = p.missing()
+let d = p.new()
+let e = p.x.scaled(1.0)
+let f: Int32 = p.scaled(1.0)
+"#,
+    );
+
+    assert_eq!(
+        found,
+        vec![
+            (8, "`scaled` takes 1 arguments but 0 were given".to_string()),
+            (
+                9,
+                "Mismatched types: expected `Float64`, found `Int32`".to_string()
+            ),
+            (10, "`Point` has no method `missing`".to_string()),
+            (
+                11,
+                "`new` doesn't take `self`, call it as `Point.new(...)`".to_string()
+            ),
+            (12, "`Float64` has no method `scaled`".to_string()),
+            (
+                13,
+                "Mismatched types: expected `Int32`, found `Point`".to_string()
+            ),
+        ]
+    );
+    assert_eq!(
+        errors(
+            "function f(self) {}\nimpl Point {\n    let x = 1\n    function g(a: Int32, self) {}\n}"
+        ),
+        vec![
+            (
+                1,
+                12,
+                "`self` can only be a parameter of functions in an `impl` or `trait`".to_string()
+            ),
+            (3, 5, "Only functions can be defined in an `impl` block".to_string()),
+            (4, 26, "`self` must be the first parameter".to_string()),
+        ]
+    );
+}
+
+const TRAITS_PROGRAM: &str = r#"
+trait Shape {
+    /// How much room it takes
+    function area(self) -> Float64
+    function grown(self, by: Float64) -> Self
+}
+
+type Square { side: Float64 }
+type Vector { x: Int32, y: Int32 }
+
+impl Shape for Square {
+    function area(self) -> Float64 { return self.side * self.side }
+    function grown(self, by: Float64) -> Square { return Square { side: self.side + by } }
+}
+impl Add for Vector {
+    function add(self, other: Self) -> Vector {
+        return Vector { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+impl Eq for Vector {
+    function eq(self, other: Vector) -> Bool { return self.x == other.x && self.y == other.y }
+}
+impl Display for Vector {
+    function format(self) -> String { return f"({self.x}, {self.y})" }
+}
+
+function total(a: Shape, b: Shape) -> Float64 {
+    return a.area() + b.grown(1.0).area()
+}
+let v = Vector { x: 1, y: 2 } + Vector { x: 3, y: 4 }
+let same = v != v
+let text = f"{v}"
+let sum = total(Square { side: 1.0 }, Square { side: 2.0 })
+"#;
+
+#[test]
+fn traits() {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex(TRAITS_PROGRAM, &mut idents);
+    let (mut tree, diagnostics) = HugTree::from_token_pairs(pairs, &mut idents);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let HugTreeEntryKind::TraitDefinition { methods, .. } = &tree.entries[0].kind else {
+        panic!("Expected a trait, found {:?}", tree.entries[0]);
+    };
+    let names = methods
+        .iter()
+        .map(|method| idents.resolve(method.name))
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["area", "grown"]);
+    // `Self` is the trait until a type implements it
+    let grown = &methods[1];
+    assert_eq!(idents.resolve(grown.parameters[0]._type.name), "Shape");
+    assert_eq!(
+        idents.resolve(grown.return_type.as_ref().unwrap().name),
+        "Shape"
+    );
+
+    let HugTreeEntryKind::Implementation { _type, _trait, .. } = &tree.entries[3].kind else {
+        panic!("Expected an impl, found {:?}", tree.entries[3]);
+    };
+    assert_eq!(idents.resolve(*_type), "Square");
+    assert_eq!(_trait.map(|t| idents.resolve(t)), Some("Shape"));
+
+    let mut globals = HugTypeGlobals::default();
+    let diagnostics = HugTypeChecker::new(&mut globals, &idents).check(&mut tree);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    // The operators and format strings call the methods of the builtin traits
+    let value = |entry: &HugTreeEntry| match &entry.kind {
+        HugTreeEntryKind::VariableDefinition { value, .. } => value.kind.clone(),
+        other => panic!("Expected a variable, found {:?}", other),
+    };
+    let HugTreeExpressionKind::Call { function, .. } = value(&tree.entries[8]) else {
+        panic!("Expected a call, found {:?}", tree.entries[8]);
+    };
+    assert_eq!(idents.resolve(function), "Vector.add");
+    let HugTreeExpressionKind::Unary { operand, .. } = value(&tree.entries[9]) else {
+        panic!("Expected `!`, found {:?}", tree.entries[9]);
+    };
+    assert!(matches!(
+        operand.kind,
+        HugTreeExpressionKind::Call { function, .. } if idents.resolve(function) == "Vector.eq"
+    ));
+    let HugTreeExpressionKind::FormatString(segments) = value(&tree.entries[10]) else {
+        unreachable!()
+    };
+    assert!(matches!(
+        &segments[0],
+        HugTreeFormatSegment::Expression {
+            expression: HugTreeExpression {
+                kind: HugTreeExpressionKind::Call { .. },
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn trait_errors() {
+    let found = type_errors(
+        r#"
+trait Shape { function area(self) -> Float64 }
+type Square { side: Float64 }
+type Point { x: Int32 }
+impl Shape for Square {
+    function area(self) -> Int32 { return 1 }
+    function side(self) -> Float64 { return self.side }
+}
+impl Shape for Point {}
+impl Point for Square {}
+impl Missing for Square {}
+function area(shape: Shape) -> Float64 { return shape.perimeter() }
+let a = area(1)
+let b = Point { x: 1 } + Point { x: 2 }
+let c = Point { x: 1 } == Point { x: 1 }
+"#,
+    );
+
+    assert_eq!(
+        found,
+        vec![
+            (
+                6,
+                "`area` should be `function(Square) -> Float64` to implement `Shape`, found \
+                 `function(Square) -> Int32`"
+                    .to_string()
+            ),
+            (7, "`side` is not a method of `Shape`".to_string()),
+            (
+                9,
+                "Missing method `area` in `impl Shape for Point`".to_string()
+            ),
+            (10, "`Point` is a type, not a trait".to_string()),
+            (11, "Unknown trait `Missing`".to_string()),
+            (13, "`Int32` doesn't implement `Shape`".to_string()),
+            (14, "Cannot apply `+` to `Point` and `Point`".to_string()),
+            (15, "Cannot compare `Point` to `Point`".to_string()),
+            // Bodies of functions are checked last
+            (12, "`Shape` has no method `perimeter`".to_string()),
+        ]
+    );
+    assert_eq!(
+        errors(
+            "trait Shape {\n    function area(self) -> Float64 { return 1.0 }\n}\nlet x: Self = 1"
+        ),
+        vec![
+            (2, 36, "Methods of a trait can't have a body".to_string()),
+            (
+                4,
+                8,
+                "`Self` can only be used in an `impl` or `trait`".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn method_names() {
+    // Trait methods are named after the type, like the methods of its own `impl`s
+    let found = type_diagnostics(
+        r#"
+trait T { function f(self) -> Int32 }
+trait U { function f(self) -> Int32 }
+type A { x: Int32 }
+impl T for A {
+    function f(self) -> Int32 { return 1 }
+}
+impl U for A {
+    function f(self) -> Int32 { return 2 }
+}
+impl A {
+    function f(self) -> Int32 { return 3 }
+}
+"#,
+    );
+
+    let messages = found
+        .iter()
+        .map(|d| (d.span.start.line, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (9, "`A` already has a method `f`"),
+            (12, "`A` already has a method `f`"),
+        ]
+    );
+    assert_eq!(
+        found[0].hint.as_deref(),
+        Some("methods of traits share their names with the other methods of a type")
+    );
+}
+
+const GENERICS_PROGRAM: &str = r#"
+type Pair<A, B> { first: A, second: B }
+enum Option<T> { None, Some(T) }
+function first<A, B>(pair: Pair<A, B>) -> A { return pair.first }
+function sum<T: Add>(a: T, b: T) -> T { return a + b }
+let nested: Pair<Int32, Pair<Int32, Int32>> = Pair { first: 1, second: Pair { first: 2, second: 3 } }
+let one = first(nested)
+let maybe: Option<String> = Option.None
+let three = sum(1, 2)
+"#;
+
+#[test]
+fn generics() {
+    let tree = parse(GENERICS_PROGRAM);
+
+    let HugTreeEntryKind::TypeDefinition { generics, .. } = &tree.entries[0].kind else {
+        panic!("Expected a type, found {:?}", tree.entries[0]);
+    };
+    assert_eq!(generics.len(), 2);
+    let HugTreeEntryKind::EnumDefinition { generics, .. } = &tree.entries[1].kind else {
+        unreachable!()
+    };
+    assert_eq!(generics.len(), 1);
+    let HugTreeEntryKind::FunctionDefinition(sum) = &tree.entries[3].kind else {
+        unreachable!()
+    };
+    assert!(sum.generics[0].bound.is_some());
+
+    // The `>>` closing both type arguments is split in two
+    let HugTreeEntryKind::VariableDefinition {
+        _type: Some(nested),
+        ..
+    } = &tree.entries[4].kind
+    else {
+        unreachable!()
+    };
+    assert_eq!(nested.args.len(), 2);
+    assert_eq!(nested.args[1].args.len(), 2);
+
+    assert_eq!(type_errors(GENERICS_PROGRAM), vec![]);
+}
+
+#[test]
+fn generic_errors() {
+    let found = type_errors(
+        r#"
+type Pair<A, B> { first: A, second: B }
+type Point { x: Int32 }
+function same<T>(a: T, b: T) -> T { return a }
+function empty<T>() -> T { return empty() }
+function sum<T: Add>(a: T, b: T) -> T { return a + b }
+function add<T>(a: T, b: T) -> T { return a + b }
+let a: Pair<Int32> = Pair { first: 1, second: 2 }
+let b: Point<Int32> = Point { x: 1 }
+let c = same(1, "one")
+let d = empty()
+let e = sum(Point { x: 1 }, Point { x: 2 })
+let f: Pair<Int32, String> = Pair { first: 1, second: 2 }
+"#,
+    );
+
+    assert_eq!(
+        found,
+        vec![
+            (
+                8,
+                "`Pair` takes 2 type arguments but 1 were given".to_string()
+            ),
+            (9, "`Point` takes no type arguments".to_string()),
+            (
+                10,
+                "Mismatched types: expected `Int32`, found `String`".to_string()
+            ),
+            (
+                11,
+                "Cannot infer the type of `T` in this call of `empty`".to_string()
+            ),
+            (12, "`Point` doesn't implement `Add`".to_string()),
+            (
+                13,
+                "Mismatched types: expected `String`, found `Int32`".to_string()
+            ),
+            // Bodies of functions are checked last
+            (7, "Cannot apply `+` to `T` and `T`".to_string()),
+        ]
+    );
+    assert_eq!(
+        errors("type Pair<T, T> { first: T }\ntrait Shape { function area<T>(self) -> T }"),
+        vec![
+            (1, 14, "Type parameter `T` is defined twice".to_string()),
+            (2, 29, "Methods of a trait can't be generic".to_string()),
+        ]
+    );
+}
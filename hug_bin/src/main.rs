@@ -1,7 +1,7 @@
 use std::{fs::OpenOptions, io::Read};
 
 use clap::{crate_authors, crate_description, crate_name, crate_version, PossibleValue};
-use hug_interpreter::vm::HugVM;
+use hug_interpreter::vm::{HugVM, VM_STACK_SIZE};
 
 fn app() -> clap::App<'static> {
     clap::App::new(crate_name!())
@@ -27,8 +27,28 @@ fn main() {
 
     match app.value_of("command").unwrap() {
         "r" | "run" => {
-            let mut vm = HugVM::new(app.value_of("input_file").unwrap_or_else(|| todo!())); // TODO: Read project.hug
-            vm.run();
+            let file_name = app
+                .value_of("input_file")
+                .unwrap_or_else(|| todo!())
+                .to_owned(); // TODO: Read project.hug
+
+            // Deep recursion needs more than the main thread's stack
+            let program = std::thread::Builder::new()
+                .stack_size(VM_STACK_SIZE)
+                .spawn(move || {
+                    let mut vm = HugVM::new();
+                    if let Err(diagnostics) = vm.load_file(&file_name) {
+                        eprint!("{}", vm.render_diagnostics(&diagnostics));
+                        std::process::exit(1);
+                    }
+                    vm.run();
+                })
+                .expect("Could not start the program!");
+
+            // The panic has already been reported
+            if program.join().is_err() {
+                std::process::exit(101);
+            }
         }
         "t" | "transpile" => {}
         "c" | "compile" => {
@@ -36,7 +56,7 @@ fn main() {
             let mut file = OpenOptions::new()
                 .read(true)
                 .open(file_name)
-                .expect(format!("Could not open file {}!", file_name).as_str());
+                .unwrap_or_else(|_| panic!("Could not open file {}!", file_name));
 
             let mut buffer = String::new();
             file.read_to_string(&mut buffer)
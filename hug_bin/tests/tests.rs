@@ -0,0 +1,59 @@
+use std::{fs, path::PathBuf, process::Command};
+
+/// A fresh directory in the temp dir, with the core library where `core.hug` looks for it. The
+/// library is the one cargo builds next to the tests.
+fn project_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("hug_bin_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    let target = directory.join("target/debug");
+    fs::create_dir_all(&target).unwrap();
+
+    let library = std::env::current_exe()
+        .unwrap()
+        .with_file_name("libhug_core.so");
+    fs::copy(library, target.join("libhug_core.so")).unwrap();
+    directory
+}
+
+/// Runs `program` with `hug run`, giving back what it printed.
+fn run(name: &str, program: &str) -> String {
+    let directory = project_directory(name);
+    fs::write(directory.join("main.hug"), program).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hug"))
+        .args(["run", "main.hug"])
+        .current_dir(&directory)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const PRINT_PROGRAM: &str = r#"
+type Point { x: Int32, y: Int32 }
+impl Display for Point {
+    function format(self) -> String { return f"<{self.x}, {self.y}>" }
+}
+
+print(Point { x: 1, y: 2 })
+print(add(40, 2))
+print("hi")
+"#;
+
+#[test]
+fn print() {
+    let output = run("print", PRINT_PROGRAM);
+
+    // Debug builds log what they run in between
+    let lines = output.lines().collect::<Vec<_>>();
+    let printed = ["<1, 2>", "42", "hi"].map(|line| lines.iter().position(|l| *l == line));
+    assert!(
+        printed.iter().all(Option::is_some) && printed.is_sorted(),
+        "{}",
+        output
+    );
+}
@@ -5,11 +5,11 @@ pub const HUG_CORE_SCRIPT: &str = include_str!("../hug/core.hug");
 pub fn init(module: &mut HugModule) {
     println!("Registering...");
     module.register_function("add", add);
-    module.register_function("print", print);
+    module.register_function("print_line", print_line);
     println!("HUG CORE LOADED!!!!");
 }
 
-pub fn deinit(module: &mut HugModule) {
+pub fn deinit(_module: &mut HugModule) {
     println!("rip hug");
 }
 
@@ -21,14 +21,9 @@ fn add(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
     Some(HugValue::from(left + right))
 }
 
-fn print(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
-    let fmt = args
-        .next()
-        .expect("Nothing to print!")
-        .assert::<String>()
-        .expect("First argument of print must be a string!");
-
-    let fmt_args = args.collect::<Vec<HugValue>>();
+fn print_line(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let text = unwrap_args!(args, String);
+    println!("{}", text);
 
     None
 }
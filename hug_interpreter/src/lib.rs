@@ -1,3 +1 @@
-use hug_ast::HugTree;
-
 pub mod vm;
@@ -1,145 +1,1094 @@
-use std::{collections::HashMap, fs::OpenOptions, io::Read};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use hug_ast::HugTree;
+use hug_ast::{
+    checker::{HugTypeChecker, HugTypeGlobals},
+    parser::HugTreeParser,
+    HugTree, HugTreeBinaryOperator, HugTreeEntry, HugTreeEntryKind, HugTreeExpression,
+    HugTreeExpressionKind, HugTreeFormatSegment, HugTreeFunction, HugTreeMatch, HugTreePattern,
+    HugTreePatternKind, HugTreePayload, HugTreeUnaryOperator, HugType,
+};
 use hug_core::HUG_CORE_SCRIPT;
 use hug_lexer::{parser::generate_pairs, tokenizer::Tokenizer};
 use hug_lib::{
-    value::{HugExternalFunction, HugValue},
+    diagnostic::Diagnostic,
+    interner::Interner,
+    span::{FileId, Span},
+    value::{HugEnum, HugExternalFunction, HugPayload, HugStruct, HugValue},
     HugModule, Ident,
 };
 
 const INVALID_MODULE_ERROR: &str = "No function __HUG_MODULE_INIT was found on this module, add one with hug_module! or contact the module's developer.";
 
+/// How many frames the call stack can hold before the program is stopped, so runaway recursion is a hug
+/// error instead of a native stack overflow.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// Native stack a thread needs to run a VM up to [`MAX_CALL_DEPTH`], even in debug builds.
+pub const VM_STACK_SIZE: usize = 64 << 20;
+
+/// Frames shown in a stack trace, innermost first.
+const STACK_TRACE_FRAMES: usize = 16;
+
+/// What to do after executing an entry. Anything other than `Normal` unwinds out of blocks until
+/// something handles it.
+#[derive(Debug, Clone)]
+pub enum HugControlFlow {
+    Normal,
+    Break,
+    Continue,
+    Return(HugValue),
+}
+
+/// A function call on the stack, the first frame is the top level of the script.
+#[derive(Debug, Clone)]
+pub struct HugFrame {
+    /// `None` for the top level.
+    pub function: Option<Ident>,
+    /// Where the function was called from.
+    pub span: Span,
+    /// Local slots as resolved by the parser, with the name of the variable in each.
+    pub locals: Vec<Option<(Ident, HugValue)>>,
+}
+
+impl HugFrame {
+    pub fn new(function: Option<Ident>, span: Span, slots: usize) -> HugFrame {
+        HugFrame {
+            function,
+            span,
+            locals: vec![None; slots],
+        }
+    }
+
+    /// The locals that currently hold a value, in slot order.
+    pub fn locals(&self) -> impl Iterator<Item = (Ident, &HugValue)> {
+        self.locals
+            .iter()
+            .filter_map(|local| local.as_ref().map(|(name, value)| (*name, value)))
+    }
+}
+
+/// A loaded script, indexed by `FileId`. Kept around so diagnostics can show the offending code.
+#[derive(Debug)]
+pub struct HugSource {
+    pub name: String,
+    pub text: String,
+}
+
 #[derive(Debug)]
 pub struct HugVM {
     paused: bool,
     pointer: usize,
     tree: HugTree,
-    idents: HashMap<String, Ident>,
+    idents: Interner,
     variables: Vec<Option<HugValue>>,
+    /// Functions written in hug, `HugValue::Function` is an index into this.
+    functions: Vec<Rc<HugTreeFunction>>,
+    frames: Vec<HugFrame>,
+    sources: Vec<HugSource>,
+    /// Types of everything loaded so far, so later scripts are checked against them.
+    types: HugTypeGlobals,
+    /// Directories `use` looks in when a module isn't next to the file that uses it.
+    search_paths: Vec<PathBuf>,
+    /// Modules loaded by `use`, each module is only loaded once.
+    modules: HashSet<Ident>,
+    /// Modules that are being loaded, innermost last, for detecting modules that use each other.
+    loading: Vec<Ident>,
+    /// Native libraries of `@extern` modules, they must stay loaded while their functions can
+    /// still be called.
+    libraries: Vec<libloading::Library>,
+}
+
+impl Default for HugVM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HugVM {
-    pub fn new(file_path: &str) -> HugVM {
-        let mut vm = HugVM {
+    /// Creates a VM with the core library loaded, use `load_file` to load a script.
+    pub fn new() -> HugVM {
+        let mut vm = Self::empty();
+        if let Err(diagnostics) = vm.load_script("core.hug", HUG_CORE_SCRIPT) {
+            panic!(
+                "The core library contains errors:\n{}",
+                vm.render_diagnostics(&diagnostics)
+            );
+        }
+
+        vm
+    }
+
+    /// Creates a VM without the core library.
+    pub fn empty() -> HugVM {
+        HugVM {
             paused: false,
             pointer: 0,
             tree: HugTree::new(),
-            idents: HashMap::new(),
+            idents: Interner::new(),
             variables: Vec::new(),
-        };
-
-        vm.load_script(HUG_CORE_SCRIPT);
-        vm.load_file(file_path);
-        vm
+            functions: Vec::new(),
+            frames: Vec::new(),
+            sources: Vec::new(),
+            types: HugTypeGlobals::default(),
+            search_paths: Vec::new(),
+            modules: HashSet::new(),
+            loading: Vec::new(),
+            libraries: Vec::new(),
+        }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) {
         if !self.paused {
             self.pointer += 1;
         }
     }
 
-    pub fn load_file(&mut self, file_path: &str) {
+    pub fn load_file(&mut self, file_path: &str) -> Result<(), Vec<Diagnostic>> {
         #[cfg(debug_assertions)]
         println!("Loading file: {}", file_path);
 
         let mut file = OpenOptions::new()
             .read(true)
             .open(file_path)
-            .expect(format!("Could not open file {}!", file_path).as_str());
+            .unwrap_or_else(|_| panic!("Could not open file {}!", file_path));
 
         let mut buffer = String::new();
         file.read_to_string(&mut buffer)
             .expect("Could not read file!");
 
-        self.load_script(&buffer);
+        let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+        self.load(file_path, &buffer, directory, None)
     }
 
-    pub fn load_script(&mut self, program: &str) {
+    /// Loads `program` into the VM, `name` is only used when displaying diagnostics. Nothing is
+    /// loaded if the script contains errors. Modules it `use`s are looked for in the current
+    /// directory and the search paths.
+    pub fn load_script(&mut self, name: &str, program: &str) -> Result<(), Vec<Diagnostic>> {
+        self.load(name, program, Path::new(""), None)
+    }
+
+    /// Adds a directory to look for modules in, `use utils.math` loads `utils/math.hug` from the
+    /// first directory that has it.
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.search_paths.push(path.into());
+    }
+
+    /// Loads a script, `directory` is where the modules it uses are looked for first. When the
+    /// script is a `module`, everything it defines is qualified with the module's name.
+    fn load(
+        &mut self,
+        name: &str,
+        program: &str,
+        directory: &Path,
+        module: Option<Ident>,
+    ) -> Result<(), Vec<Diagnostic>> {
         #[cfg(debug_assertions)]
-        println!("Loading script:\n> {}", program.replace("\n", "\n> "));
+        println!("Loading script:\n> {}", program.replace('\n', "\n> "));
 
-        let mut tokenizer = Tokenizer::with_idents(self.idents.clone(), program);
-        let tokens = tokenizer.tokenize();
-        self.idents = tokenizer.idents;
+        let file = FileId(self.sources.len());
+        self.sources.push(HugSource {
+            name: name.to_string(),
+            text: program.to_string(),
+        });
+
+        let mut tokenizer = Tokenizer::new(program, &mut self.idents);
+        tokenizer.file = file;
+        let (tokens, diagnostics) = tokenizer.tokenize();
+
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(diagnostics);
+        }
 
         let pairs = generate_pairs(program, tokens);
-        let t = HugTree::from_token_pairs(pairs);
+        let mut parser = HugTreeParser::new(pairs, &mut self.idents);
+        if let Some(module) = module {
+            parser = parser.in_module(module);
+        }
+        let (mut t, diagnostics) = parser.parse();
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(diagnostics);
+        }
+
+        // Used modules have to be loaded before the script can be checked against them
+        let mut uses = Vec::new();
+        collect_uses(&t.entries, &mut uses);
+        let mut diagnostics = Vec::new();
+        for (module, span) in uses {
+            if let Err(errors) = self.load_module(module, span, directory) {
+                diagnostics.extend(errors);
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        let mut checker = HugTypeChecker::new(&mut self.types, &self.idents);
+        if let Some(module) = module {
+            checker = checker.in_module(module);
+        }
+        let diagnostics = checker.check(&mut t);
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(diagnostics);
+        }
+
+        // Functions can be called before the line that defines them
+        self.define_functions(&t.entries);
+
         self.tree.merge_with(t);
+        Ok(())
+    }
+
+    /// Loads the file of `module` unless it was loaded before, `span` is the `use` that needs it.
+    fn load_module(
+        &mut self,
+        module: Ident,
+        span: Span,
+        directory: &Path,
+    ) -> Result<(), Vec<Diagnostic>> {
+        if self.modules.contains(&module) {
+            return Ok(());
+        }
+
+        let name = self.idents.resolve(module).to_string();
+        if let Some(start) = self.loading.iter().position(|m| *m == module) {
+            let cycle = self.loading[start..]
+                .iter()
+                .chain([&module])
+                .map(|m| format!("`{}`", self.idents.resolve(*m)))
+                .collect::<Vec<_>>()
+                .join(" uses ");
+            return Err(vec![Diagnostic::error(
+                span,
+                format!("Module `{}` uses itself", name),
+            )
+            .with_hint(cycle)]);
+        }
+
+        let relative = name.split('.').collect::<PathBuf>().with_extension("hug");
+        let path = std::iter::once(directory)
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|directory| directory.join(&relative))
+            .find(|path| path.is_file());
+        let Some(path) = path else {
+            return Err(vec![Diagnostic::error(
+                span,
+                format!("Could not find module `{}`", name),
+            )
+            .with_hint(format!(
+                "looked for `{}` next to this file and in the search paths",
+                relative.display()
+            ))]);
+        };
+
+        let program = fs::read_to_string(&path).map_err(|error| {
+            vec![Diagnostic::error(
+                span,
+                format!("Could not read `{}`: {}", path.display(), error),
+            )]
+        })?;
+
+        self.loading.push(module);
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let result = self.load(&path.to_string_lossy(), &program, directory, Some(module));
+        self.loading.pop();
+
+        if result.is_ok() {
+            self.modules.insert(module);
+        }
+        result
+    }
+
+    fn define_functions(&mut self, entries: &[HugTreeEntry]) {
+        for entry in entries {
+            match &entry.kind {
+                HugTreeEntryKind::FunctionDefinition(function) => {
+                    self.define_function(function.clone())
+                }
+                HugTreeEntryKind::ModuleDefinition { body, .. } => self.define_functions(body),
+                HugTreeEntryKind::Implementation { methods, .. } => self.define_functions(methods),
+                _ => (),
+            }
+        }
+    }
+
+    pub fn define_function(&mut self, function: HugTreeFunction) {
+        let name = function.name;
+        self.functions.push(Rc::new(function));
+        self.set_variable(name, HugValue::Function(self.functions.len() - 1));
+    }
+
+    /// Makes a Rust function callable from hug as `name`, the same as a native module would.
+    pub fn define_external_function(&mut self, name: &str, function: HugExternalFunction) {
+        let id = self.idents.intern(name);
+        self.set_variable(id, HugValue::ExternalFunction(function));
+        // Like an `@extern function` without a signature, calls of it aren't checked
+        self.types.values.entry(id).or_insert(HugType::Unknown);
+    }
+
+    pub fn idents(&self) -> &Interner {
+        &self.idents
+    }
+
+    pub fn source(&self, file: FileId) -> Option<&HugSource> {
+        self.sources.get(file.0)
+    }
+
+    /// Formats diagnostics with the source code they point at.
+    pub fn render_diagnostics(&self, diagnostics: &[Diagnostic]) -> String {
+        let mut buffer = String::new();
+        for diagnostic in diagnostics {
+            match self.source(diagnostic.span.file) {
+                Some(source) => buffer.push_str(&diagnostic.render(&source.name, &source.text)),
+                None => buffer.push_str(&format!("{}\n", diagnostic)),
+            }
+        }
+        buffer
     }
 
     pub fn run(&mut self) {
         #[cfg(debug_assertions)]
         {
-            println!("HugTree: {}", self.tree);
+            println!(
+                "HugTree: {}",
+                self.idents.annotate_debug(&self.tree.to_string())
+            );
             println!("Identifiers: {}", {
                 let mut buffer = String::new();
-                for (key, value) in self.idents.iter() {
-                    buffer.push_str(&format!("\n  {:?}: \"{}\",", value, key));
+                for (id, name) in self.idents.iter() {
+                    buffer.push_str(&format!("\n  {:?}: \"{}\",", id, name));
                 }
                 buffer
             });
             println!("Memory: {}", {
                 let mut buffer = String::new();
                 for (i, value) in self.variables.iter().enumerate() {
-                    buffer.push_str(&format!("\n  {}: \"{:?}\",", i, value.clone()));
+                    if let Some(value) = value {
+                        let name = self.idents.resolve(Ident(i));
+                        buffer.push_str(&format!("\n  {}: {:?},", name, value));
+                    }
                 }
                 buffer
             })
         }
 
+        // Scripts loaded since the last run can need more slots
+        match self.frames.first_mut() {
+            Some(root) => root.locals.resize(self.tree.locals, None),
+            None => self
+                .frames
+                .push(HugFrame::new(None, Span::default(), self.tree.locals)),
+        }
+
         while self.pointer < self.tree.entries.len() {
             let instruction = self.tree.entries.get(self.pointer).unwrap().clone();
 
-            #[cfg(debug_assertions)]
-            println!("Instruction: {:?}", instruction);
+            if let HugControlFlow::Return(_) = self.execute(&instruction) {
+                break; // Returning from the script itself stops it
+            }
+            self.next();
+        }
+    }
 
-            match instruction {
-                hug_ast::HugTreeEntry::ModuleDefinition { module } => todo!(),
-                hug_ast::HugTreeEntry::ExternalModuleDefinition { module, location } => unsafe {
-                    let library = libloading::Library::new(location).unwrap();
-                    let init_func: libloading::Symbol<unsafe extern "C" fn(&mut HugModule)> =
-                        library
-                            .get(b"__HUG_MODULE_INIT")
-                            .expect(INVALID_MODULE_ERROR);
+    /// The call stack, the top level of the script first.
+    pub fn stack(&self) -> &[HugFrame] {
+        &self.frames
+    }
 
-                    let mut module = HugModule::new(&mut self.idents);
-                    init_func(&mut module);
+    /// Describes the call stack, innermost call first, with the locals of every frame.
+    pub fn stack_trace(&self) -> String {
+        let mut buffer = String::new();
+        for frame in self.frames.iter().rev().take(STACK_TRACE_FRAMES) {
+            match frame.function {
+                Some(function) => buffer.push_str(&format!(
+                    "  in {} called at {}\n",
+                    self.idents.resolve(function),
+                    self.location(frame.span)
+                )),
+                None => buffer.push_str("  in the top level\n"),
+            }
 
-                    let HugModule { functions, .. } = module;
+            for (name, value) in frame.locals() {
+                buffer.push_str(&format!(
+                    "    {} = {:?}\n",
+                    self.idents.resolve(name),
+                    value
+                ));
+            }
+        }
+
+        if self.frames.len() > STACK_TRACE_FRAMES {
+            buffer.push_str(&format!(
+                "  ... {} more frames\n",
+                self.frames.len() - STACK_TRACE_FRAMES
+            ));
+        }
+        buffer
+    }
 
-                    for (id, fun) in functions {
-                        self.set_variable(id, HugValue::from(fun));
+    /// `file:line:column` of a span.
+    fn location(&self, span: Span) -> String {
+        match self.source(span.file) {
+            Some(source) => format!("{}:{}", source.name, span),
+            None => span.to_string(),
+        }
+    }
+
+    /// Stops the program with an error at `span`, followed by the call stack.
+    pub fn error(&self, span: Span, message: impl Display) -> ! {
+        panic!(
+            "{}: {}\nStack:\n{}",
+            self.location(span),
+            message,
+            self.stack_trace()
+        )
+    }
+
+    pub fn execute(&mut self, instruction: &HugTreeEntry) -> HugControlFlow {
+        #[cfg(debug_assertions)]
+        println!(
+            "Instruction: {}",
+            self.idents.annotate_debug(&format!("{:?}", instruction))
+        );
+
+        match &instruction.kind {
+            HugTreeEntryKind::ModuleDefinition { body, .. } => return self.execute_block(body),
+            HugTreeEntryKind::Use { .. } => (), // Loaded with the script that uses it
+            HugTreeEntryKind::ExternalModuleDefinition { location, .. } => unsafe {
+                let library = libloading::Library::new(location).unwrap();
+                let init_func: libloading::Symbol<unsafe extern "C" fn(&mut HugModule)> = library
+                    .get(b"__HUG_MODULE_INIT")
+                    .expect(INVALID_MODULE_ERROR);
+
+                let mut module = HugModule::new(&mut self.idents);
+                init_func(&mut module);
+
+                let HugModule { functions, .. } = module;
+
+                for (id, fun) in functions {
+                    self.set_variable(id, HugValue::from(fun));
+                }
+                self.libraries.push(library);
+            },
+            HugTreeEntryKind::VariableDefinition {
+                variable,
+                slot,
+                value,
+                ..
+            } => {
+                let value = self.evaluate(value);
+                match slot {
+                    Some(slot) => self.set_local(*slot, *variable, value),
+                    None => self.set_variable(*variable, value),
+                }
+            }
+            HugTreeEntryKind::Assignment {
+                variable,
+                slot,
+                fields,
+                operator,
+                value,
+            } => {
+                let mut value = self.evaluate(value);
+                if operator.is_some() || !fields.is_empty() {
+                    let current = match slot {
+                        Some(slot) => self.get_local(*slot),
+                        None => self.get_variable(*variable),
+                    };
+                    let Some(mut current) = current.cloned() else {
+                        self.error(
+                            instruction.span,
+                            format!(
+                                "Variable `{}` is not defined!",
+                                self.idents.resolve(*variable)
+                            ),
+                        )
+                    };
+
+                    let mut target = &mut current;
+                    for field in fields {
+                        let name = self.idents.resolve(*field);
+                        let type_name = target.type_name().to_string();
+                        target = match target {
+                            HugValue::Struct(value) => value.get_mut(name),
+                            _ => None,
+                        }
+                        .unwrap_or_else(|| {
+                            let message = format!("`{}` has no field `{}`", type_name, name);
+                            self.error(instruction.span, message)
+                        });
                     }
-                },
-                hug_ast::HugTreeEntry::VariableDefinition { variable, value } => {
-                    self.set_variable(variable, value.clone());
-                }
-                hug_ast::HugTreeEntry::FunctionCall { function, args } => {
-                    match self.get_variable(function).unwrap() {
-                        HugValue::ExternalFunction(f) => {
-                            f(args
-                                .iter()
-                                .map(|a| match a {
-                                    hug_ast::HugTreeFunctionCallArg::Variable(v) => {
-                                        self.get_variable(*v).unwrap().clone()
-                                    }
-                                    hug_ast::HugTreeFunctionCallArg::Value(v) => v.clone(),
-                                })
-                                .collect::<Vec<HugValue>>()
-                                .into_iter());
+
+                    if let Some(operator) = operator {
+                        value = arithmetic(*operator, target, &value)
+                            .unwrap_or_else(|e| self.error(instruction.span, e));
+                    }
+                    *target = value;
+                    value = current;
+                }
+
+                match slot {
+                    Some(slot) => self.set_local(*slot, *variable, value),
+                    None => self.set_variable(*variable, value),
+                }
+            }
+            HugTreeEntryKind::FunctionCall { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|a| self.evaluate(a))
+                    .collect::<Vec<HugValue>>();
+
+                self.call(*function, args, instruction.span);
+            }
+            HugTreeEntryKind::MethodCall {
+                value,
+                method,
+                args,
+            } => {
+                self.call_method(value, *method, args, instruction.span);
+            }
+            HugTreeEntryKind::If {
+                condition,
+                body,
+                otherwise,
+            } => {
+                if self.condition(condition) {
+                    return self.execute_block(body);
+                } else if let Some(otherwise) = otherwise {
+                    return self.execute_block(otherwise);
+                }
+            }
+            HugTreeEntryKind::While { condition, body } => {
+                while self.condition(condition) {
+                    match self.execute_block(body) {
+                        HugControlFlow::Break => break,
+                        flow @ HugControlFlow::Return(_) => return flow,
+                        _ => (),
+                    }
+                }
+            }
+            HugTreeEntryKind::For {
+                variable,
+                slot,
+                iterable,
+                body,
+            } => {
+                for value in self.iterate(iterable) {
+                    self.set_local(*slot, *variable, value);
+                    match self.execute_block(body) {
+                        HugControlFlow::Break => break,
+                        flow @ HugControlFlow::Return(_) => return flow,
+                        _ => (),
+                    }
+                }
+            }
+            HugTreeEntryKind::Loop { body } => loop {
+                match self.execute_block(body) {
+                    HugControlFlow::Break => break,
+                    flow @ HugControlFlow::Return(_) => return flow,
+                    _ => (),
+                }
+            },
+            HugTreeEntryKind::Block { body } => return self.execute_block(body),
+            HugTreeEntryKind::Match(HugTreeMatch { value, arms }) => {
+                let patterns = arms.iter().map(|arm| &arm.pattern);
+                let arm = self.match_arm(value, patterns, instruction.span);
+                return self.execute_block(&arms[arm].body);
+            }
+            HugTreeEntryKind::FunctionDefinition(_) => (), // Defined when the script was loaded
+            HugTreeEntryKind::Break => return HugControlFlow::Break,
+            HugTreeEntryKind::Continue => return HugControlFlow::Continue,
+            HugTreeEntryKind::Return { value } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value),
+                    None => HugValue::Void,
+                };
+                return HugControlFlow::Return(value);
+            }
+            _ => (),
+        }
+
+        HugControlFlow::Normal
+    }
+
+    /// Calls the function stored in the variable `function`, `span` is where it's called from.
+    /// Functions that don't return anything return `HugValue::Void`.
+    pub fn call(&mut self, function: Ident, args: Vec<HugValue>, span: Span) -> HugValue {
+        match self.get_variable(function) {
+            Some(HugValue::ExternalFunction(f)) => f(args.into_iter()).unwrap_or(HugValue::Void),
+            Some(HugValue::Function(index)) => {
+                let definition = self.functions[*index].clone();
+                if args.len() != definition.parameters.len() {
+                    self.error(
+                        span,
+                        format!(
+                            "`{}` takes {} arguments but {} were given",
+                            self.idents.resolve(function),
+                            definition.parameters.len(),
+                            args.len()
+                        ),
+                    );
+                }
+
+                let mut frame = HugFrame::new(Some(function), span, definition.locals);
+                for (slot, (parameter, value)) in definition.parameters.iter().zip(args).enumerate()
+                {
+                    frame.locals[slot] = Some((parameter.name, value));
+                }
+
+                if self.frames.len() >= MAX_CALL_DEPTH {
+                    self.error(
+                        span,
+                        format!(
+                            "Stack overflow: the call stack is limited to {} frames",
+                            MAX_CALL_DEPTH
+                        ),
+                    );
+                }
+
+                self.frames.push(frame);
+                let flow = self.execute_block(&definition.body);
+                self.frames.pop();
+
+                match flow {
+                    HugControlFlow::Return(value) => value,
+                    _ => HugValue::Void,
+                }
+            }
+            _ => self.error(
+                span,
+                format!("`{}` is not a function!", self.idents.resolve(function)),
+            ),
+        }
+    }
+
+    /// Executes entries until one of them changes the control flow.
+    pub fn execute_block(&mut self, entries: &[HugTreeEntry]) -> HugControlFlow {
+        for entry in entries {
+            match self.execute(entry) {
+                HugControlFlow::Normal => (),
+                flow => return flow,
+            }
+        }
+
+        HugControlFlow::Normal
+    }
+
+    /// Evaluates the condition of an `if` or `while`, which has to be a `Bool`.
+    pub fn condition(&mut self, expression: &HugTreeExpression) -> bool {
+        match self.evaluate(expression) {
+            HugValue::Bool(value) => value,
+            other => self.error(
+                expression.span,
+                format!(
+                    "Expected a condition of type `Bool`, found `{}`",
+                    other.type_name()
+                ),
+            ),
+        }
+    }
+
+    /// The values a `for` loop iterates over: the integers in a range or the characters of a
+    /// string.
+    pub fn iterate(&mut self, iterable: &HugTreeExpression) -> Box<dyn Iterator<Item = HugValue>> {
+        if let HugTreeExpressionKind::Range {
+            start,
+            end,
+            inclusive,
+        } = &iterable.kind
+        {
+            let (start, end) = (self.evaluate(start), self.evaluate(end));
+            if start.type_name() != end.type_name() {
+                self.error(
+                    iterable.span,
+                    format!(
+                        "Both ends of a range must have the same type, found `{}` and `{}`",
+                        start.type_name(),
+                        end.type_name()
+                    ),
+                );
+            }
+
+            let (from, to) = match (start.as_integer(), end.as_integer()) {
+                (Some(from), Some(to)) => (from, to + *inclusive as i128),
+                _ => self.error(
+                    iterable.span,
+                    format!(
+                        "Ranges can only be made of integers, found `{}`",
+                        start.type_name()
+                    ),
+                ),
+            };
+
+            return Box::new((from..to).map(move |i| start.with_integer(i).unwrap()));
+        }
+
+        match self.evaluate(iterable) {
+            HugValue::String(string) => Box::new(
+                string
+                    .chars()
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .map(HugValue::from),
+            ),
+            other => self.error(
+                iterable.span,
+                format!(
+                    "Cannot iterate over a value of type `{}`",
+                    other.type_name()
+                ),
+            ),
+        }
+    }
+
+    pub fn evaluate(&mut self, expression: &HugTreeExpression) -> HugValue {
+        match &expression.kind {
+            HugTreeExpressionKind::Variable(v) => match self.get_variable(*v) {
+                Some(value) => value.clone(),
+                None => self.error(
+                    expression.span,
+                    format!("Variable `{}` is not defined!", self.idents.resolve(*v)),
+                ),
+            },
+            HugTreeExpressionKind::Local { variable, slot } => match self.get_local(*slot) {
+                Some(value) => value.clone(),
+                None => self.error(
+                    expression.span,
+                    format!(
+                        "Variable `{}` is not defined!",
+                        self.idents.resolve(*variable)
+                    ),
+                ),
+            },
+            HugTreeExpressionKind::Value(v) | HugTreeExpressionKind::Number { value: v, .. } => {
+                v.clone()
+            }
+            HugTreeExpressionKind::FormatString(segments) => {
+                let mut buffer = String::new();
+                for segment in segments {
+                    match segment {
+                        HugTreeFormatSegment::Text(text) => buffer.push_str(text),
+                        HugTreeFormatSegment::Expression { expression, spec } => {
+                            let value = self.evaluate(expression);
+                            let value = self.display(value, expression.span);
+                            buffer.push_str(&spec.apply(&value))
                         }
-                        HugValue::Function(l) => {
-                            self.pointer = *l;
+                    }
+                }
+                HugValue::String(buffer)
+            }
+            HugTreeExpressionKind::Call { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|a| self.evaluate(a))
+                    .collect::<Vec<HugValue>>();
+
+                self.call(*function, args, expression.span)
+            }
+            HugTreeExpressionKind::Unary { operator, operand } => {
+                let result = match operator {
+                    HugTreeUnaryOperator::Not => Ok(HugValue::Bool(!self.condition(operand))),
+                    HugTreeUnaryOperator::Negate => self.evaluate(operand).negate(),
+                    HugTreeUnaryOperator::BinaryNot => self.evaluate(operand).bitwise_not(),
+                };
+                result.unwrap_or_else(|e| self.error(expression.span, e))
+            }
+            HugTreeExpressionKind::Binary {
+                operator: HugTreeBinaryOperator::And,
+                left,
+                right,
+            } => HugValue::Bool(self.condition(left) && self.condition(right)),
+            HugTreeExpressionKind::Binary {
+                operator: HugTreeBinaryOperator::Or,
+                left,
+                right,
+            } => HugValue::Bool(self.condition(left) || self.condition(right)),
+            HugTreeExpressionKind::Binary {
+                operator,
+                left,
+                right,
+            } if operator.is_comparison() => {
+                let (left, right) = (self.evaluate(left), self.evaluate(right));
+                let equality = matches!(
+                    operator,
+                    HugTreeBinaryOperator::IsEqualTo | HugTreeBinaryOperator::IsNotEqualTo
+                );
+                if let Some(eq) = self.operator_method(&left, "eq").filter(|_| equality) {
+                    let equal = self.call(eq, vec![left, right], expression.span);
+                    let negate = *operator == HugTreeBinaryOperator::IsNotEqualTo;
+                    return HugValue::Bool(matches!(equal, HugValue::Bool(true)) != negate);
+                }
+                if left.type_name() != right.type_name() {
+                    self.error(
+                        expression.span,
+                        format!(
+                            "Cannot compare `{}` to `{}`",
+                            left.type_name(),
+                            right.type_name()
+                        ),
+                    );
+                }
+
+                // Only NaN and functions have no ordering, they are never equal to anything
+                let ordering = left.compare(&right);
+                HugValue::Bool(match operator {
+                    HugTreeBinaryOperator::IsEqualTo => ordering == Some(Ordering::Equal),
+                    HugTreeBinaryOperator::IsNotEqualTo => ordering != Some(Ordering::Equal),
+                    HugTreeBinaryOperator::LessThan => ordering.is_some_and(Ordering::is_lt),
+                    HugTreeBinaryOperator::GreaterThan => ordering.is_some_and(Ordering::is_gt),
+                    HugTreeBinaryOperator::LessThanOrEquals => {
+                        ordering.is_some_and(Ordering::is_le)
+                    }
+                    _ => ordering.is_some_and(Ordering::is_ge),
+                })
+            }
+            HugTreeExpressionKind::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let (left, right) = (self.evaluate(left), self.evaluate(right));
+                let add = *operator == HugTreeBinaryOperator::Add;
+                if let Some(add) = self.operator_method(&left, "add").filter(|_| add) {
+                    return self.call(add, vec![left, right], expression.span);
+                }
+                arithmetic(*operator, &left, &right)
+                    .unwrap_or_else(|e| self.error(expression.span, e))
+            }
+            HugTreeExpressionKind::Range { .. } => {
+                self.error(expression.span, "Ranges can only be used in `for` loops")
+            }
+            HugTreeExpressionKind::Struct { _type, fields } => {
+                let mut value = HugStruct::new(self.idents.resolve(*_type));
+                for (field, field_value) in fields {
+                    let field_value = self.evaluate(field_value);
+                    value = value.with(self.idents.resolve(*field), field_value);
+                }
+                HugValue::Struct(value)
+            }
+            HugTreeExpressionKind::Field { value, field } => {
+                let value = self.evaluate(value);
+                let name = self.idents.resolve(*field);
+                let found = match &value {
+                    HugValue::Struct(value) => value.get(name),
+                    _ => None,
+                };
+                match found {
+                    Some(found) => found.clone(),
+                    None => self.error(
+                        expression.span,
+                        format!("`{}` has no field `{}`", value.type_name(), name),
+                    ),
+                }
+            }
+            HugTreeExpressionKind::Variant {
+                _type,
+                variant,
+                payload,
+            } => {
+                let mut value =
+                    HugEnum::new(self.idents.resolve(*_type), self.idents.resolve(*variant));
+                match payload {
+                    HugTreePayload::Unit => (),
+                    HugTreePayload::Tuple(values) => {
+                        for item in values {
+                            value = value.with(self.evaluate(item));
+                        }
+                    }
+                    HugTreePayload::Struct(fields) => {
+                        for (field, field_value) in fields {
+                            let field_value = self.evaluate(field_value);
+                            value = value.with_field(self.idents.resolve(*field), field_value);
                         }
-                        _ => panic!("Not a function! {:?}", function),
                     }
                 }
-                _ => (),
+                HugValue::Enum(value)
+            }
+            HugTreeExpressionKind::Match(HugTreeMatch { value, arms }) => {
+                let patterns = arms.iter().map(|arm| &arm.pattern);
+                let arm = self.match_arm(value, patterns, expression.span);
+                self.evaluate(&arms[arm].body)
+            }
+            HugTreeExpressionKind::MethodCall {
+                value,
+                method,
+                args,
+            } => self.call_method(value, *method, args, expression.span),
+        }
+    }
+
+    /// Calls a method the type checker couldn't resolve, because the type of `value` is only
+    /// known now. The method is the function named after the type of the value.
+    fn call_method(
+        &mut self,
+        value: &HugTreeExpression,
+        method: Ident,
+        args: &[HugTreeExpression],
+        span: Span,
+    ) -> HugValue {
+        let value = self.evaluate(value);
+        let name = format!("{}.{}", value.type_name(), self.idents.resolve(method));
+        let Some(function) = self.idents.get(&name) else {
+            let args = args.iter().map(|a| self.evaluate(a)).collect::<Vec<_>>();
+            if let Some(result) = self.builtin_method(&value, method, &args, span) {
+                return result;
+            }
+            self.error(
+                span,
+                format!(
+                    "`{}` has no method `{}`",
+                    value.type_name(),
+                    self.idents.resolve(method)
+                ),
+            )
+        };
+
+        let args = std::iter::once(value)
+            .chain(args.iter().map(|a| self.evaluate(a)))
+            .collect::<Vec<HugValue>>();
+        self.call(function, args, span)
+    }
+
+    /// The methods builtin types have for the builtin traits, `None` if `value` doesn't have
+    /// `method`.
+    fn builtin_method(
+        &mut self,
+        value: &HugValue,
+        method: Ident,
+        args: &[HugValue],
+        span: Span,
+    ) -> Option<HugValue> {
+        if matches!(value, HugValue::Struct(_) | HugValue::Enum(_)) {
+            return None;
+        }
+        Some(match (self.idents.resolve(method), args) {
+            ("format", []) => HugValue::String(value.to_string()),
+            ("add", [other]) => arithmetic(HugTreeBinaryOperator::Add, value, other)
+                .unwrap_or_else(|e| self.error(span, e)),
+            ("eq", [other]) => HugValue::Bool(value.compare(other) == Some(Ordering::Equal)),
+            _ => return None,
+        })
+    }
+
+    /// The method a struct or enum overloads an operator with. The checker calls it itself
+    /// when it knows the type of the operands, but not for those of a type parameter.
+    fn operator_method(&self, value: &HugValue, method: &str) -> Option<Ident> {
+        if !matches!(value, HugValue::Struct(_) | HugValue::Enum(_)) {
+            return None;
+        }
+        self.idents
+            .get(&format!("{}.{}", value.type_name(), method))
+    }
+
+    /// Structs and enums that implement `Display` are shown in format strings with their
+    /// `format` method. The checker calls it itself when it knows the type of the value.
+    fn display(&mut self, value: HugValue, span: Span) -> HugValue {
+        if !matches!(value, HugValue::Struct(_) | HugValue::Enum(_)) {
+            return value;
+        }
+        let (Some(_type), Some(display)) = (
+            self.idents.get(value.type_name()),
+            self.idents.get("Display"),
+        ) else {
+            return value;
+        };
+        if !self.types.implementations.contains(&(_type, display)) {
+            return value;
+        }
+
+        let format = format!("{}.format", value.type_name());
+        let function = self
+            .idents
+            .get(&format)
+            .expect("`Display` needs a `format` method");
+        self.call(function, vec![value], span)
+    }
+
+    /// Evaluates the value of a `match` and gives back the index of the first arm whose pattern
+    /// matches it, after binding the names in that pattern.
+    fn match_arm<'p>(
+        &mut self,
+        value: &HugTreeExpression,
+        patterns: impl Iterator<Item = &'p HugTreePattern>,
+        span: Span,
+    ) -> usize {
+        let value = self.evaluate(value);
+        for (index, pattern) in patterns.enumerate() {
+            if self.matches(pattern, &value) {
+                return index;
             }
-            self.next();
         }
+
+        self.error(span, format!("No arm of the `match` matches `{}`", value))
+    }
+
+    /// Whether `value` matches `pattern`, names in the pattern are bound as it is matched.
+    fn matches(&mut self, pattern: &HugTreePattern, value: &HugValue) -> bool {
+        match &pattern.kind {
+            HugTreePatternKind::Wildcard => true,
+            HugTreePatternKind::Binding { variable, slot } => {
+                self.set_local(*slot, *variable, value.clone());
+                true
+            }
+            HugTreePatternKind::Literal(literal) => {
+                self.evaluate(literal).compare(value) == Some(Ordering::Equal)
+            }
+            HugTreePatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.evaluate(start).compare(value);
+                let end = value.compare(&self.evaluate(end));
+                start.is_some_and(Ordering::is_le)
+                    && match inclusive {
+                        true => end.is_some_and(Ordering::is_le),
+                        false => end.is_some_and(Ordering::is_lt),
+                    }
+            }
+            HugTreePatternKind::Variant {
+                _type,
+                variant,
+                payload,
+            } => {
+                let HugValue::Enum(value) = value else {
+                    return false;
+                };
+                if value.name != self.idents.resolve(*_type)
+                    || value.variant != self.idents.resolve(*variant)
+                {
+                    return false;
+                }
+
+                match (payload, &value.payload) {
+                    (HugTreePayload::Unit, HugPayload::Unit) => true,
+                    (HugTreePayload::Tuple(patterns), HugPayload::Tuple(values)) => patterns
+                        .iter()
+                        .zip(values)
+                        .all(|(pattern, value)| self.matches(pattern, value)),
+                    (HugTreePayload::Struct(patterns), HugPayload::Struct(_)) => {
+                        patterns.iter().all(|(field, pattern)| {
+                            let field = value.field(self.idents.resolve(*field));
+                            field.is_some_and(|field| self.matches(pattern, field))
+                        })
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// Looks up a variable by its name, mostly useful for inspecting the VM from Rust.
+    pub fn get_named_variable(&self, name: &str) -> Option<&HugValue> {
+        self.idents.get(name).and_then(|id| self.get_variable(id))
     }
 
     #[inline]
@@ -172,4 +1121,58 @@ impl HugVM {
         self.enforce_variables_len(at.0);
         let _ = self.variables.get_mut(at.0).unwrap().insert(value);
     }
+
+    /// Reads a slot of the current frame.
+    #[inline]
+    pub fn get_local(&self, slot: usize) -> Option<&HugValue> {
+        self.frames
+            .last()
+            .and_then(|frame| frame.locals.get(slot))
+            .and_then(|local| local.as_ref().map(|(_, value)| value))
+    }
+
+    /// Stores `variable` in a slot of the current frame.
+    #[inline]
+    pub fn set_local(&mut self, slot: usize, variable: Ident, value: HugValue) {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("No frame to store a local in");
+        frame.locals[slot] = Some((variable, value));
+    }
+}
+
+/// Applies an arithmetic or bitwise operator, logical and comparison operators need more than the
+/// two values and are handled by `HugVM::evaluate`.
+fn arithmetic(
+    operator: HugTreeBinaryOperator,
+    left: &HugValue,
+    right: &HugValue,
+) -> Result<HugValue, String> {
+    match operator {
+        HugTreeBinaryOperator::Multiply => left.multiply(right),
+        HugTreeBinaryOperator::Divide => left.divide(right),
+        HugTreeBinaryOperator::Modulus => left.modulus(right),
+        HugTreeBinaryOperator::Add => left.add(right),
+        HugTreeBinaryOperator::Subtract => left.subtract(right),
+        HugTreeBinaryOperator::ShiftLeft => left.shift_left(right),
+        HugTreeBinaryOperator::ShiftRight => left.shift_right(right),
+        HugTreeBinaryOperator::ShiftLeftOverflow => left.rotate_left(right),
+        HugTreeBinaryOperator::ShiftRightOverflow => left.rotate_right(right),
+        HugTreeBinaryOperator::BinaryAnd => left.bitwise_and(right),
+        HugTreeBinaryOperator::BinaryXOr => left.bitwise_xor(right),
+        HugTreeBinaryOperator::BinaryOr => left.bitwise_or(right),
+        _ => unreachable!("Logical and comparison operators are handled by the VM"),
+    }
+}
+
+/// The modules `entries` use, with the span of the `use`.
+fn collect_uses(entries: &[HugTreeEntry], uses: &mut Vec<(Ident, Span)>) {
+    for entry in entries {
+        match &entry.kind {
+            HugTreeEntryKind::Use { module } => uses.push((*module, entry.span)),
+            HugTreeEntryKind::ModuleDefinition { body, .. } => collect_uses(body, uses),
+            _ => (),
+        }
+    }
 }
@@ -0,0 +1,1062 @@
+use hug_core::HUG_CORE_SCRIPT;
+use hug_interpreter::vm::{HugVM, VM_STACK_SIZE};
+use hug_lib::{
+    unwrap_args,
+    value::{HugEnum, HugStruct, HugValue},
+};
+
+fn run(program: &str) -> HugVM {
+    let mut vm = HugVM::empty();
+    if let Err(diagnostics) = vm.load_script("test.hug", program) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+    vm
+}
+
+fn string_variable(vm: &HugVM, name: &str) -> String {
+    vm.get_named_variable(name)
+        .unwrap_or_else(|| panic!("No variable {}", name))
+        .assert::<String>()
+        .unwrap()
+}
+
+const FORMAT_STRINGS_PROGRAM: &str = r#"
+let name = "hug"
+let pi = 3.14159
+let answer = 42
+let greeting = f"Hello, {name}!"
+let padded = f"[{name:>6}] [{answer:<4}] [{name:*^7}] [{answer:05}]"
+let precise = f"{pi:.2} {{literal}} {'x'}\t{10u8}"
+"#;
+
+#[test]
+fn format_strings() {
+    let vm = run(FORMAT_STRINGS_PROGRAM);
+
+    assert_eq!(string_variable(&vm, "greeting"), "Hello, hug!");
+    assert_eq!(
+        string_variable(&vm, "padded"),
+        "[   hug] [42  ] [**hug**] [00042]"
+    );
+    assert_eq!(string_variable(&vm, "precise"), "3.14 {literal} x\t10");
+}
+
+const CONTROL_FLOW_PROGRAM: &str = r#"
+function last_letter(letters: String) -> Char {
+    let last = ' '
+    for c in letters {
+        if c == 'c' {
+            return c
+        }
+    }
+    return last
+}
+
+function last_small() -> Int32 {
+    for i in 0..10 {
+        if i > 2 && i != 5 {
+            continue
+        }
+        if i == 5 {
+            return i
+        }
+    }
+    return -1
+}
+
+function last_byte() -> UInt8 {
+    for i in 1u8..=3u8 {
+        if i == 3u8 {
+            return i
+        }
+    }
+    return 0u8
+}
+
+function branch(n: Int32) -> String {
+    loop {
+        if n == 5 {
+            return "first"
+        } else if !(n < 5) {
+            return "second"
+        } else {
+            break
+        }
+    }
+    return "third"
+}
+
+let running = true
+while running {
+    break
+}
+
+let letter = last_letter("abc")
+let small = last_small()
+let byte = last_byte()
+let first = branch(5)
+let second = branch(6)
+let third = branch(4)
+
+let before_return = "yes"
+return
+let before_return = "no"
+"#;
+
+#[test]
+fn control_flow() {
+    let vm = run(CONTROL_FLOW_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("letter").assert::<char>(), Some('c'));
+    assert_eq!(variable("small").assert::<i32>(), Some(5));
+    assert_eq!(variable("byte").assert::<u8>(), Some(3));
+    assert_eq!(string_variable(&vm, "first"), "first");
+    assert_eq!(string_variable(&vm, "second"), "second");
+    assert_eq!(string_variable(&vm, "third"), "third");
+    assert_eq!(string_variable(&vm, "before_return"), "yes");
+}
+
+const EXPRESSIONS_PROGRAM: &str = r#"
+let base = 6
+let arithmetic = base * 7 - 10 / 3 + 17 % 5
+let grouped = (base + 1) * -(2 - 5)
+let bits = 1u8 << 4 | 3u8 & ~1u8 ^ 8u8
+let rotated = 0b1000_0001u8 <<< 1u8
+let halved = -7.0f64 / 2.0f64
+let logic = base > 5 && !(base == 6 || false)
+let joined = "hug" + f"{base + 1}"
+let in_format = f"{base * base:>5}"
+"#;
+
+#[test]
+fn expressions() {
+    let vm = run(EXPRESSIONS_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(
+        variable("arithmetic").assert::<i32>(),
+        Some(6 * 7 - 10 / 3 + 17 % 5)
+    );
+    assert_eq!(variable("grouped").assert::<i32>(), Some(21));
+    assert_eq!(
+        variable("bits").assert::<u8>(),
+        Some(1u8 << 4 | 3u8 & !1u8 ^ 8u8)
+    );
+    assert_eq!(variable("rotated").assert::<u8>(), Some(0b0000_0011));
+    assert_eq!(variable("halved").assert::<f64>(), Some(-3.5));
+    assert_eq!(variable("logic").assert::<bool>(), Some(false));
+    assert_eq!(string_variable(&vm, "joined"), "hug7");
+    assert_eq!(string_variable(&vm, "in_format"), "   36");
+}
+
+#[test]
+#[should_panic(expected = "1:9: `200 + 100` overflows `UInt8`")]
+fn overflow() {
+    run("let x = 200u8 + 100u8");
+}
+
+#[test]
+#[should_panic(expected = "Cannot apply `+` to `Int32` and `Float32`")]
+fn mismatched_operands() {
+    run("let x = 1 + 2.0");
+}
+
+const FUNCTIONS_PROGRAM: &str = r#"
+let offset = 100
+let fib_10 = fibonacci(10)
+
+function fibonacci(n: Int32) -> Int32 {
+    if n < 2 {
+        return n
+    }
+    return fibonacci(n - 1) + fibonacci(n - 2)
+}
+
+function first_even_above(start: Int32, limit: Int32) -> Int32 {
+    for i in start..limit {
+        if is_even(i) {
+            return i + offset
+        }
+    }
+    return -1
+}
+
+function is_even(n: Int32) -> Bool {
+    let remainder = n % 2
+    return remainder == 0
+}
+
+function set_nothing() {
+    let local_only = 1
+}
+
+let even = first_even_above(7, 20)
+let none_found = first_even_above(7, 8)
+set_nothing()
+"#;
+
+#[test]
+fn functions() {
+    let vm = run(FUNCTIONS_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("fib_10").assert::<i32>(), Some(55));
+    assert_eq!(variable("even").assert::<i32>(), Some(108));
+    assert_eq!(variable("none_found").assert::<i32>(), Some(-1));
+
+    // Locals don't leak out of their function
+    assert!(vm.get_named_variable("local_only").is_none());
+    assert!(vm.get_named_variable("remainder").is_none());
+}
+
+const BLOCKS_PROGRAM: &str = r#"
+let x = 1
+{
+    let x = 2
+    let y = x
+}
+let after = x
+
+function f() -> Int32 {
+    let x = 10
+    {
+        let x = 20
+    }
+    return x
+}
+let in_function = f()
+"#;
+
+#[test]
+fn blocks() {
+    let vm = run(BLOCKS_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("after").assert::<i32>(), Some(1));
+    assert_eq!(variable("in_function").assert::<i32>(), Some(10));
+    assert!(vm.get_named_variable("y").is_none());
+}
+
+#[test]
+#[should_panic(expected = "`add` takes 2 arguments but 1 were given")]
+fn wrong_argument_count() {
+    run("function add(a: Int32, b: Int32) -> Int32 { return a + b }\nlet x = add(1)");
+}
+
+const SCOPES_PROGRAM: &str = r#"
+let x = 1
+let outer = x
+
+function shadow(x: Int32) -> Int32 {
+    let x = x * 10
+    if x > 5 {
+        let x = x + 1
+        let inner = x
+        return inner + x
+    }
+    return x
+}
+
+// Both calls use `n`, they must not clobber each other
+function count_down(n: Int32) -> Int32 {
+    if n == 0 {
+        return 0
+    }
+    let rest = count_down(n - 1)
+    return n + rest
+}
+
+if true {
+    let block_only = 5
+    let x = block_only
+}
+
+let shadowed = shadow(2)
+let total = count_down(4)
+"#;
+
+#[test]
+fn scopes() {
+    let vm = run(SCOPES_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("x").assert::<i32>(), Some(1));
+    assert_eq!(variable("outer").assert::<i32>(), Some(1));
+    assert_eq!(variable("shadowed").assert::<i32>(), Some(42));
+    assert_eq!(variable("total").assert::<i32>(), Some(10));
+    assert!(vm.get_named_variable("block_only").is_none());
+
+    // Only the top level is left once the script is done
+    assert_eq!(vm.stack().len(), 1);
+    assert!(vm.stack()[0].function.is_none());
+}
+
+#[test]
+#[should_panic(
+    expected = "in inner called at test.hug:7:5\n    depth = Int32(2)\n  in outer called at test.hug:9:1"
+)]
+fn stack_trace() {
+    run(r#"
+function inner(depth: Int32) {
+    let x = 1 / (depth - 2)
+}
+
+function outer() {
+    inner(2)
+}
+outer()
+"#);
+}
+
+#[test]
+fn runaway_recursion() {
+    let program = std::thread::Builder::new()
+        .stack_size(VM_STACK_SIZE)
+        .spawn(|| {
+            run(r#"
+function d(n: Int32) -> Int32 {
+    return d(n + 1)
+}
+d(0)
+"#);
+        })
+        .unwrap();
+
+    let panic = program.join().unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(
+        message.contains("Stack overflow: the call stack is limited to 1000 frames"),
+        "{}",
+        message
+    );
+    assert!(message.contains("  ... 984 more frames\n"), "{}", message);
+}
+
+fn native_add(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let (left, right) = unwrap_args!(args, i32, i32);
+    Some(HugValue::from(left + right))
+}
+
+fn native_nothing(_args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    None
+}
+
+const NATIVE_RETURNS_PROGRAM: &str = r#"
+function twice(n: Int32) -> Int32 {
+    return add(n, n)
+}
+
+function no_value() {
+    let x = 1
+}
+
+let sum = add(1, 2)
+let nested = add(add(1, 2), twice(3)) * 2
+let in_condition = if_positive(add(-5, 2))
+let from_native = nothing()
+no_value()
+let described = f"{nothing()} {add(2, 2)}"
+
+function if_positive(n: Int32) -> Bool {
+    if n > 0 {
+        return true
+    }
+    return false
+}
+"#;
+
+#[test]
+fn native_returns() {
+    let mut vm = HugVM::empty();
+    vm.define_external_function("add", native_add);
+    vm.define_external_function("nothing", native_nothing);
+    if let Err(diagnostics) = vm.load_script("test.hug", NATIVE_RETURNS_PROGRAM) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("sum").assert::<i32>(), Some(3));
+    assert_eq!(variable("nested").assert::<i32>(), Some(18));
+    assert_eq!(variable("in_condition").assert::<bool>(), Some(false));
+    assert!(matches!(variable("from_native"), HugValue::Void));
+    assert_eq!(string_variable(&vm, "described"), "() 4");
+}
+
+/// A VM with the core library, its native functions come from the `libhug_core.so` cargo builds
+/// next to the tests.
+fn core_vm() -> HugVM {
+    let library = std::env::current_exe()
+        .unwrap()
+        .with_file_name("libhug_core.so");
+    let script = HUG_CORE_SCRIPT.replace("target/debug/libhug_core.so", library.to_str().unwrap());
+    let mut vm = HugVM::empty();
+    if let Err(diagnostics) = vm.load_script("core.hug", &script) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm
+}
+
+#[test]
+fn native_library() {
+    let mut vm = core_vm();
+    if let Err(diagnostics) = vm.load_script("test.hug", "let x = add(1, 2)\nprint(\"hi\")") {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    // The functions of the library are called after it is loaded
+    vm.run();
+
+    let x = vm.get_named_variable("x").unwrap().clone();
+    assert_eq!(x.assert::<i32>(), Some(3));
+
+    // Calls are checked against the signatures in `core.hug` before anything runs
+    let diagnostics = vm
+        .load_script("test.hug", "let y = add(\"one\", 2)")
+        .unwrap_err();
+    assert!(vm
+        .render_diagnostics(&diagnostics)
+        .contains("Mismatched types: expected `Int32`, found `String`"));
+}
+
+const ASSIGNMENT_PROGRAM: &str = r#"
+let mut sum = 0
+let mut flags = 1u8
+let mut text = "hug"
+
+function count_to(limit: Int32) -> Int32 {
+    let mut count = 0
+    while count < limit {
+        count += 1
+    }
+    return count
+}
+
+for i in 1..=10 {
+    sum += i
+}
+sum *= 2
+sum -= count_to(5)
+flags <<= 3
+flags |= 1u8
+flags ^= 3u8
+text += "!"
+let counted = count_to(7)
+"#;
+
+#[test]
+fn assignment() {
+    let vm = run(ASSIGNMENT_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("sum").assert::<i32>(), Some(55 * 2 - 5));
+    assert_eq!(variable("flags").assert::<u8>(), Some((1u8 << 3 | 1) ^ 3));
+    assert_eq!(variable("counted").assert::<i32>(), Some(7));
+    assert_eq!(string_variable(&vm, "text"), "hug!");
+}
+
+#[test]
+fn immutable_assignment() {
+    let mut vm = HugVM::empty();
+    let diagnostics = vm.load_script("test.hug", "let x = 1\nx += 1").unwrap_err();
+    assert!(vm
+        .render_diagnostics(&diagnostics)
+        .contains("Cannot assign twice to immutable variable `x`"));
+}
+
+const TYPES_PROGRAM: &str = r#"
+let small: UInt16 = 500
+let mut big: Int64 = 1
+let precise: Float64 = 0.1
+
+function scale(value: Int64, by: Int64) -> Int64 {
+    return value * by
+}
+
+for i in 0..3 {
+    big = scale(big, 1000)
+}
+let negative: Int8 = -128 + 1
+let smallest = -128i8
+let minimum = -2147483648
+"#;
+
+#[test]
+fn static_types() {
+    let vm = run(TYPES_PROGRAM);
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("small").assert::<u16>(), Some(500));
+    assert_eq!(variable("big").assert::<i64>(), Some(1_000_000_000));
+    assert_eq!(variable("precise").assert::<f64>(), Some(0.1));
+    assert_eq!(variable("negative").assert::<i8>(), Some(-127));
+    assert_eq!(variable("smallest").assert::<i8>(), Some(i8::MIN));
+    assert_eq!(variable("minimum").assert::<i32>(), Some(i32::MIN));
+}
+
+#[test]
+fn type_errors() {
+    let mut vm = HugVM::empty();
+    let diagnostics = vm
+        .load_script(
+            "test.hug",
+            "function add(a: Int32, b: Int32) -> Int32 {\n    return a + b\n}\nlet x = add(\"1\", 2)",
+        )
+        .unwrap_err();
+    assert!(vm
+        .render_diagnostics(&diagnostics)
+        .contains("Mismatched types: expected `Int32`, found `String`"));
+}
+
+/// A fresh directory in the temp dir containing `files`.
+fn module_directory(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("hug-{}-{}", name, std::process::id()));
+    for (path, program) in files {
+        let path = directory.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, program).unwrap();
+    }
+    directory
+}
+
+#[test]
+fn modules() {
+    let directory = module_directory(
+        "modules",
+        &[
+            (
+                "utils.hug",
+                "use shared\npublic function double(x: Int32) -> Int32 {\n    return shared.add(x, x)\n}\n",
+            ),
+            (
+                "shapes/square.hug",
+                "use shared\npublic function area(side: Int32) -> Int32 {\n    return side * side\n}\n",
+            ),
+            (
+                "shared.hug",
+                "public function add(a: Int32, b: Int32) -> Int32 {\n    return a + b\n}\n",
+            ),
+        ],
+    );
+    let mut vm = HugVM::empty();
+    vm.add_search_path(&directory);
+    let program = r#"
+use utils
+use shapes.square
+
+module local {
+    let base = 10
+    public function offset(x: Int32) -> Int32 {
+        return x + base
+    }
+}
+
+let four = utils.double(2)
+let nine = shapes.square.area(3)
+let twelve = local.offset(2)
+"#;
+    if let Err(diagnostics) = vm.load_script("test.hug", program) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+    std::fs::remove_dir_all(directory).unwrap();
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("four").assert::<i32>(), Some(4));
+    assert_eq!(variable("nine").assert::<i32>(), Some(9));
+    assert_eq!(variable("twelve").assert::<i32>(), Some(12));
+}
+
+#[test]
+fn module_errors() {
+    let directory = module_directory(
+        "module-errors",
+        &[("a.hug", "use b\n"), ("b.hug", "use a\n")],
+    );
+    let mut vm = HugVM::empty();
+    vm.add_search_path(&directory);
+    let diagnostics = vm
+        .load_script("test.hug", "use a\nuse missing")
+        .unwrap_err();
+    let rendered = vm.render_diagnostics(&diagnostics);
+    std::fs::remove_dir_all(directory).unwrap();
+
+    assert!(rendered.contains("Module `a` uses itself"), "{}", rendered);
+    assert!(rendered.contains("`a` uses `b` uses `a`"), "{}", rendered);
+    assert!(
+        rendered.contains("Could not find module `missing`"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn visibility() {
+    let directory = module_directory(
+        "visibility",
+        &[(
+            "secrets.hug",
+            "function hidden() -> Int32 {\n    return 42\n}\npublic function shown() -> Int32 {\n    return hidden()\n}\n",
+        )],
+    );
+    let mut vm = HugVM::empty();
+    vm.add_search_path(&directory);
+    let program = r#"
+use secrets
+
+module outer {
+    module inner {
+        public function f() -> Int32 {
+            return 1
+        }
+    }
+    public let value = inner.f()
+}
+
+let shown = secrets.shown()
+let value = outer.value
+let hidden = secrets.hidden()
+let f = outer.inner.f()
+"#;
+    let diagnostics = vm.load_script("test.hug", program).unwrap_err();
+    let rendered = vm.render_diagnostics(&diagnostics);
+    std::fs::remove_dir_all(directory).unwrap();
+
+    let messages = diagnostics
+        .iter()
+        .map(|d| (d.span.start.line, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (15, "`hidden` is private to module `secrets`"),
+            (16, "`inner` is private to module `outer`"),
+        ],
+        "{}",
+        rendered
+    );
+    assert_eq!(
+        diagnostics[0].hint.as_deref(),
+        Some("mark it `public` to use it outside of `secrets`")
+    );
+}
+
+const STRUCTS_PROGRAM: &str = r#"
+type Point { x: Int32, y: Int32 }
+
+module shapes {
+    public type Rect {
+        corner: Point
+        width: Int32
+        height: Int32
+    }
+}
+
+function area(rect: shapes.Rect) -> Int32 {
+    return rect.width * rect.height
+}
+
+function moved(point: Point, by: Int32) -> Point {
+    let mut point = point
+    point.x += by
+    point.y = point.y + by
+    return point
+}
+
+let mut rect = shapes.Rect { width: 2, height: 3, corner: Point { x: 0, y: 0 } }
+rect.corner = moved(rect.corner, 5)
+rect.width *= 10
+let size = area(rect)
+let corner = rect.corner.x + moved(rect.corner, 1).y
+let shown = f"{rect}"
+let mirrored = mirror(Point { x: 1, y: 2 })
+let native = make_point(7).x
+"#;
+
+fn native_mirror(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let point = args.next()?.assert::<HugStruct>()?;
+    let (x, y) = (point.field::<i32>("x")?, point.field::<i32>("y")?);
+    Some(HugValue::from(
+        HugStruct::new("Point").with("x", y).with("y", x),
+    ))
+}
+
+fn native_make_point(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let x = unwrap_args!(args, i32);
+    Some(HugValue::from(
+        HugStruct::new("Point").with("x", x).with("y", 0),
+    ))
+}
+
+#[test]
+fn structs() {
+    let mut vm = HugVM::empty();
+    vm.define_external_function("mirror", native_mirror);
+    vm.define_external_function("make_point", native_make_point);
+    if let Err(diagnostics) = vm.load_script("test.hug", STRUCTS_PROGRAM) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("size").assert::<i32>(), Some(60));
+    assert_eq!(variable("corner").assert::<i32>(), Some(11));
+    assert_eq!(
+        string_variable(&vm, "shown"),
+        "shapes.Rect { corner: Point { x: 5, y: 5 }, width: 20, height: 3 }"
+    );
+    let mirrored = variable("mirrored").assert::<HugStruct>().unwrap();
+    assert_eq!(mirrored.field::<i32>("x"), Some(2));
+    assert_eq!(mirrored.field::<i32>("y"), Some(1));
+    assert_eq!(variable("native").assert::<i32>(), Some(7));
+}
+
+const ENUMS_PROGRAM: &str = r#"
+module shapes {
+    public enum Shape {
+        Circle(Int32)
+        Rect { width: Int32, height: Int32 }
+        Empty
+    }
+}
+
+function area(shape: shapes.Shape) -> Int32 {
+    return match shape {
+        shapes.Shape.Circle(radius) => radius * radius * 3,
+        shapes.Shape.Rect { width: 0 } => 0,
+        shapes.Shape.Rect { width, height } => width * height,
+        shapes.Shape.Empty => 0,
+    }
+}
+
+function describe(n: Int32) -> String {
+    let mut text = ""
+    match n {
+        0 => text = "zero"
+        1..=9 => {
+            text = "digit"
+        }
+        -9..0 => text = "negative digit"
+        _ => {
+            return "large"
+        }
+    }
+    return text
+}
+
+let circle = area(shapes.Shape.Circle(2))
+let rect = area(shapes.Shape.Rect { height: 3, width: 4 })
+let flat = area(shapes.Shape.Rect { width: 0, height: 3 })
+let empty = area(shapes.Shape.Empty)
+let small = shapes.Shape.Rect { width: 1, height: 2 }
+let shown = f"{small} {shapes.Shape.Circle(1)}"
+let kinds = f"{describe(0)} {describe(7)} {describe(-3)} {describe(12)}"
+let letter = match 'q' {
+    'a'..='m' => 1,
+    'n'..='z' => 2,
+    _ => 3,
+}
+let flag = match false {
+    true => "yes",
+    false => "no",
+}
+let grown = grow(shapes.Shape.Circle(4))
+let native = area(make_rect(5))
+"#;
+
+fn native_grow(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let shape = args.next()?.assert::<HugEnum>()?;
+    let radius = shape.get(0)?.clone().assert::<i32>()?;
+    Some(HugValue::from(
+        HugEnum::new("shapes.Shape", "Circle").with(radius + 1),
+    ))
+}
+
+fn native_make_rect(mut args: std::vec::IntoIter<HugValue>) -> Option<HugValue> {
+    let side = unwrap_args!(args, i32);
+    Some(HugValue::from(
+        HugEnum::new("shapes.Shape", "Rect")
+            .with_field("width", side)
+            .with_field("height", side),
+    ))
+}
+
+#[test]
+fn enums() {
+    let mut vm = HugVM::empty();
+    vm.define_external_function("grow", native_grow);
+    vm.define_external_function("make_rect", native_make_rect);
+    if let Err(diagnostics) = vm.load_script("test.hug", ENUMS_PROGRAM) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("circle").assert::<i32>(), Some(12));
+    assert_eq!(variable("rect").assert::<i32>(), Some(12));
+    assert_eq!(variable("flat").assert::<i32>(), Some(0));
+    assert_eq!(variable("empty").assert::<i32>(), Some(0));
+    assert_eq!(
+        string_variable(&vm, "shown"),
+        "shapes.Shape.Rect { width: 1, height: 2 } shapes.Shape.Circle(1)"
+    );
+    assert_eq!(
+        string_variable(&vm, "kinds"),
+        "zero digit negative digit large"
+    );
+    assert_eq!(variable("letter").assert::<i32>(), Some(2));
+    assert_eq!(string_variable(&vm, "flag"), "no");
+    let grown = variable("grown").assert::<HugEnum>().unwrap();
+    assert_eq!(grown.variant, "Circle");
+    assert_eq!(grown.get(0).cloned().unwrap().assert::<i32>(), Some(5));
+    assert_eq!(variable("native").assert::<i32>(), Some(25));
+}
+
+const METHODS_PROGRAM: &str = r#"
+type Point { x: Int32, y: Int32 }
+
+impl Point {
+    function new(x: Int32, y: Int32) -> Point {
+        return Point { x: x, y: y }
+    }
+
+    function sum(self) -> Int32 {
+        return self.x + self.y
+    }
+
+    function moved(self, by: Int32) -> Point {
+        return Point.new(self.x + by, self.y + by)
+    }
+}
+
+module shapes {
+    public enum Shape { Square(Int32), Empty }
+
+    impl Shape {
+        public function area(self) -> Int32 {
+            return match self {
+                Shape.Square(side) => side * side,
+                Shape.Empty => 0,
+            }
+        }
+
+        function secret(self) -> Int32 {
+            return 42
+        }
+    }
+
+    public function peek(shape: Shape) -> Int32 {
+        return shape.secret()
+    }
+}
+
+type Line { start: Point, end: Point }
+
+let mut total = 0
+function add(line: Line) {
+    total += line.end.sum()
+    line.start.moved(1)
+}
+
+let origin = Point.new(1, 2)
+let line = Line { start: origin, end: origin.moved(10) }
+add(line)
+let chained = Point.new(0, 0).moved(2).moved(3).sum()
+let square = shapes.Shape.Square(3).area()
+let peeked = shapes.peek(shapes.Shape.Empty)
+let native = make_point(7).sum()
+"#;
+
+#[test]
+fn methods() {
+    let mut vm = HugVM::empty();
+    vm.define_external_function("make_point", native_make_point);
+    if let Err(diagnostics) = vm.load_script("test.hug", METHODS_PROGRAM) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("total").assert::<i32>(), Some(23));
+    assert_eq!(variable("chained").assert::<i32>(), Some(10));
+    assert_eq!(variable("square").assert::<i32>(), Some(9));
+    assert_eq!(variable("peeked").assert::<i32>(), Some(42));
+    // `make_point` is native, so `sum` is found once the value is there
+    assert_eq!(variable("native").assert::<i32>(), Some(7));
+
+    let mut vm = HugVM::empty();
+    vm.define_external_function("make_point", native_make_point);
+    let program = format!(
+        "{}\nlet hidden = shapes.Shape.Empty.secret()",
+        METHODS_PROGRAM
+    );
+    let diagnostics = vm.load_script("test.hug", &program).unwrap_err();
+    assert_eq!(
+        diagnostics.len(),
+        1,
+        "{}",
+        vm.render_diagnostics(&diagnostics)
+    );
+    assert_eq!(
+        diagnostics[0].message,
+        "`secret` is private to module `shapes`"
+    );
+}
+
+const TRAITS_PROGRAM: &str = r#"
+type Point { x: Int32, y: Int32 }
+
+trait Shape {
+    function area(self) -> Int32
+    function name(self) -> String
+}
+
+type Square { side: Int32 }
+enum Blob { Small, Big(Int32) }
+
+impl Shape for Square {
+    function area(self) -> Int32 { return self.side * self.side }
+    function name(self) -> String { return "square" }
+}
+impl Shape for Blob {
+    function area(self) -> Int32 {
+        return match self {
+            Blob.Small => 1,
+            Blob.Big(size) => size,
+        }
+    }
+    function name(self) -> String { return "blob" }
+}
+
+impl Add for Point {
+    function add(self, other: Point) -> Point {
+        return Point { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+impl Eq for Point {
+    function eq(self, other: Point) -> Bool { return self.x + self.y == other.x + other.y }
+}
+impl Display for Point {
+    function format(self) -> String { return f"<{self.x}, {self.y}>" }
+}
+
+function describe(shape: Shape) -> String {
+    return f"{shape.name()} of {shape.area()}"
+}
+function show(value: Display) -> String {
+    return f"[{value}]"
+}
+function twice(value: Add) -> Add {
+    return value.add(value)
+}
+
+let square = describe(Square { side: 3 })
+let blob = describe(Blob.Big(5))
+let mut moved = Point { x: 1, y: 2 } + Point { x: 10, y: 20 }
+moved += Point { x: 100, y: 200 }
+let same = Point { x: 1, y: 2 } == Point { x: 2, y: 1 }
+let different = Point { x: 1, y: 2 } != Point { x: 1, y: 3 }
+let text = f"moved to {moved}"
+let shown = show(moved)
+let number = show(42)
+let native = show(make_point(7))
+let doubled = twice(21)
+let joined = twice("ab")
+"#;
+
+#[test]
+fn traits() {
+    let mut vm = HugVM::empty();
+    vm.define_external_function("make_point", native_make_point);
+    if let Err(diagnostics) = vm.load_script("test.hug", TRAITS_PROGRAM) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+
+    assert_eq!(string_variable(&vm, "square"), "square of 9");
+    assert_eq!(string_variable(&vm, "blob"), "blob of 5");
+    assert_eq!(string_variable(&vm, "text"), "moved to <111, 222>");
+    assert_eq!(string_variable(&vm, "shown"), "[<111, 222>]");
+    assert_eq!(string_variable(&vm, "number"), "[42]");
+    // The type of a native value is only known when it runs
+    assert_eq!(string_variable(&vm, "native"), "[<7, 0>]");
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(variable("same").assert::<bool>(), Some(true));
+    assert_eq!(variable("different").assert::<bool>(), Some(true));
+    // Builtin types implement the builtin traits with their operators
+    assert_eq!(variable("doubled").assert::<i32>(), Some(42));
+    assert_eq!(string_variable(&vm, "joined"), "abab");
+}
+
+const GENERICS_PROGRAM: &str = r#"
+type Pair<A, B> { first: A, second: B }
+enum Option<T> { None, Some(T) }
+type Vector { x: Int32, y: Int32 }
+
+impl Pair {
+    function new(first: A, second: B) -> Pair<A, B> {
+        return Pair { first: first, second: second }
+    }
+    function swap(self) -> Pair<B, A> {
+        return Pair { first: self.second, second: self.first }
+    }
+}
+impl Add for Vector {
+    function add(self, other: Vector) -> Vector {
+        return Vector { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+impl Eq for Vector {
+    function eq(self, other: Vector) -> Bool { return self.x == other.x }
+}
+
+function identity<T>(value: T) -> T { return value }
+function unwrap_or<T>(option: Option<T>, default: T) -> T {
+    return match option {
+        Option.Some(value) => value,
+        Option.None => default,
+    }
+}
+function sum<T: Add>(a: T, b: T) -> T { return a + b }
+function same<T: Eq>(a: T, b: T) -> Bool { return a == b }
+function show<T: Display>(value: T) -> String { return f"({value})" }
+
+let name = identity("hug")
+let pair = Pair.new(1, "one").swap()
+let word = pair.first
+let some = unwrap_or(Option.Some(5), 0)
+let none: Option<Int32> = Option.None
+let fallback = unwrap_or(none, 7)
+let total = sum(20, 22)
+let vector = sum(Vector { x: 1, y: 2 }, Vector { x: 10, y: 20 })
+let joined = sum("a", "b")
+let equal = same(Vector { x: 1, y: 2 }, Vector { x: 1, y: 3 })
+let unequal = same(1, 2)
+let shown = show(3.5)
+"#;
+
+#[test]
+fn generics() {
+    let mut vm = HugVM::empty();
+    if let Err(diagnostics) = vm.load_script("test.hug", GENERICS_PROGRAM) {
+        panic!("{}", vm.render_diagnostics(&diagnostics));
+    }
+    vm.run();
+
+    let variable = |name| vm.get_named_variable(name).unwrap().clone();
+    assert_eq!(string_variable(&vm, "name"), "hug");
+    assert_eq!(string_variable(&vm, "word"), "one");
+    assert_eq!(variable("some").assert::<i32>(), Some(5));
+    assert_eq!(variable("fallback").assert::<i32>(), Some(7));
+    assert_eq!(variable("total").assert::<i32>(), Some(42));
+    // Operators on a type parameter use the methods of the type it is at runtime
+    let HugValue::Struct(vector) = variable("vector") else {
+        panic!("Expected a struct");
+    };
+    assert_eq!(vector.get("y").unwrap().clone().assert::<i32>(), Some(22));
+    assert_eq!(string_variable(&vm, "joined"), "ab");
+    assert_eq!(variable("equal").assert::<bool>(), Some(true));
+    assert_eq!(variable("unequal").assert::<bool>(), Some(false));
+    assert_eq!(string_variable(&vm, "shown"), "(3.5)");
+}
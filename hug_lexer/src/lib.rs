@@ -1,36 +1,33 @@
+use hug_lib::{diagnostic::Diagnostic, interner::Interner};
 use parser::{generate_pairs, TokenPair};
 use tokenizer::{Token, TokenKind, Tokenizer};
 
+pub mod literal;
 pub mod parser;
 pub mod tokenizer;
 
+/// Removes the tokens the parser doesn't care about: whitespace and regular comments. Doc comments
+/// are kept so they can be attached to declarations.
 pub trait FilterUseless {
     fn filter_useless(self) -> Self;
 }
 
-impl FilterUseless for Vec<TokenPair> {
-    fn filter_useless(self) -> Self {
-        let mut new_self = Vec::with_capacity(self.capacity());
-
-        for pair in self {
-            match pair.token.kind {
-                TokenKind::LineComment | TokenKind::BlockComment | TokenKind::Whitespace => (),
-                _ => new_self.push(TokenPair {
-                    text: pair.text.clone(),
-                    token: pair.token,
-                }),
-            }
-        }
-
-        new_self.shrink_to_fit();
-        new_self
+impl FilterUseless for Vec<TokenPair<'_>> {
+    fn filter_useless(mut self) -> Self {
+        self.retain(|pair| {
+            !matches!(
+                pair.token.kind,
+                TokenKind::LineComment | TokenKind::BlockComment | TokenKind::Whitespace
+            )
+        });
+        self
     }
 }
 
 pub trait CustomDisplay {
     fn display(&self) -> String;
 }
-impl CustomDisplay for Vec<TokenPair> {
+impl CustomDisplay for Vec<TokenPair<'_>> {
     fn display(&self) -> String {
         let mut buffer = String::new();
         let max_len = self
@@ -60,19 +57,18 @@ impl CustomDisplay for Vec<TokenPair> {
     }
 }
 
-pub fn lex(program: &str) -> Vec<TokenPair> {
-    let tokens = tokenize(program);
-    generate_pairs(program, tokens)
+pub fn lex<'a>(program: &'a str, idents: &mut Interner) -> (Vec<TokenPair<'a>>, Vec<Diagnostic>) {
+    let (tokens, diagnostics) = tokenize(program, idents);
+    (generate_pairs(program, tokens), diagnostics)
 }
 
-pub fn tokenize(program: &str) -> Vec<Token> {
-    let mut buffer = String::new();
-    buffer.push_str("import core");
-    Tokenizer::new(program).tokenize()
+pub fn tokenize(program: &str, idents: &mut Interner) -> (Vec<Token>, Vec<Diagnostic>) {
+    Tokenizer::new(program, idents).tokenize()
 }
 
 pub fn run_test(program: &str, expected_result: &[(TokenKind, usize)]) {
-    let tokens = tokenize(program);
+    let (tokens, diagnostics) = tokenize(program, &mut Interner::new());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
     for (token, (expected_kind, expected_len)) in tokens.iter().zip(expected_result.iter()) {
         println!(
             "Token: {:?}, Expected: {:?}, len: {}",
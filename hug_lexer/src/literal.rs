@@ -0,0 +1,383 @@
+//! Decoding of literal text into `HugValue`s. Used by the tokenizer to report invalid literals and
+//! by `TokenPair::parse_literal` to produce the actual values.
+
+use std::ops::Range;
+
+use hug_lib::value::HugValue;
+
+use crate::tokenizer::Base;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteralError {
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl LiteralError {
+    pub fn new(message: impl Into<String>) -> LiteralError {
+        LiteralError {
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> LiteralError {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl Base {
+    pub fn radix(self) -> u32 {
+        match self {
+            Base::Binary => 2,
+            Base::Octal => 8,
+            Base::Hexadecimal => 16,
+            Base::Decimal => 10,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Base::Binary => "binary",
+            Base::Octal => "octal",
+            Base::Hexadecimal => "hexadecimal",
+            Base::Decimal => "decimal",
+        }
+    }
+}
+
+/// Splits a number literal into its base, digits and type suffix. The digits still contain
+/// separators (`_`).
+pub fn split_number(text: &str) -> (Base, &str, &str) {
+    let (base, body) = match text.get(..2) {
+        Some("0b") => (Base::Binary, &text[2..]),
+        Some("0o") => (Base::Octal, &text[2..]),
+        Some("0x") => (Base::Hexadecimal, &text[2..]),
+        _ => (Base::Decimal, text),
+    };
+
+    for (i, c) in body.char_indices() {
+        let is_suffix = match base {
+            Base::Hexadecimal => !c.is_ascii_hexdigit() && c != '_',
+            Base::Decimal if c == 'e' || c == 'E' => {
+                // Only an exponent when followed by (signed) digits
+                let rest = &body[i + 1..];
+                let rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+                !rest.starts_with(|c: char| c.is_ascii_digit())
+            }
+            _ => c.is_alphabetic(),
+        };
+
+        if is_suffix {
+            return (base, &body[..i], &body[i..]);
+        }
+    }
+
+    (base, body, "")
+}
+
+/// Parses an integer or float literal like `0xFF`, `1_000u16` or `2.5e-3f64`. Literals without a
+/// suffix become an `Int32` or `Float32`.
+pub fn parse_number(text: &str) -> Result<HugValue, LiteralError> {
+    parse_signed_number(text, false)
+}
+
+/// Parses a number literal that comes after a `-` when `negative`. The sign decides whether it
+/// fits in its type, `-128i8` does even though `128i8` doesn't.
+pub fn parse_signed_number(text: &str, negative: bool) -> Result<HugValue, LiteralError> {
+    let (base, digits, suffix) = split_number(text);
+    let digits = digits.replace('_', "");
+
+    if digits.is_empty() {
+        return Err(LiteralError::new(format!(
+            "Missing digits in {} literal",
+            base.name()
+        )));
+    }
+
+    let is_float = base == Base::Decimal && digits.contains(['.', 'e', 'E']);
+    match suffix {
+        "" if is_float => float::<f32>(&digits, negative, "Float32"),
+        // Digits are checked first, only a literal that is out of range needs a bigger type
+        "" => digits_in_base(&digits, base).and_then(|()| {
+            integer::<i32>(&digits, base, negative, "Int32")
+                .map_err(|e| e.with_hint("add a type suffix like `i64` to use a bigger type"))
+        }),
+        "f32" | "f64" if base != Base::Decimal => Err(LiteralError::new(format!(
+            "Float suffix `{}` is not allowed on a {} literal",
+            suffix,
+            base.name()
+        ))),
+        "f32" => float::<f32>(&digits, negative, "Float32"),
+        "f64" => float::<f64>(&digits, negative, "Float64"),
+        _ if is_float && INTEGER_SUFFIXES.contains(&suffix) => Err(LiteralError::new(format!(
+            "Integer suffix `{}` on a float literal",
+            suffix
+        ))
+        .with_hint("use `f32` or `f64` instead")),
+        "i8" => integer::<i8>(&digits, base, negative, "Int8"),
+        "i16" => integer::<i16>(&digits, base, negative, "Int16"),
+        "i32" => integer::<i32>(&digits, base, negative, "Int32"),
+        "i64" => integer::<i64>(&digits, base, negative, "Int64"),
+        "i128" => integer::<i128>(&digits, base, negative, "Int128"),
+        "u8" => integer::<u8>(&digits, base, negative, "UInt8"),
+        "u16" => integer::<u16>(&digits, base, negative, "UInt16"),
+        "u32" => integer::<u32>(&digits, base, negative, "UInt32"),
+        "u64" => integer::<u64>(&digits, base, negative, "UInt64"),
+        "u128" => integer::<u128>(&digits, base, negative, "UInt128"),
+        other => Err(
+            LiteralError::new(format!("Invalid suffix `{}` for a number literal", other))
+                .with_hint("valid suffixes are `i8`-`i128`, `u8`-`u128`, `f32` and `f64`"),
+        ),
+    }
+}
+
+const INTEGER_SUFFIXES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128",
+];
+
+fn integer<T>(
+    digits: &str,
+    base: Base,
+    negative: bool,
+    type_name: &str,
+) -> Result<HugValue, LiteralError>
+where
+    T: TryFrom<u128> + TryFrom<i128>,
+    HugValue: From<T>,
+{
+    digits_in_base(digits, base)?;
+    u128::from_str_radix(digits, base.radix())
+        .ok()
+        .and_then(|value| match negative {
+            true => 0i128
+                .checked_sub_unsigned(value)
+                .and_then(|value| T::try_from(value).ok()),
+            false => T::try_from(value).ok(),
+        })
+        .map(HugValue::from)
+        .ok_or_else(|| {
+            LiteralError::new(format!(
+                "Integer literal is out of range for `{}`",
+                type_name
+            ))
+        })
+}
+
+fn digits_in_base(digits: &str, base: Base) -> Result<(), LiteralError> {
+    match digits.chars().find(|c| !c.is_digit(base.radix())) {
+        Some(c) => Err(LiteralError::new(format!(
+            "Invalid digit `{}` in {} literal",
+            c,
+            base.name()
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn float<T>(digits: &str, negative: bool, type_name: &str) -> Result<HugValue, LiteralError>
+where
+    T: std::str::FromStr + Into<f64> + Copy,
+    HugValue: From<T>,
+{
+    let sign = if negative { "-" } else { "" };
+    match format!("{}{}", sign, digits).parse::<T>() {
+        Ok(value) if value.into().is_finite() => Ok(HugValue::from(value)),
+        Ok(_) => Err(LiteralError::new(format!(
+            "Float literal is out of range for `{}`",
+            type_name
+        ))),
+        Err(_) => Err(LiteralError::new("Invalid float literal")),
+    }
+}
+
+/// Decodes the escape sequences in the body of a string or char literal (without the quotes).
+/// Invalid escapes are reported through `on_error` with their byte range in `body`, and are kept
+/// as-is in the output so decoding can continue.
+pub fn unescape(body: &str, mut on_error: impl FnMut(Range<usize>, LiteralError)) -> String {
+    let mut buffer = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            buffer.push(c);
+            continue;
+        }
+
+        let escape = match chars.next() {
+            Some((_, 'n')) => Ok('\n'),
+            Some((_, 't')) => Ok('\t'),
+            Some((_, 'r')) => Ok('\r'),
+            Some((_, '0')) => Ok('\0'),
+            Some((_, '\\')) => Ok('\\'),
+            Some((_, '"')) => Ok('"'),
+            Some((_, '\'')) => Ok('\''),
+            Some((_, 'x')) => {
+                let mut digits = String::new();
+                while digits.len() < 2 && chars.peek().is_some_and(|(_, c)| c.is_ascii_hexdigit()) {
+                    digits.push(chars.next().unwrap().1);
+                }
+
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(value) if digits.len() == 2 && value <= 0x7F => Ok(value as char),
+                    Ok(_) if digits.len() == 2 => {
+                        Err(LiteralError::new("`\\x` escapes must be at most `\\x7F`")
+                            .with_hint("use `\\u{..}` for other characters"))
+                    }
+                    _ => Err(LiteralError::new(
+                        "`\\x` escapes need exactly two hexadecimal digits",
+                    )),
+                }
+            }
+            Some((_, 'u')) => {
+                let mut digits = None;
+                if chars.next_if(|(_, c)| *c == '{').is_some() {
+                    let mut buffer = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| *c != '}') {
+                        buffer.push(c);
+                    }
+
+                    if chars.next_if(|(_, c)| *c == '}').is_some() {
+                        digits = Some(buffer);
+                    }
+                }
+
+                match digits {
+                    Some(digits) if (1..=6).contains(&digits.len()) => {
+                        u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                LiteralError::new(format!(
+                                    "`{}` is not a valid unicode character",
+                                    digits
+                                ))
+                            })
+                    }
+                    _ => Err(LiteralError::new("Invalid unicode escape")
+                        .with_hint("unicode escapes look like `\\u{1F600}`")),
+                }
+            }
+            Some((_, other)) => Err(LiteralError::new(format!(
+                "Unknown escape sequence `\\{}`",
+                other
+            ))
+            .with_hint("use `\\\\` for a literal backslash")),
+            None => Err(LiteralError::new("Expected an escape sequence after `\\`")),
+        };
+
+        let end = chars.peek().map(|(i, _)| *i).unwrap_or(body.len());
+        match escape {
+            Ok(c) => buffer.push(c),
+            Err(error) => {
+                buffer.push_str(&body[start..end]);
+                on_error(start..end, error);
+            }
+        }
+    }
+
+    buffer
+}
+
+/// Parses the body of a char literal (without the quotes).
+pub fn parse_char(body: &str) -> Result<char, LiteralError> {
+    let mut error = None;
+    let decoded = unescape(body, |_, e| {
+        error.get_or_insert(e);
+    });
+
+    if let Some(error) = error {
+        return Err(error);
+    }
+
+    let mut chars = decoded.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        (None, _) => Err(LiteralError::new("Empty character literal")),
+        _ => Err(
+            LiteralError::new("Character literal must contain exactly one character")
+                .with_hint("use double quotes for strings"),
+        ),
+    }
+}
+
+/// A piece of a format string body, ranges are byte offsets into the body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatPiece {
+    /// Literal text, escape sequences are not decoded yet.
+    Text(Range<usize>),
+    /// An interpolation like `{value:>8}`, `spec` is the part after the `:`.
+    Interpolation {
+        expression: Range<usize>,
+        spec: Option<Range<usize>>,
+    },
+}
+
+/// Splits the body of a format string (without `f"` and `"`) into text and interpolations.
+/// `{{` and `}}` are literal braces. Errors are reported through `on_error` with their byte range in
+/// `body`.
+pub fn split_format_string(
+    body: &str,
+    mut on_error: impl FnMut(Range<usize>, LiteralError),
+) -> Vec<FormatPiece> {
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' | '}' if chars.next_if(|(_, next)| *next == c).is_some() => {
+                // Escaped brace, keep the first one as text
+                pieces.push(FormatPiece::Text(text_start..i + 1));
+                text_start = i + 2;
+            }
+            '}' => {
+                on_error(
+                    i..i + 1,
+                    LiteralError::new("Unmatched `}` in format string")
+                        .with_hint("use `}}` to insert a literal `}`"),
+                );
+            }
+            '{' => {
+                pieces.push(FormatPiece::Text(text_start..i));
+
+                let end = match body[i..].find('}') {
+                    Some(end) => i + end,
+                    None => {
+                        on_error(
+                            i..body.len(),
+                            LiteralError::new("Unterminated interpolation in format string")
+                                .with_hint("use `{{` to insert a literal `{`"),
+                        );
+                        text_start = body.len();
+                        break;
+                    }
+                };
+
+                let (expression, spec) = match body[i + 1..end].find(':') {
+                    Some(colon) => (i + 1..i + 1 + colon, Some(i + 2 + colon..end)),
+                    None => (i + 1..end, None),
+                };
+
+                if body[expression.clone()].trim().is_empty() {
+                    on_error(
+                        i..end + 1,
+                        LiteralError::new("Empty interpolation in format string")
+                            .with_hint("put an expression between the braces, like `{name}`"),
+                    );
+                } else {
+                    pieces.push(FormatPiece::Interpolation { expression, spec });
+                }
+
+                while chars.next_if(|(j, _)| *j <= end).is_some() {}
+                text_start = end + 1;
+            }
+            _ => (),
+        }
+    }
+
+    pieces.push(FormatPiece::Text(text_start..body.len()));
+    pieces.retain(|piece| !matches!(piece, FormatPiece::Text(range) if range.is_empty()));
+    pieces
+}
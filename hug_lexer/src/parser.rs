@@ -1,21 +1,67 @@
-use hug_lib::value::HugValue;
+use hug_lib::{span::Span, value::HugValue};
 
-use crate::tokenizer::Token;
+use crate::{
+    literal,
+    tokenizer::{LiteralKind, Token},
+};
 
-#[derive(Debug, Clone)]
-pub struct TokenPair {
-    pub text: String,
+/// A token together with the source text it was made from.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenPair<'a> {
+    pub text: &'a str,
     pub token: Token,
 }
 
-impl TokenPair {
+impl<'a> TokenPair<'a> {
+    #[inline]
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+
+    /// Text between the quotes of a string or char literal.
+    fn quoted_body(&self, quote: char) -> &'a str {
+        let text = self.text.strip_prefix(quote).unwrap_or(self.text);
+        text.strip_suffix(quote).unwrap_or(text)
+    }
+
+    /// Text of a `///` or `//!` doc comment without the comment marker and line ending.
+    pub fn doc_text(&self) -> &'a str {
+        let text = self.text.get(3..).unwrap_or_default();
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        text.trim_end_matches(['\n', '\r'])
+    }
+
     pub fn parse_literal(&self) -> Option<HugValue> {
-        if let Some(_) = self.token.kind.expect_literal() {
-            if let Ok(int) = self.text.parse::<i32>() {
-                Some(HugValue::from(int))
-            } else if let Ok(float) = self.text.parse::<f32>() {
-                Some(HugValue::from(float))
-            } else if self.text.len() > 2 {
+        if let Some(kind) = self.token.kind.expect_literal() {
+            match kind {
+                LiteralKind::Integer(_) | LiteralKind::Float(_) => {
+                    return literal::parse_number(self.text).ok();
+                }
+                LiteralKind::Boolean => return Some(HugValue::from(self.text == "true")),
+                LiteralKind::Char => {
+                    return literal::parse_char(self.quoted_body('\''))
+                        .ok()
+                        .map(HugValue::from);
+                }
+                LiteralKind::String => {
+                    let body = self.quoted_body('"');
+                    return Some(HugValue::from(literal::unescape(body, |_, _| ())));
+                }
+                LiteralKind::RawString => {
+                    let text = self.text.strip_prefix('r').unwrap_or(self.text);
+                    let guards = &text[..text.len() - text.trim_start_matches('#').len()];
+                    let body = text
+                        .strip_prefix(guards)
+                        .and_then(|t| t.strip_suffix(guards))
+                        .unwrap_or(text);
+                    let body = body.strip_prefix('"').unwrap_or(body);
+                    let body = body.strip_suffix('"').unwrap_or(body);
+                    return Some(HugValue::from(body.to_string()));
+                }
+                _ => (),
+            }
+
+            if self.text.len() > 2 {
                 Some(HugValue::from(String::from(
                     &self.text[1..self.text.len() - 1],
                 )))
@@ -28,20 +74,15 @@ impl TokenPair {
     }
 }
 
-pub fn generate_pairs(program: &str, tokens: Vec<Token>) -> Vec<TokenPair> {
-    let mut pairs = Vec::new();
+pub fn generate_pairs(program: &str, tokens: Vec<Token>) -> Vec<TokenPair<'_>> {
+    let mut pairs = Vec::with_capacity(tokens.len());
 
-    let mut chars = program.chars();
+    // Tokens cover the whole program, so each token starts where the previous one ended
+    let mut rest = program;
     for token in tokens {
-        let mut buffer = String::new();
-        for _i in 0..token.len {
-            buffer.push(chars.next().unwrap());
-        }
-
-        pairs.push(TokenPair {
-            text: buffer,
-            token,
-        })
+        let (text, next) = rest.split_at(token.len);
+        rest = next;
+        pairs.push(TokenPair { text, token })
     }
 
     pairs
@@ -1,31 +1,45 @@
-use std::{collections::HashMap, str::Chars};
+use std::{ops::Range, str::Chars};
 
-use hug_lib::Ident;
+use hug_lib::{
+    diagnostic::Diagnostic,
+    format::FormatSpec,
+    interner::Interner,
+    span::{FileId, Position, Span},
+    Ident,
+};
+
+use crate::literal::{self, FormatPiece, LiteralError};
 
 type TokenList = Vec<Token>;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Token {
     pub kind: TokenKind,
+    /// Length in bytes, the same unit as the offsets in `span`.
     pub len: usize,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TokenKind {
     // Comments
-    LineComment,  //  //
-    BlockComment, //  /* .. */
+    LineComment,     //  //
+    BlockComment,    //  /* .. */
+    DocComment,      //  ///
+    InnerDocComment, //  //!
 
     Whitespace, //  \s,\n,\n\r, etc.
 
     Literal(LiteralKind),       //  420, "nice", 6.9, 'F'
-    Keyword(KeywordKind),       //  var, function, type, module
+    Keyword(KeywordKind),       //  let, function, if, while
     Identifier(Ident),          //  var [this] = 10
     Annotation(AnnotationKind), //  @
 
     // Not specific to any usage
     Comma,            //  ,
     Dot,              //  .
+    Range,            //  ..
+    RangeInclusive,   //  ..=
     OpenParenthesis,  //  (
     CloseParenthesis, //  )
     OpenBrace,        //  {
@@ -33,6 +47,8 @@ pub enum TokenKind {
     OpenBracket,      //  [
     CloseBracket,     //  ]
     Colon,            //  :
+    Arrow,            //  ->
+    FatArrow,         //  =>
 
     // Operators
     Assign,         //  =
@@ -71,6 +87,8 @@ pub enum TokenKind {
     ShiftRight,         //  >>
     ShiftLeftOverflow,  //  <<<
     ShiftRightOverflow, //  >>>
+    ShiftLeftAssign,    //  <<=
+    ShiftRightAssign,   //  >>=
 
     Unknown, // Error
 }
@@ -85,12 +103,27 @@ pub enum AnnotationKind {
 pub enum KeywordKind {
     Enum,
     Function,
+    Impl,
     Let,
+    Mut,
     Module,
     Private,
     Public,
+    Trait,
     Type,
     Use,
+
+    // Control flow
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Loop,
+    Break,
+    Continue,
+    Return,
+    Match,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -147,26 +180,45 @@ impl TokenKind {
 }
 
 pub struct Tokenizer<'a> {
+    pub program: &'a str,
     pub len: usize,
     pub chars: Chars<'a>,
-    pub idents: HashMap<String, Ident>,
+    pub idents: &'a mut Interner,
+    pub file: FileId,
+    pub position: Position,
+    token_start: Position,
+    start_offset: usize,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl<'a> Tokenizer<'a> {
-    pub fn new(program: &'a str) -> Self {
+    pub fn new(program: &'a str, idents: &'a mut Interner) -> Self {
         Self {
+            program,
             len: program.len(),
             chars: program.chars(),
-            idents: HashMap::new(),
+            idents,
+            file: FileId::default(),
+            position: Position::new(),
+            token_start: Position::new(),
+            start_offset: 0,
+            diagnostics: Vec::new(),
         }
     }
 
-    pub fn with_idents(idents: HashMap<String, Ident>, program: &'a str) -> Self {
-        Self {
-            len: program.len(),
-            chars: program.chars(),
-            idents,
-        }
+    /// Makes spans start at `position` in `file`, for tokenizing a piece of a bigger file (like
+    /// the interpolations of a format string).
+    pub fn starting_at(mut self, file: FileId, position: Position) -> Self {
+        self.file = file;
+        self.position = position;
+        self.token_start = position;
+        self.start_offset = position.offset;
+        self
+    }
+
+    /// Source text between two absolute byte offsets.
+    pub fn source(&self, start: usize, end: usize) -> &'a str {
+        &self.program[start - self.start_offset..end - self.start_offset]
     }
 
     pub fn consumed_len(&self) -> usize {
@@ -175,10 +227,28 @@ impl<'a> Tokenizer<'a> {
 
     pub fn reset_consumed_len(&mut self) {
         self.len = self.chars.as_str().len();
+        self.token_start = self.position;
+    }
+
+    /// Span from the start of the current token up to the current position.
+    pub fn token_span(&self) -> Span {
+        Span::new(self.file, self.token_start, self.position)
     }
 
+    /// Reports an error spanning the current token.
+    pub fn error(&mut self, message: impl Into<String>, hint: Option<&str>) {
+        let mut diagnostic = Diagnostic::error(self.token_span(), message);
+        if let Some(hint) = hint {
+            diagnostic = diagnostic.with_hint(hint);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<char> {
-        self.chars.next()
+        let c = self.chars.next()?;
+        self.position.advance(c);
+        Some(c)
     }
 
     pub fn peek_next(&self) -> char {
@@ -187,7 +257,7 @@ impl<'a> Tokenizer<'a> {
 
     pub fn peek_next_next(&self) -> char {
         let mut chars = self.chars.clone();
-        chars.next().expect("Unexpected end of file!");
+        chars.next();
         chars.next().unwrap_or('\0')
     }
 
@@ -203,77 +273,265 @@ impl<'a> Tokenizer<'a> {
 
     pub fn line_comment(&mut self) -> TokenKind {
         self.next().unwrap(); // Skip /[/]
+
+        // `////` and longer are regular comments, like in Rust
+        let kind = match (self.peek_next(), self.peek_next_next()) {
+            ('/', '/') => TokenKind::LineComment,
+            ('/', _) => TokenKind::DocComment,
+            ('!', _) => TokenKind::InnerDocComment,
+            _ => TokenKind::LineComment,
+        };
+
         self.ignore_until(|c| c == '\n');
         self.next();
-        TokenKind::LineComment
+        kind
     }
 
     pub fn block_comment(&mut self) -> TokenKind {
         self.next().unwrap(); // Skip /[*]
-        let mut can_end = false;
-        while let Some(c) = self.next() {
-            match c {
-                '*' => can_end = true,
-                '/' if can_end => break,
-                _ => (),
+
+        // Block comments nest, so commenting out code that contains comments works
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Some('/') if self.peek_next() == '*' => {
+                    self.next(); // Skip /[*]
+                    depth += 1;
+                }
+                Some('*') if self.peek_next() == '/' => {
+                    self.next(); // Skip *[/]
+                    depth -= 1;
+                }
+                Some(_) => (),
+                None => break,
             }
         }
-        self.next();
+
+        if depth > 0 {
+            let hint = if depth == 1 {
+                "close the comment with `*/`".to_string()
+            } else {
+                format!("close the comments with `*/`, {} are still open", depth)
+            };
+            self.error("Unterminated block comment", Some(&hint));
+        }
+
         TokenKind::BlockComment
     }
 
     pub fn operator(&mut self, operator: TokenKind) -> TokenKind {
+        let assign_operator = match operator {
+            TokenKind::Add => TokenKind::AddAssign,
+            TokenKind::Subtract => TokenKind::SubtractAssign,
+            TokenKind::Multiply => TokenKind::MultiplyAssign,
+            TokenKind::Divide => TokenKind::DivideAssign,
+            TokenKind::Modulus => TokenKind::ModulusAssign,
+            TokenKind::BinaryNot => TokenKind::BinaryNotAssign,
+            TokenKind::BinaryXOr => TokenKind::BinaryXOrAssign,
+            TokenKind::BinaryAnd => TokenKind::BinaryAndAssign,
+            TokenKind::BinaryOr => TokenKind::BinaryOrAssign,
+            other => return other, // No <operator>= variant
+        };
+
         if self.peek_next() == '=' {
             self.next().unwrap(); // Skip <operator>[=]
-            match operator {
-                TokenKind::Add => TokenKind::AddAssign,
-                TokenKind::Subtract => TokenKind::SubtractAssign,
-                TokenKind::Multiply => TokenKind::MultiplyAssign,
-                TokenKind::Divide => TokenKind::DivideAssign,
-                TokenKind::Modulus => TokenKind::ModulusAssign,
-                TokenKind::BinaryNot => TokenKind::BinaryNotAssign,
-                TokenKind::BinaryXOr => TokenKind::BinaryXOrAssign,
-                TokenKind::BinaryAnd => TokenKind::BinaryAndAssign,
-                TokenKind::BinaryOr => TokenKind::BinaryOrAssign,
-                other => panic!("Unrecognized operator: {:?}", other),
-            }
+            assign_operator
         } else {
             operator
         }
     }
 
+    pub fn dot(&mut self) -> TokenKind {
+        if self.peek_next() != '.' {
+            return TokenKind::Dot;
+        }
+
+        self.next(); // Skip .[.]
+        if self.peek_next() == '=' {
+            self.next(); // Skip ..[=]
+            TokenKind::RangeInclusive
+        } else {
+            TokenKind::Range
+        }
+    }
+
     pub fn whitespace(&mut self) -> TokenKind {
         self.ignore_until(|c| !c.is_whitespace());
         TokenKind::Whitespace
     }
 
     pub fn string(&mut self) -> TokenKind {
+        let body_start = self.position.offset;
         let mut is_escaped = false;
+        let mut terminated = false;
         while let Some(c) = self.next() {
             match c {
-                '\\' => is_escaped = true,
-                '"' if !is_escaped => break,
-                _ if is_escaped => is_escaped = false,
-                _ => (),
+                '\\' if !is_escaped => is_escaped = true,
+                '"' if !is_escaped => {
+                    terminated = true;
+                    break;
+                }
+                _ => is_escaped = false,
             }
         }
+
+        if terminated {
+            self.check_escapes(body_start, self.position.offset - 1);
+        } else {
+            self.error(
+                "Unterminated string literal",
+                Some("close the string with `\"`"),
+            );
+        }
+
         TokenKind::Literal(LiteralKind::String)
     }
 
+    /// Whether the next characters continue an `r` into a raw string, like `r"` or `r##"`.
+    pub fn is_raw_string_start(&self) -> bool {
+        let mut chars = self.chars.clone().skip_while(|c| *c == '#');
+        chars.next() == Some('"')
+    }
+
+    pub fn raw_string(&mut self) -> TokenKind {
+        let mut guards = 0;
+        while self.peek_next() == '#' {
+            self.next(); // Skip r[#]
+            guards += 1;
+        }
+        self.next(); // Skip r#["]
+
+        let mut terminated = false;
+        while let Some(c) = self.next() {
+            if c == '"' {
+                let mut chars = self.chars.clone();
+                if (0..guards).all(|_| chars.next() == Some('#')) {
+                    for _ in 0..guards {
+                        self.next(); // Skip "[#]
+                    }
+                    terminated = true;
+                    break;
+                }
+            }
+        }
+
+        if !terminated {
+            let hint = format!("close the string with `\"{}`", "#".repeat(guards));
+            self.error("Unterminated raw string literal", Some(&hint));
+        }
+
+        TokenKind::Literal(LiteralKind::RawString)
+    }
+
     pub fn format_string(&mut self) -> TokenKind {
         self.next().unwrap(); // Ignore f["]
+        let body_start = self.position.offset;
+        let errors = self.diagnostics.len();
         self.string();
+
+        // Don't bother checking the interpolations of a broken string
+        if self.diagnostics.len() == errors {
+            let body = self.source(body_start, self.position.offset - 1);
+            let mut errors = Vec::new();
+            let pieces =
+                literal::split_format_string(body, |range, error| errors.push((range, error)));
+
+            for piece in pieces {
+                if let FormatPiece::Interpolation { expression, spec } = piece {
+                    if let Some(spec) = spec {
+                        if let Err(message) = FormatSpec::parse(&body[spec.clone()]) {
+                            errors.push((spec, LiteralError::new(message)));
+                        }
+                    }
+
+                    // Only to report errors, the parser tokenizes the expression again
+                    let start = self.position_at(body_start + expression.start);
+                    let (_, diagnostics) = Tokenizer::new(&body[expression], &mut Interner::new())
+                        .starting_at(self.file, start)
+                        .tokenize();
+                    self.diagnostics.extend(diagnostics);
+                }
+            }
+
+            for (range, error) in errors {
+                self.literal_error(body_start, range, error);
+            }
+        }
+
         TokenKind::Literal(LiteralKind::FormatString)
     }
 
     pub fn char(&mut self) -> TokenKind {
-        self.next().unwrap(); // Skip '[<char>]'
-        self.next().unwrap(); // Skip '<char>[']
+        let body_start = self.position.offset;
+        let mut is_escaped = false;
+        while !self.is_eof() {
+            match self.peek_next() {
+                '\'' if !is_escaped => break,
+                '\n' => break,
+                '\\' if !is_escaped => is_escaped = true,
+                _ => is_escaped = false,
+            }
+            self.next();
+        }
+
+        let body_end = self.position.offset;
+        if self.peek_next() != '\'' {
+            self.error(
+                "Unterminated character literal",
+                Some("close the character with `'`"),
+            );
+            return TokenKind::Literal(LiteralKind::Char);
+        }
+        self.next(); // Skip '<char>[']
+
+        if self.check_escapes(body_start, body_end) {
+            if let Err(error) = literal::parse_char(self.source(body_start, body_end)) {
+                let hint = error.hint.as_deref();
+                self.error(error.message.clone(), hint);
+            }
+        }
+
         TokenKind::Literal(LiteralKind::Char)
     }
 
+    /// Reports every invalid escape sequence between the `start` and `end` byte offsets, returns
+    /// `true` if there were none.
+    pub fn check_escapes(&mut self, start: usize, end: usize) -> bool {
+        let mut errors = Vec::new();
+        literal::unescape(self.source(start, end), |range, error| {
+            errors.push((range, error))
+        });
+
+        let is_valid = errors.is_empty();
+        for (range, error) in errors {
+            self.literal_error(start, range, error);
+        }
+
+        is_valid
+    }
+
+    /// Reports an error inside a literal, `range` is relative to the `start` byte offset.
+    pub fn literal_error(&mut self, start: usize, range: Range<usize>, error: LiteralError) {
+        let span = Span::new(
+            self.file,
+            self.position_at(start + range.start),
+            self.position_at(start + range.end),
+        );
+        let mut diagnostic = Diagnostic::error(span, error.message);
+        diagnostic.hint = error.hint;
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Position of a byte offset inside the current token.
+    pub fn position_at(&self, offset: usize) -> Position {
+        let mut position = self.token_start;
+        for c in self.source(position.offset, offset).chars() {
+            position.advance(c);
+        }
+        position
+    }
+
     pub fn number(&mut self, starts_with_zero: bool) -> TokenKind {
-        let mut kind = None;
         let base = if starts_with_zero {
             match self.peek_next() {
                 'b' => Base::Binary,
@@ -285,35 +543,71 @@ impl<'a> Tokenizer<'a> {
             Base::Decimal
         };
 
-        while !self.is_eof() {
-            let c = self.peek_next();
-            if c == '.' || c == 'f' {
-                if kind.is_none() {
-                    kind = Some(LiteralKind::Float(base));
-                } else {
-                    break;
+        let mut is_float = false;
+        if base == Base::Decimal {
+            self.ignore_until(|c| !c.is_ascii_digit() && c != '_');
+
+            // Fraction, but not a range (`0..10`) or field access (`1.max`)
+            if self.peek_next() == '.' && self.peek_next_next().is_ascii_digit() {
+                is_float = true;
+                self.next(); // Skip [.]
+                self.ignore_until(|c| !c.is_ascii_digit() && c != '_');
+            }
+
+            // Exponent
+            if matches!(self.peek_next(), 'e' | 'E') {
+                let mut chars = self.chars.clone();
+                chars.next();
+                let mut sign_len = 0;
+                if let Some('+' | '-') = chars.clone().next() {
+                    chars.next();
+                    sign_len = 1;
+                }
+
+                if chars.next().is_some_and(|c| c.is_ascii_digit()) {
+                    is_float = true;
+                    for _ in 0..=sign_len {
+                        self.next(); // Skip [e][+-]
+                    }
+                    self.ignore_until(|c| !c.is_ascii_digit() && c != '_');
                 }
-            } else if !c.is_numeric() && c != '_' {
-                break;
             }
+        } else {
+            self.next(); // Skip 0[b/o/x]
+        }
 
-            self.next();
+        // Remaining digits for other bases, and the type suffix
+        self.ignore_until(|c| !c.is_alphanumeric() && c != '_');
+
+        let text = self.source(self.token_start.offset, self.position.offset);
+        let (_, _, suffix) = literal::split_number(text);
+        // Whether it fits in its type depends on a `-` in front of it, which the parser checks
+        let parsed =
+            literal::parse_number(text).or_else(|_| literal::parse_signed_number(text, true));
+        if let Err(error) = parsed {
+            let hint = error.hint.as_deref();
+            self.error(error.message.clone(), hint);
         }
 
-        TokenKind::Literal(kind.unwrap_or(LiteralKind::Integer(base)))
+        if base == Base::Decimal && (is_float || suffix.starts_with('f')) {
+            TokenKind::Literal(LiteralKind::Float(base))
+        } else {
+            TokenKind::Literal(LiteralKind::Integer(base))
+        }
     }
 
     pub fn annotation(&mut self) -> TokenKind {
-        let mut buffer = String::new();
-        while self.peek_next().is_alphanumeric() && !self.is_eof() {
-            let c = self.next().unwrap();
-            buffer.push(c);
-        }
+        let name_start = self.position.offset;
+        self.ignore_until(|c| !c.is_alphanumeric());
 
-        let kind = match buffer.as_ref() {
+        let kind = match self.source(name_start, self.position.offset) {
             "extern" => AnnotationKind::Extern,
             other => {
-                if other.len() == 0 {
+                if other.is_empty() {
+                    self.error(
+                        "Expected an annotation name after `@`",
+                        Some("annotations look like `@extern`"),
+                    );
                     return TokenKind::Unknown;
                 }
 
@@ -322,17 +616,12 @@ impl<'a> Tokenizer<'a> {
                     if !((ch.is_alphabetic() && i == 0) || (ch.is_alphanumeric() && i != 0))
                         && ch != '_'
                     {
+                        self.error(format!("Invalid annotation name `{}`", other), None);
                         return TokenKind::Unknown;
                     }
                 }
 
-                if let Some(id) = self.idents.get(other) {
-                    AnnotationKind::Other(*id)
-                } else {
-                    let id = self.idents.values().len();
-                    self.idents.insert(other.to_string(), Ident(id));
-                    AnnotationKind::Other(Ident(id))
-                }
+                AnnotationKind::Other(self.idents.intern(other))
             }
         };
 
@@ -359,23 +648,29 @@ impl<'a> Tokenizer<'a> {
             }
             TokenKind::Assign if next_char == '=' => TokenKind::IsEqualTo,
             TokenKind::LessThan if next_char == '=' => TokenKind::LessThanOrEquals,
-            TokenKind::LessThan if next_char == '<' => {
-                if self.peek_next_next() == '<' {
+            TokenKind::LessThan if next_char == '<' => match self.peek_next_next() {
+                '<' => {
                     self.next();
                     TokenKind::ShiftLeftOverflow
-                } else {
-                    TokenKind::ShiftLeft
                 }
-            }
+                '=' => {
+                    self.next();
+                    TokenKind::ShiftLeftAssign
+                }
+                _ => TokenKind::ShiftLeft,
+            },
             TokenKind::GreaterThan if next_char == '=' => TokenKind::GreaterThanOrEquals,
-            TokenKind::GreaterThan if next_char == '>' => {
-                if self.peek_next_next() == '>' {
+            TokenKind::GreaterThan if next_char == '>' => match self.peek_next_next() {
+                '>' => {
                     self.next();
                     TokenKind::ShiftRightOverflow
-                } else {
-                    TokenKind::ShiftRight
                 }
-            }
+                '=' => {
+                    self.next();
+                    TokenKind::ShiftRightAssign
+                }
+                _ => TokenKind::ShiftRight,
+            },
             _ => kind,
         };
 
@@ -386,50 +681,45 @@ impl<'a> Tokenizer<'a> {
         new_kind
     }
 
-    pub fn try_keyword(&mut self, first_char: char) -> TokenKind {
-        let mut buffer = String::new();
-        buffer.push(first_char);
+    pub fn try_keyword(&mut self) -> TokenKind {
+        self.ignore_until(|c| !c.is_alphanumeric() && c != '_');
 
-        while {
-            let c = self.peek_next();
-            c.is_alphanumeric() || c == '_'
-        } && !self.is_eof()
-        {
-            buffer.push(self.next().unwrap());
-        }
-
-        match buffer.as_str() {
+        match self.source(self.token_start.offset, self.position.offset) {
             "enum" => TokenKind::Keyword(KeywordKind::Enum),
             "function" => TokenKind::Keyword(KeywordKind::Function),
             "let" => TokenKind::Keyword(KeywordKind::Let),
+            "mut" => TokenKind::Keyword(KeywordKind::Mut),
             "module" => TokenKind::Keyword(KeywordKind::Module),
             "private" => TokenKind::Keyword(KeywordKind::Private),
             "public" => TokenKind::Keyword(KeywordKind::Public),
             "type" => TokenKind::Keyword(KeywordKind::Type),
+            "impl" => TokenKind::Keyword(KeywordKind::Impl),
+            "trait" => TokenKind::Keyword(KeywordKind::Trait),
             "use" => TokenKind::Keyword(KeywordKind::Use),
+            "if" => TokenKind::Keyword(KeywordKind::If),
+            "else" => TokenKind::Keyword(KeywordKind::Else),
+            "while" => TokenKind::Keyword(KeywordKind::While),
+            "for" => TokenKind::Keyword(KeywordKind::For),
+            "in" => TokenKind::Keyword(KeywordKind::In),
+            "loop" => TokenKind::Keyword(KeywordKind::Loop),
+            "break" => TokenKind::Keyword(KeywordKind::Break),
+            "continue" => TokenKind::Keyword(KeywordKind::Continue),
+            "return" => TokenKind::Keyword(KeywordKind::Return),
+            "match" => TokenKind::Keyword(KeywordKind::Match),
             "true" => TokenKind::Literal(LiteralKind::Boolean),
             "false" => TokenKind::Literal(LiteralKind::Boolean),
             other => {
-                if other.len() == 0 {
-                    return TokenKind::Unknown;
-                }
-
                 for (i, ch) in other.chars().enumerate() {
                     // If not a valid identifier name
                     if !((ch.is_alphabetic() && i == 0) || (ch.is_alphanumeric() && i != 0))
                         && ch != '_'
                     {
+                        self.error(format!("Unexpected character `{}`", ch), None);
                         return TokenKind::Unknown;
                     }
                 }
 
-                if let Some(id) = self.idents.get(other) {
-                    return TokenKind::Identifier(*id);
-                }
-
-                let id = self.idents.values().len();
-                self.idents.insert(other.to_string(), Ident(id));
-                TokenKind::Identifier(Ident(id))
+                TokenKind::Identifier(self.idents.intern(other))
             }
         }
     }
@@ -450,6 +740,9 @@ impl<'a> Tokenizer<'a> {
             // Format string
             'f' if self.peek_next() == '"' => self.format_string(),
 
+            // Raw string
+            'r' if self.is_raw_string_start() => self.raw_string(),
+
             // Regular string
             '"' => self.string(),
 
@@ -463,7 +756,8 @@ impl<'a> Tokenizer<'a> {
 
             // Others
             ',' => TokenKind::Comma,
-            '.' => TokenKind::Dot,
+            // ., .., ..=
+            '.' => self.dot(),
             '(' => TokenKind::OpenParenthesis,
             ')' => TokenKind::CloseParenthesis,
             '{' => TokenKind::OpenBrace,
@@ -475,7 +769,11 @@ impl<'a> Tokenizer<'a> {
             // Common operators
             // +, +=
             '+' => self.operator(TokenKind::Add),
-            // -, -=
+            // -, -=, ->
+            '-' if self.peek_next() == '>' => {
+                self.next(); // Skip -[>]
+                TokenKind::Arrow
+            }
             '-' => self.operator(TokenKind::Subtract),
             // *, *=
             '*' => self.operator(TokenKind::Multiply),
@@ -490,7 +788,11 @@ impl<'a> Tokenizer<'a> {
             '^' => self.operator(TokenKind::BinaryXOr),
 
             // Conditions or operators
-            // =, ==
+            // =, ==, =>
+            '=' if self.peek_next() == '>' => {
+                self.next(); // Skip =[>]
+                TokenKind::FatArrow
+            }
             '=' => self.condition(TokenKind::Assign),
             // !, !=
             '!' => self.condition(TokenKind::Not),
@@ -498,31 +800,41 @@ impl<'a> Tokenizer<'a> {
             '&' => self.condition(TokenKind::BinaryAnd),
             // |, ||
             '|' => self.condition(TokenKind::BinaryOr),
-            // <, <<, <<<, <=
+            // <, <<, <<<, <<=, <=
             '<' => self.condition(TokenKind::LessThan),
-            // >, >>, >>>, >=
+            // >, >>, >>>, >>=, >=
             '>' => self.condition(TokenKind::GreaterThan),
 
             emoji if !emoji.is_ascii() && unic_emoji_char::is_emoji(emoji) => {
-                panic!("Dont use emojis in your script!")
+                self.error(
+                    "Emojis are not allowed outside of strings",
+                    Some("put the emoji inside a string or remove it"),
+                );
+                TokenKind::Unknown
             }
 
             // Try keywords otherwise return TokenKind::Unknown
-            other => self.try_keyword(other),
+            _ => self.try_keyword(),
         };
 
         Token {
             len: self.consumed_len(),
             kind: token_kind,
+            span: self.token_span(),
         }
     }
 
-    pub fn tokenize(&mut self) -> TokenList {
+    /// Tokenizes the whole program. Never fails, problems are reported as diagnostics and the
+    /// offending text becomes a `TokenKind::Unknown` token.
+    pub fn tokenize(&mut self) -> (TokenList, Vec<Diagnostic>) {
         let mut tokens = TokenList::new();
         while !self.is_eof() {
             self.reset_consumed_len();
             tokens.push(self.next_token());
         }
-        tokens
+
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        diagnostics.sort_by_key(|d| d.span.start);
+        (tokens, diagnostics)
     }
 }
@@ -1,8 +1,10 @@
 use hug_lexer::{
+    parser::TokenPair,
     run_test,
-    tokenizer::{Base, KeywordKind, LiteralKind, TokenKind},
+    tokenizer::{AnnotationKind, Base, KeywordKind, LiteralKind, TokenKind},
+    FilterUseless,
 };
-use hug_lib::Ident;
+use hug_lib::{interner::Interner, Ident};
 
 const COMMENTS_PROGRAM: &str = r###"
 // Hello
@@ -18,10 +20,13 @@ const COMMENTS_EXPECTED_RESULT: &[(TokenKind, usize)] = &[
     (TokenKind::Whitespace, 1),
     (TokenKind::LineComment, 9),
     (TokenKind::LineComment, 18),
-    (TokenKind::BlockComment, 42),
+    (TokenKind::BlockComment, 41),
+    (TokenKind::Whitespace, 1),
     (TokenKind::LineComment, 3),
-    (TokenKind::BlockComment, 5),
-    (TokenKind::BlockComment, 7),
+    (TokenKind::BlockComment, 4),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::BlockComment, 6),
+    (TokenKind::Whitespace, 1),
     (TokenKind::Divide, 1),
     (TokenKind::Whitespace, 1),
     (TokenKind::Divide, 1),
@@ -33,6 +38,30 @@ fn comments() {
     run_test(COMMENTS_PROGRAM, COMMENTS_EXPECTED_RESULT);
 }
 
+const NESTED_COMMENTS_PROGRAM: &str =
+    "/* outer /* inner */ still comment */x/* a /* b /* c */ */ */";
+
+const NESTED_COMMENTS_EXPECTED_RESULT: &[(TokenKind, usize)] = &[
+    (TokenKind::BlockComment, 37),
+    (TokenKind::Identifier(Ident(0)), 1),
+    (TokenKind::BlockComment, 23),
+];
+
+#[test]
+fn nested_comments() {
+    run_test(NESTED_COMMENTS_PROGRAM, NESTED_COMMENTS_EXPECTED_RESULT);
+
+    let (pairs, diagnostics) = hug_lexer::lex(
+        "let a = 1 /* open /* nested */
+let b = 2",
+        &mut Interner::new(),
+    );
+    assert_eq!(pairs.last().unwrap().token.kind, TokenKind::BlockComment);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Unterminated block comment");
+    assert_eq!(diagnostics[0].span.start.column, 11);
+}
+
 const VARIABLES_PROGRAM: &str = r###"
 let some_Weird_hellishName: MyType[] = [MyType(50), MyType(13)]
 let is_snake_case_epic = true
@@ -121,3 +150,417 @@ const VARIABLES_EXPECTED_RESULT: &[(TokenKind, usize)] = &[
 fn variables() {
     run_test(VARIABLES_PROGRAM, VARIABLES_EXPECTED_RESULT);
 }
+
+const SPANS_PROGRAM: &str = "let ünïcode = 10\n  add(ünïcode, 5)";
+
+#[test]
+fn spans() {
+    let (pairs, _) = hug_lexer::lex(SPANS_PROGRAM, &mut Interner::new());
+
+    for pair in pairs.iter() {
+        assert_eq!(&SPANS_PROGRAM[pair.span().range()], pair.text);
+    }
+
+    let literal = &pairs[6];
+    assert_eq!(literal.text, "10");
+    assert_eq!(literal.span().start.offset, 16);
+    assert_eq!(
+        (literal.span().start.line, literal.span().start.column),
+        (1, 15)
+    );
+
+    let add = &pairs[8];
+    assert_eq!(add.text, "add");
+    assert_eq!((add.span().start.line, add.span().start.column), (2, 3));
+    assert_eq!((add.span().end.line, add.span().end.column), (2, 6));
+}
+
+const ERRORS_PROGRAM: &str = "let a = 🦀\nlet $b = 5\nlet c = \"unterminated";
+
+#[test]
+fn errors() {
+    let (pairs, diagnostics) = hug_lexer::lex(ERRORS_PROGRAM, &mut Interner::new());
+
+    // Everything is still tokenized, errors become unknown tokens
+    assert_eq!(pairs.last().unwrap().text, "\"unterminated");
+    assert_eq!(
+        pairs
+            .iter()
+            .filter(|p| p.token.kind == TokenKind::Unknown)
+            .count(),
+        2
+    );
+
+    let messages = diagnostics
+        .iter()
+        .map(|d| (d.span.start.line, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (1, "Emojis are not allowed outside of strings"),
+            (2, "Unexpected character `$`"),
+            (3, "Unterminated string literal"),
+        ]
+    );
+}
+
+const NUMBERS_PROGRAM: &str = "0xFF 0b1010_1010u8 0o777 1_000 1e-9 2.5f64 10u8 3i128 7f32 0..10";
+
+#[test]
+fn numbers() {
+    let (pairs, diagnostics) = hug_lexer::lex(NUMBERS_PROGRAM, &mut Interner::new());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let values = pairs
+        .iter()
+        .filter_map(|p| p.parse_literal())
+        .map(|v| format!("{:?}", v))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            "Int32(255)",
+            "UInt8(170)",
+            "Int32(511)",
+            "Int32(1000)",
+            "Float32(1e-9)",
+            "Float64(2.5)",
+            "UInt8(10)",
+            "Int128(3)",
+            "Float32(7.0)",
+            "Int32(0)",
+            "Int32(10)",
+        ]
+    );
+
+    assert_eq!(
+        pairs[0].token.kind,
+        TokenKind::Literal(LiteralKind::Integer(Base::Hexadecimal))
+    );
+    assert_eq!(
+        pairs[8].token.kind,
+        TokenKind::Literal(LiteralKind::Float(Base::Decimal))
+    );
+}
+
+#[test]
+fn invalid_numbers() {
+    let (_, diagnostics) =
+        hug_lexer::lex("256u8 0b102 1.5i32 3x 0x 5000000000", &mut Interner::new());
+    let messages = diagnostics
+        .iter()
+        .map(|d| d.message.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            "Integer literal is out of range for `UInt8`",
+            "Invalid digit `2` in binary literal",
+            "Integer suffix `i32` on a float literal",
+            "Invalid suffix `x` for a number literal",
+            "Missing digits in hexadecimal literal",
+            "Integer literal is out of range for `Int32`",
+        ]
+    );
+
+    // Only a literal that doesn't fit needs a bigger type
+    let (_, diagnostics) = hug_lexer::lex("0b102 0o8 5000000000", &mut Interner::new());
+    let hints = diagnostics
+        .iter()
+        .map(|d| d.hint.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        hints,
+        vec![
+            None,
+            None,
+            Some("add a type suffix like `i64` to use a bigger type")
+        ]
+    );
+}
+
+const ESCAPES_PROGRAM: &str = r#""a\nb\t\"c\" \\ \x7F \u{1F600}" '\n' '\'' '\u{1F600}' 'x'"#;
+
+#[test]
+fn escapes() {
+    let (pairs, diagnostics) = hug_lexer::lex(ESCAPES_PROGRAM, &mut Interner::new());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let values = pairs
+        .iter()
+        .filter_map(|p| p.parse_literal())
+        .map(|v| format!("{:?}", v))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            r#"String("a\nb\t\"c\" \\ \u{7f} 😀")"#,
+            r"Char('\n')",
+            r"Char('\'')",
+            "Char('😀')",
+            "Char('x')",
+        ]
+    );
+}
+
+#[test]
+fn invalid_escapes() {
+    let (_, diagnostics) =
+        hug_lexer::lex(r#""\q and \xFF" '\u{110000}' 'ab' '"#, &mut Interner::new());
+    let messages = diagnostics
+        .iter()
+        .map(|d| (d.span.start.column, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (2, "Unknown escape sequence `\\q`"),
+            (9, "`\\x` escapes must be at most `\\x7F`"),
+            (16, "`110000` is not a valid unicode character"),
+            (28, "Character literal must contain exactly one character"),
+            (33, "Unterminated character literal"),
+        ]
+    );
+}
+
+const RAW_STRINGS_PROGRAM: &str = r####"r"C:\Users\hug" r#"say "hi""# r##"a "# b
+second line"## r"""####;
+
+#[test]
+fn raw_strings() {
+    let (pairs, diagnostics) = hug_lexer::lex(RAW_STRINGS_PROGRAM, &mut Interner::new());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let values = pairs
+        .iter()
+        .filter(|p| p.token.kind == TokenKind::Literal(LiteralKind::RawString))
+        .filter_map(|p| p.parse_literal())
+        .map(|v| format!("{:?}", v))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            r#"String("C:\\Users\\hug")"#,
+            r#"String("say \"hi\"")"#,
+            r##"String("a \"# b\nsecond line")"##,
+            r#"String("")"#,
+        ]
+    );
+
+    let (_, diagnostics) = hug_lexer::lex(r###"r##"never closed"#"###, &mut Interner::new());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].hint.as_deref(),
+        Some("close the string with `\"##`")
+    );
+}
+
+#[test]
+fn invalid_format_strings() {
+    let (_, diagnostics) = hug_lexer::lex(r#"f"{a:>>>} } {} {🦀} {b""#, &mut Interner::new());
+    let messages = diagnostics
+        .iter()
+        .map(|d| (d.span.start.column, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            (6, "Invalid format specifier `>>>`"),
+            (11, "Unmatched `}` in format string"),
+            (13, "Empty interpolation in format string"),
+            (17, "Emojis are not allowed outside of strings"),
+            (20, "Unterminated interpolation in format string"),
+        ]
+    );
+}
+
+const DOC_COMMENTS_PROGRAM: &str = "//! Module docs\n/// Item docs\n//// Not docs\n// Plain\n";
+
+const DOC_COMMENTS_EXPECTED_RESULT: &[(TokenKind, usize)] = &[
+    (TokenKind::InnerDocComment, 16),
+    (TokenKind::DocComment, 14),
+    (TokenKind::LineComment, 14),
+    (TokenKind::LineComment, 9),
+];
+
+#[test]
+fn doc_comments() {
+    run_test(DOC_COMMENTS_PROGRAM, DOC_COMMENTS_EXPECTED_RESULT);
+}
+
+#[test]
+fn pairs_borrow_source() {
+    let program = "let s = \"héllo wörld 🦀\"\nlet x = 1 // ünïcode\nlet y = x";
+    let (pairs, diagnostics) = hug_lexer::lex(program, &mut Interner::new());
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let range = program.as_bytes().as_ptr_range();
+    for pair in pairs.iter() {
+        // Text is a slice of the program, not a copy
+        assert!(range.contains(&pair.text.as_ptr()) || pair.text.is_empty());
+        assert_eq!(pair.text.len(), pair.token.len);
+        assert_eq!(&program[pair.span().range()], pair.text);
+    }
+
+    let texts = pairs
+        .filter_useless()
+        .iter()
+        .map(|p| p.text)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        texts,
+        vec![
+            "let",
+            "s",
+            "=",
+            "\"héllo wörld 🦀\"",
+            "let",
+            "x",
+            "=",
+            "1",
+            "let",
+            "y",
+            "=",
+            "x"
+        ]
+    );
+}
+
+#[test]
+fn shared_interner() {
+    let mut idents = Interner::new();
+    let (first, _) = hug_lexer::lex("let name = other", &mut idents);
+    let (second, _) = hug_lexer::lex("@custom print(other)", &mut idents);
+
+    // The same name gets the same ident across scripts
+    let other = |pairs: &[TokenPair]| pairs.iter().find(|p| p.text == "other").unwrap().token.kind;
+    assert_eq!(other(&first), other(&second));
+
+    let names = first
+        .iter()
+        .chain(second.iter())
+        .filter_map(|p| match p.token.kind {
+            TokenKind::Identifier(id) | TokenKind::Annotation(AnnotationKind::Other(id)) => {
+                Some(idents.resolve(id))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["name", "other", "custom", "print", "other"]);
+    assert_eq!(idents.len(), 4);
+    assert_eq!(
+        idents.annotate_debug("Call(Ident(3), [Ident(1)])"),
+        "Call(Ident(3 \"print\"), [Ident(1 \"other\")])"
+    );
+}
+
+const CONTROL_FLOW_PROGRAM: &str = "for i in 0..10 {} 1..=x a.b";
+
+const CONTROL_FLOW_EXPECTED_RESULT: &[(TokenKind, usize)] = &[
+    (TokenKind::Keyword(KeywordKind::For), 3),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::Identifier(Ident(0)), 1),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::Keyword(KeywordKind::In), 2),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::Literal(LiteralKind::Integer(Base::Decimal)), 1),
+    (TokenKind::Range, 2),
+    (TokenKind::Literal(LiteralKind::Integer(Base::Decimal)), 2),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::OpenBrace, 1),
+    (TokenKind::CloseBrace, 1),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::Literal(LiteralKind::Integer(Base::Decimal)), 1),
+    (TokenKind::RangeInclusive, 3),
+    (TokenKind::Identifier(Ident(1)), 1),
+    (TokenKind::Whitespace, 1),
+    (TokenKind::Identifier(Ident(2)), 1),
+    (TokenKind::Dot, 1),
+    (TokenKind::Identifier(Ident(3)), 1),
+];
+
+#[test]
+fn control_flow() {
+    run_test(CONTROL_FLOW_PROGRAM, CONTROL_FLOW_EXPECTED_RESULT);
+
+    let (pairs, _) = hug_lexer::lex(
+        "if else while loop break continue return iffy",
+        &mut Interner::new(),
+    );
+    let kinds = pairs
+        .filter_useless()
+        .iter()
+        .map(|p| p.token.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Keyword(KeywordKind::If),
+            TokenKind::Keyword(KeywordKind::Else),
+            TokenKind::Keyword(KeywordKind::While),
+            TokenKind::Keyword(KeywordKind::Loop),
+            TokenKind::Keyword(KeywordKind::Break),
+            TokenKind::Keyword(KeywordKind::Continue),
+            TokenKind::Keyword(KeywordKind::Return),
+            TokenKind::Identifier(Ident(0)),
+        ]
+    );
+}
+
+#[test]
+fn assignment_operators() {
+    let (pairs, _) = hug_lexer::lex(
+        "let mut x = 1\nx <<= 2 x >>= 1 x += 3 x <<<= 1",
+        &mut Interner::new(),
+    );
+    let kinds = pairs
+        .filter_useless()
+        .iter()
+        .map(|p| p.token.kind)
+        .filter(|k| !matches!(k, TokenKind::Identifier(_) | TokenKind::Literal(_)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Keyword(KeywordKind::Let),
+            TokenKind::Keyword(KeywordKind::Mut),
+            TokenKind::Assign,
+            TokenKind::ShiftLeftAssign,
+            TokenKind::ShiftRightAssign,
+            TokenKind::AddAssign,
+            TokenKind::ShiftLeftOverflow,
+            TokenKind::Assign,
+        ]
+    );
+}
+
+#[test]
+fn match_tokens() {
+    let mut idents = Interner::new();
+    let (pairs, _) = hug_lexer::lex("match x { 1 => a, _ => b == c }", &mut idents);
+    let kinds = pairs
+        .filter_useless()
+        .iter()
+        .map(|p| p.token.kind)
+        .filter(|k| !matches!(k, TokenKind::Literal(_)))
+        .collect::<Vec<_>>();
+    let ident = |name| TokenKind::Identifier(idents.get(name).unwrap());
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Keyword(KeywordKind::Match),
+            ident("x"),
+            TokenKind::OpenBrace,
+            TokenKind::FatArrow,
+            ident("a"),
+            TokenKind::Comma,
+            ident("_"),
+            TokenKind::FatArrow,
+            ident("b"),
+            TokenKind::IsEqualTo,
+            ident("c"),
+            TokenKind::CloseBrace,
+        ]
+    );
+}
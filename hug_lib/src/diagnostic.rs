@@ -0,0 +1,102 @@
+use std::fmt::Display;
+
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A problem found somewhere in a script, reported instead of aborting so every problem in a file
+/// can be shown at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            span,
+            hint: None,
+        }
+    }
+
+    pub fn error(span: Span, message: impl Into<String>) -> Diagnostic {
+        Self::new(Severity::Error, span, message)
+    }
+
+    pub fn warning(span: Span, message: impl Into<String>) -> Diagnostic {
+        Self::new(Severity::Warning, span, message)
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Diagnostic {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Formats the diagnostic together with the offending line of `source`, `file_name` is only
+    /// used for display.
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let mut buffer = format!(
+            "{}: {}\n --> {}:{}\n",
+            self.severity, self.message, file_name, self.span.start
+        );
+
+        if let Some(line) = source.lines().nth(self.span.start.line - 1) {
+            let gutter = self.span.start.line.to_string();
+            let width = if self.span.end.line == self.span.start.line {
+                (self.span.end.column - self.span.start.column).max(1)
+            } else {
+                1
+            };
+
+            buffer.push_str(&format!(
+                "{0:1$} |\n{2} | {3}\n{0:1$} | {4}{5}\n",
+                "",
+                gutter.len(),
+                gutter,
+                line,
+                " ".repeat(self.span.start.column - 1),
+                "^".repeat(width)
+            ));
+        }
+
+        if let Some(hint) = &self.hint {
+            buffer.push_str(&format!(" = hint: {}\n", hint));
+        }
+
+        buffer
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.span, self.severity, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, " (hint: {})", hint)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use crate::value::HugValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The part after the `:` in a format string interpolation, like the `>8` in `{x:>8}`. Follows
+/// Rust's syntax: `[[fill]align][0][width][.precision]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub zero_pad: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            zero_pad: false,
+            width: None,
+            precision: None,
+        }
+    }
+}
+
+fn align_from_char(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    }
+}
+
+impl FormatSpec {
+    pub fn parse(spec: &str) -> Result<FormatSpec, String> {
+        let mut result = FormatSpec::default();
+        let mut rest = spec;
+
+        let mut chars = rest.chars();
+        let first = chars.next();
+        let second = chars.next();
+        if let (Some(fill), Some(align)) = (first, second.and_then(align_from_char)) {
+            result.fill = fill;
+            result.align = Some(align);
+            rest = &rest[fill.len_utf8() + 1..];
+        } else if let Some(align) = first.and_then(align_from_char) {
+            result.align = Some(align);
+            rest = &rest[1..];
+        }
+
+        if let Some(r) = rest.strip_prefix('0') {
+            result.zero_pad = true;
+            rest = r;
+        }
+
+        let width_len = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if width_len > 0 {
+            result.width = Some(rest[..width_len].parse().map_err(|_| "Width is too big")?);
+            rest = &rest[width_len..];
+        }
+
+        if let Some(r) = rest.strip_prefix('.') {
+            let precision_len = r.len() - r.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if precision_len == 0 {
+                return Err("Expected a precision after `.`".to_string());
+            }
+            result.precision = Some(
+                r[..precision_len]
+                    .parse()
+                    .map_err(|_| "Precision is too big")?,
+            );
+            rest = &r[precision_len..];
+        }
+
+        if !rest.is_empty() {
+            return Err(format!("Invalid format specifier `{}`", spec));
+        }
+
+        Ok(result)
+    }
+
+    /// Formats `value` according to this spec.
+    pub fn apply(&self, value: &HugValue) -> String {
+        let is_numeric = value.is_numeric();
+        let text = match (self.precision, value) {
+            (Some(p), HugValue::Float32(v)) => format!("{:.*}", p, v),
+            (Some(p), HugValue::Float64(v)) => format!("{:.*}", p, v),
+            (Some(p), v) if !is_numeric => v.to_string().chars().take(p).collect(),
+            (_, v) => v.to_string(),
+        };
+
+        let len = text.chars().count();
+        let width = match self.width {
+            Some(width) if width > len => width,
+            _ => return text,
+        };
+
+        if self.zero_pad && is_numeric {
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", text.as_str()),
+            };
+            return format!("{}{}{}", sign, "0".repeat(width - len), digits);
+        }
+
+        let padding = width - len;
+        let align = self.align.unwrap_or(if is_numeric {
+            Align::Right
+        } else {
+            Align::Left
+        });
+        let (before, after) = match align {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+
+        let fill = |n| std::iter::repeat_n(self.fill, n).collect::<String>();
+        format!("{}{}{}", fill(before), text, fill(after))
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::Ident;
+
+/// Maps identifier names to `Ident`s and back. A single interner is shared by the lexer, parser,
+/// VM and native modules so the same name always gets the same `Ident`.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    idents: HashMap<String, Ident>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Returns the `Ident` for `name`, registering it if it's new.
+    pub fn intern(&mut self, name: &str) -> Ident {
+        if let Some(id) = self.idents.get(name) {
+            return *id;
+        }
+
+        let id = Ident(self.names.len());
+        self.idents.insert(name.to_string(), id);
+        self.names.push(name.to_string());
+        id
+    }
+
+    /// Returns the `Ident` for `name` without registering it.
+    pub fn get(&self, name: &str) -> Option<Ident> {
+        self.idents.get(name).copied()
+    }
+
+    /// Returns the name of `id`, panics if `id` didn't come from this interner.
+    pub fn resolve(&self, id: Ident) -> &str {
+        &self.names[id.0]
+    }
+
+    pub fn try_resolve(&self, id: Ident) -> Option<&str> {
+        self.names.get(id.0).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Ident, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (Ident(i), name.as_str()))
+    }
+
+    /// Adds the names to every `Ident(n)` in a `{:?}` dump, for debug output.
+    pub fn annotate_debug(&self, text: &str) -> String {
+        let mut buffer = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("Ident(") {
+            let (before, after) = rest.split_at(start + "Ident(".len());
+            buffer.push_str(before);
+
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let name = after[..digits]
+                .parse()
+                .ok()
+                .and_then(|id| self.try_resolve(Ident(id)));
+
+            buffer.push_str(&after[..digits]);
+            if let (Some(name), true) = (name, after[digits..].starts_with(')')) {
+                buffer.push_str(&format!(" {:?}", name));
+            }
+            rest = &after[digits..];
+        }
+        buffer.push_str(rest);
+        buffer
+    }
+}
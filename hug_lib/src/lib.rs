@@ -1,6 +1,12 @@
+use interner::Interner;
 use std::collections::HashMap;
-use value::HugValue;
+use value::HugExternalFunction;
 
+pub mod diagnostic;
+pub mod format;
+pub mod interner;
+pub mod operators;
+pub mod span;
 pub mod value;
 
 #[macro_export]
@@ -33,25 +39,25 @@ macro_rules! unwrap_args {
 pub struct Ident(pub usize);
 
 pub struct HugModule<'a> {
-    pub functions: HashMap<Ident, fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>>,
-    idents: &'a mut HashMap<String, Ident>,
+    pub functions: HashMap<Ident, HugExternalFunction>,
+    idents: &'a mut Interner,
 }
 
 impl<'a> HugModule<'a> {
-    pub fn new(idents: &mut HashMap<String, Ident>) -> HugModule {
+    pub fn new(idents: &mut Interner) -> HugModule<'_> {
         HugModule {
             functions: HashMap::new(),
             idents,
         }
     }
 
-    pub fn register_function(
-        &mut self,
-        name: &str,
-        func: fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>,
-    ) {
+    pub fn idents(&self) -> &Interner {
+        self.idents
+    }
+
+    pub fn register_function(&mut self, name: &str, func: HugExternalFunction) {
         if let Some(id) = self.idents.get(name) {
-            self.functions.insert(*id, func);
+            self.functions.insert(id, func);
         } else {
             println!(
                 "The function \"{0}\" was registered before it was defined. \
@@ -60,7 +66,7 @@ impl<'a> HugModule<'a> {
             );
         }
         assert!(
-            self.idents.contains_key(name),
+            self.idents.get(name).is_some(),
             "Define the function \"{}\" first with @export function before registering it!",
             name
         );
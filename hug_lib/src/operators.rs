@@ -0,0 +1,251 @@
+//! Arithmetic and bitwise operators on `HugValue`s. Both sides of a binary operator must have the
+//! same type, errors are returned as messages so the caller can point them at the source.
+
+use crate::value::HugValue;
+
+macro_rules! integer_op {
+    ($left:expr, $right:expr, $method:ident, $($float:tt)?) => {
+        match ($left, $right) {
+            (HugValue::Int8(a), HugValue::Int8(b)) => a.$method(*b).map(HugValue::Int8),
+            (HugValue::Int16(a), HugValue::Int16(b)) => a.$method(*b).map(HugValue::Int16),
+            (HugValue::Int32(a), HugValue::Int32(b)) => a.$method(*b).map(HugValue::Int32),
+            (HugValue::Int64(a), HugValue::Int64(b)) => a.$method(*b).map(HugValue::Int64),
+            (HugValue::Int128(a), HugValue::Int128(b)) => a.$method(*b).map(HugValue::Int128),
+            (HugValue::UInt8(a), HugValue::UInt8(b)) => a.$method(*b).map(HugValue::UInt8),
+            (HugValue::UInt16(a), HugValue::UInt16(b)) => a.$method(*b).map(HugValue::UInt16),
+            (HugValue::UInt32(a), HugValue::UInt32(b)) => a.$method(*b).map(HugValue::UInt32),
+            (HugValue::UInt64(a), HugValue::UInt64(b)) => a.$method(*b).map(HugValue::UInt64),
+            (HugValue::UInt128(a), HugValue::UInt128(b)) => a.$method(*b).map(HugValue::UInt128),
+            $(
+                (HugValue::Float32(a), HugValue::Float32(b)) => Some(HugValue::Float32(a $float b)),
+                (HugValue::Float64(a), HugValue::Float64(b)) => Some(HugValue::Float64(a $float b)),
+            )?
+            _ => None,
+        }
+    };
+}
+
+macro_rules! bitwise_op {
+    ($left:expr, $right:expr, $op:tt) => {
+        match ($left, $right) {
+            (HugValue::Int8(a), HugValue::Int8(b)) => Some(HugValue::Int8(a $op b)),
+            (HugValue::Int16(a), HugValue::Int16(b)) => Some(HugValue::Int16(a $op b)),
+            (HugValue::Int32(a), HugValue::Int32(b)) => Some(HugValue::Int32(a $op b)),
+            (HugValue::Int64(a), HugValue::Int64(b)) => Some(HugValue::Int64(a $op b)),
+            (HugValue::Int128(a), HugValue::Int128(b)) => Some(HugValue::Int128(a $op b)),
+            (HugValue::UInt8(a), HugValue::UInt8(b)) => Some(HugValue::UInt8(a $op b)),
+            (HugValue::UInt16(a), HugValue::UInt16(b)) => Some(HugValue::UInt16(a $op b)),
+            (HugValue::UInt32(a), HugValue::UInt32(b)) => Some(HugValue::UInt32(a $op b)),
+            (HugValue::UInt64(a), HugValue::UInt64(b)) => Some(HugValue::UInt64(a $op b)),
+            (HugValue::UInt128(a), HugValue::UInt128(b)) => Some(HugValue::UInt128(a $op b)),
+            (HugValue::Bool(a), HugValue::Bool(b)) => Some(HugValue::Bool(a $op b)),
+            _ => None,
+        }
+    };
+}
+
+macro_rules! shift_op {
+    ($value:expr, $amount:expr, $method:ident) => {
+        match $value {
+            HugValue::Int8(v) => v.$method($amount).map(HugValue::Int8),
+            HugValue::Int16(v) => v.$method($amount).map(HugValue::Int16),
+            HugValue::Int32(v) => v.$method($amount).map(HugValue::Int32),
+            HugValue::Int64(v) => v.$method($amount).map(HugValue::Int64),
+            HugValue::Int128(v) => v.$method($amount).map(HugValue::Int128),
+            HugValue::UInt8(v) => v.$method($amount).map(HugValue::UInt8),
+            HugValue::UInt16(v) => v.$method($amount).map(HugValue::UInt16),
+            HugValue::UInt32(v) => v.$method($amount).map(HugValue::UInt32),
+            HugValue::UInt64(v) => v.$method($amount).map(HugValue::UInt64),
+            HugValue::UInt128(v) => v.$method($amount).map(HugValue::UInt128),
+            _ => None,
+        }
+    };
+}
+
+/// `rotate_left`/`rotate_right` never fail, this makes them fit in `shift_op!`.
+trait CheckedRotate: Sized {
+    fn checked_rotate_left(self, amount: u32) -> Option<Self>;
+    fn checked_rotate_right(self, amount: u32) -> Option<Self>;
+}
+
+macro_rules! impl_checked_rotate {
+    ($($int:ty),+) => {
+        $(impl CheckedRotate for $int {
+            fn checked_rotate_left(self, amount: u32) -> Option<Self> {
+                Some(self.rotate_left(amount))
+            }
+
+            fn checked_rotate_right(self, amount: u32) -> Option<Self> {
+                Some(self.rotate_right(amount))
+            }
+        })+
+    };
+}
+impl_checked_rotate!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+impl HugValue {
+    fn mismatch(&self, operator: &str, other: &HugValue) -> String {
+        format!(
+            "Cannot apply `{}` to `{}` and `{}`",
+            operator,
+            self.type_name(),
+            other.type_name()
+        )
+    }
+
+    /// Checks the types of a binary operator, `result` is `None` when the operation itself failed.
+    fn binary_result(
+        &self,
+        operator: &str,
+        other: &HugValue,
+        result: Option<HugValue>,
+    ) -> Result<HugValue, String> {
+        if self.type_name() != other.type_name() {
+            return Err(self.mismatch(operator, other));
+        }
+
+        result.ok_or_else(|| {
+            if self.is_numeric() {
+                format!(
+                    "`{} {} {}` overflows `{}`",
+                    self,
+                    operator,
+                    other,
+                    self.type_name()
+                )
+            } else {
+                self.mismatch(operator, other)
+            }
+        })
+    }
+
+    /// `+`, also concatenates strings.
+    pub fn add(&self, other: &HugValue) -> Result<HugValue, String> {
+        let result = match (self, other) {
+            (HugValue::String(a), HugValue::String(b)) => Some(HugValue::String(a.clone() + b)),
+            _ => integer_op!(self, other, checked_add, +),
+        };
+        self.binary_result("+", other, result)
+    }
+
+    pub fn subtract(&self, other: &HugValue) -> Result<HugValue, String> {
+        self.binary_result("-", other, integer_op!(self, other, checked_sub, -))
+    }
+
+    pub fn multiply(&self, other: &HugValue) -> Result<HugValue, String> {
+        self.binary_result("*", other, integer_op!(self, other, checked_mul, *))
+    }
+
+    pub fn divide(&self, other: &HugValue) -> Result<HugValue, String> {
+        if other.as_integer() == Some(0) {
+            return Err("Division by zero".to_string());
+        }
+        self.binary_result("/", other, integer_op!(self, other, checked_div, /))
+    }
+
+    pub fn modulus(&self, other: &HugValue) -> Result<HugValue, String> {
+        if other.as_integer() == Some(0) {
+            return Err("Division by zero".to_string());
+        }
+        self.binary_result("%", other, integer_op!(self, other, checked_rem, %))
+    }
+
+    pub fn bitwise_and(&self, other: &HugValue) -> Result<HugValue, String> {
+        self.binary_result("&", other, bitwise_op!(self, other, &))
+    }
+
+    pub fn bitwise_or(&self, other: &HugValue) -> Result<HugValue, String> {
+        self.binary_result("|", other, bitwise_op!(self, other, |))
+    }
+
+    pub fn bitwise_xor(&self, other: &HugValue) -> Result<HugValue, String> {
+        self.binary_result("^", other, bitwise_op!(self, other, ^))
+    }
+
+    /// Shift amount of `<<`, `>>`, `<<<` and `>>>`, which can be any integer type like in Rust.
+    fn shift_amount(&self, operator: &str, amount: &HugValue) -> Result<u32, String> {
+        match amount.as_integer() {
+            Some(amount) => {
+                u32::try_from(amount).map_err(|_| format!("Cannot shift by {} bits", amount))
+            }
+            None => Err(self.mismatch(operator, amount)),
+        }
+    }
+
+    /// Applies a shift, fails if the amount is not smaller than the number of bits.
+    fn shift(
+        &self,
+        operator: &str,
+        amount: &HugValue,
+        shift: fn(&HugValue, u32) -> Option<HugValue>,
+    ) -> Result<HugValue, String> {
+        let bits = self.shift_amount(operator, amount)?;
+        if !self.is_integer() {
+            return Err(self.mismatch(operator, amount));
+        }
+
+        shift(self, bits).ok_or_else(|| {
+            format!(
+                "Cannot shift `{}` by {} bits, it is too big for `{}`",
+                self,
+                bits,
+                self.type_name()
+            )
+        })
+    }
+
+    pub fn shift_left(&self, amount: &HugValue) -> Result<HugValue, String> {
+        self.shift("<<", amount, |v, bits| shift_op!(v, bits, checked_shl))
+    }
+
+    pub fn shift_right(&self, amount: &HugValue) -> Result<HugValue, String> {
+        self.shift(">>", amount, |v, bits| shift_op!(v, bits, checked_shr))
+    }
+
+    /// `<<<`, the bits shifted out on the left come back in on the right.
+    pub fn rotate_left(&self, amount: &HugValue) -> Result<HugValue, String> {
+        self.shift("<<<", amount, |v, bits| {
+            shift_op!(v, bits, checked_rotate_left)
+        })
+    }
+
+    /// `>>>`, the bits shifted out on the right come back in on the left.
+    pub fn rotate_right(&self, amount: &HugValue) -> Result<HugValue, String> {
+        self.shift(">>>", amount, |v, bits| {
+            shift_op!(v, bits, checked_rotate_right)
+        })
+    }
+
+    /// Unary `-`.
+    pub fn negate(&self) -> Result<HugValue, String> {
+        let result = match *self {
+            HugValue::Int8(v) => v.checked_neg().map(HugValue::Int8),
+            HugValue::Int16(v) => v.checked_neg().map(HugValue::Int16),
+            HugValue::Int32(v) => v.checked_neg().map(HugValue::Int32),
+            HugValue::Int64(v) => v.checked_neg().map(HugValue::Int64),
+            HugValue::Int128(v) => v.checked_neg().map(HugValue::Int128),
+            HugValue::Float32(v) => Some(HugValue::Float32(-v)),
+            HugValue::Float64(v) => Some(HugValue::Float64(-v)),
+            _ => return Err(format!("Cannot negate a `{}`", self.type_name())),
+        };
+
+        result.ok_or_else(|| format!("`-{}` overflows `{}`", self, self.type_name()))
+    }
+
+    /// Unary `~`, flips all bits of an integer.
+    pub fn bitwise_not(&self) -> Result<HugValue, String> {
+        Ok(match *self {
+            HugValue::Int8(v) => HugValue::Int8(!v),
+            HugValue::Int16(v) => HugValue::Int16(!v),
+            HugValue::Int32(v) => HugValue::Int32(!v),
+            HugValue::Int64(v) => HugValue::Int64(!v),
+            HugValue::Int128(v) => HugValue::Int128(!v),
+            HugValue::UInt8(v) => HugValue::UInt8(!v),
+            HugValue::UInt16(v) => HugValue::UInt16(!v),
+            HugValue::UInt32(v) => HugValue::UInt32(!v),
+            HugValue::UInt64(v) => HugValue::UInt64(!v),
+            HugValue::UInt128(v) => HugValue::UInt128(!v),
+            _ => return Err(format!("Cannot apply `~` to a `{}`", self.type_name())),
+        })
+    }
+}
@@ -1,3 +1,5 @@
+use std::{cmp::Ordering, fmt::Display};
+
 pub type HugExternalFunction = fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>;
 
 macro_rules! gen_impls_for_HugValue {
@@ -35,9 +37,159 @@ gen_impls_for_HugValue!(UInt64, u64);
 gen_impls_for_HugValue!(UInt128, u128);
 gen_impls_for_HugValue!(Float32, f32);
 gen_impls_for_HugValue!(Float64, f64);
+gen_impls_for_HugValue!(Bool, bool);
+gen_impls_for_HugValue!(Char, char);
 gen_impls_for_HugValue!(String, String);
 gen_impls_for_HugValue!(Function, usize);
 gen_impls_for_HugValue!(ExternalFunction, HugExternalFunction);
+gen_impls_for_HugValue!(Struct, HugStruct);
+gen_impls_for_HugValue!(Enum, HugEnum);
+
+/// An instance of a type declared with `type Point { x: Float64, y: Float64 }`. Fields are looked
+/// up by name, so native functions can build and read structs without the VM's interner.
+#[derive(Debug, Clone)]
+pub struct HugStruct {
+    /// Name of the type, qualified with its module like `geometry.Point`.
+    pub name: String,
+    /// The fields in the order they are declared in.
+    pub fields: Vec<(String, HugValue)>,
+}
+
+impl HugStruct {
+    pub fn new(name: impl Into<String>) -> HugStruct {
+        HugStruct {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field, `HugStruct::new("Point").with("x", 1.0).with("y", 2.0)`.
+    pub fn with(mut self, field: impl Into<String>, value: impl Into<HugValue>) -> HugStruct {
+        self.fields.push((field.into(), value.into()));
+        self
+    }
+
+    pub fn get(&self, field: &str) -> Option<&HugValue> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, field: &str) -> Option<&mut HugValue> {
+        self.fields
+            .iter_mut()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value)
+    }
+
+    /// Reads a field as a Rust type, `None` if it doesn't exist or has another type.
+    pub fn field<T: FromHugValue>(&self, field: &str) -> Option<T> {
+        self.get(field).and_then(HugValue::assert)
+    }
+}
+
+impl Display for HugStruct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {{", self.name)?;
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{} {}: {}", separator, name, value)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// An instance of a variant of a type declared with `enum Shape { Circle(Float64), Empty }`.
+#[derive(Debug, Clone)]
+pub struct HugEnum {
+    /// Name of the enum, qualified with its module like `geometry.Shape`.
+    pub name: String,
+    pub variant: String,
+    pub payload: HugPayload,
+}
+
+/// The values a variant carries.
+#[derive(Debug, Clone)]
+pub enum HugPayload {
+    /// `Shape.Empty`
+    Unit,
+    /// `Shape.Circle(1.0)`
+    Tuple(Vec<HugValue>),
+    /// `Shape.Rect { width: 1.0, height: 2.0 }`, in the order the fields are declared in.
+    Struct(Vec<(String, HugValue)>),
+}
+
+impl HugEnum {
+    /// A variant without a payload, add one with `with` or `with_field`.
+    pub fn new(name: impl Into<String>, variant: impl Into<String>) -> HugEnum {
+        HugEnum {
+            name: name.into(),
+            variant: variant.into(),
+            payload: HugPayload::Unit,
+        }
+    }
+
+    /// Adds a value to a tuple variant, `HugEnum::new("Shape", "Circle").with(1.0)`.
+    pub fn with(mut self, value: impl Into<HugValue>) -> HugEnum {
+        match &mut self.payload {
+            HugPayload::Tuple(values) => values.push(value.into()),
+            HugPayload::Unit => self.payload = HugPayload::Tuple(vec![value.into()]),
+            HugPayload::Struct(_) => panic!("Can't add an unnamed value to a struct variant"),
+        }
+        self
+    }
+
+    /// Adds a field to a struct variant, `HugEnum::new("Shape", "Rect").with_field("width", 1.0)`.
+    pub fn with_field(mut self, field: impl Into<String>, value: impl Into<HugValue>) -> HugEnum {
+        let field = (field.into(), value.into());
+        match &mut self.payload {
+            HugPayload::Struct(fields) => fields.push(field),
+            HugPayload::Unit => self.payload = HugPayload::Struct(vec![field]),
+            HugPayload::Tuple(_) => panic!("Can't add a named field to a tuple variant"),
+        }
+        self
+    }
+
+    /// A value of a tuple variant.
+    pub fn get(&self, index: usize) -> Option<&HugValue> {
+        match &self.payload {
+            HugPayload::Tuple(values) => values.get(index),
+            _ => None,
+        }
+    }
+
+    /// A field of a struct variant.
+    pub fn field(&self, field: &str) -> Option<&HugValue> {
+        match &self.payload {
+            HugPayload::Struct(fields) => fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl Display for HugEnum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.name, self.variant)?;
+        match &self.payload {
+            HugPayload::Unit => Ok(()),
+            HugPayload::Tuple(values) => {
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", values.join(", "))
+            }
+            HugPayload::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<_>>();
+                write!(f, " {{ {} }}", fields.join(", "))
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum HugValue {
@@ -53,13 +205,148 @@ pub enum HugValue {
     UInt128(u128),
     Float32(f32),
     Float64(f64),
+    Bool(bool),
+    Char(char),
     String(String),
-    Function(usize), // usize = pointer to instruction
-    ExternalFunction(fn(std::vec::IntoIter<HugValue>) -> Option<HugValue>),
+    Function(usize), // usize = index of the function in the VM
+    ExternalFunction(HugExternalFunction),
+    Struct(HugStruct),
+    Enum(HugEnum),
+    /// The result of a function that doesn't return anything, like Rust's `()`.
+    Void,
 }
 
 impl HugValue {
     pub fn assert<T: FromHugValue>(&self) -> Option<T> {
         T::from_hug_value(self.clone())
     }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || matches!(self, HugValue::Float32(_) | HugValue::Float64(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            HugValue::Int8(_)
+                | HugValue::Int16(_)
+                | HugValue::Int32(_)
+                | HugValue::Int64(_)
+                | HugValue::Int128(_)
+                | HugValue::UInt8(_)
+                | HugValue::UInt16(_)
+                | HugValue::UInt32(_)
+                | HugValue::UInt64(_)
+                | HugValue::UInt128(_)
+        )
+    }
+
+    /// Name of the hug type this value belongs to, for error messages.
+    pub fn type_name(&self) -> &str {
+        match self {
+            HugValue::Int8(_) => "Int8",
+            HugValue::Int16(_) => "Int16",
+            HugValue::Int32(_) => "Int32",
+            HugValue::Int64(_) => "Int64",
+            HugValue::Int128(_) => "Int128",
+            HugValue::UInt8(_) => "UInt8",
+            HugValue::UInt16(_) => "UInt16",
+            HugValue::UInt32(_) => "UInt32",
+            HugValue::UInt64(_) => "UInt64",
+            HugValue::UInt128(_) => "UInt128",
+            HugValue::Float32(_) => "Float32",
+            HugValue::Float64(_) => "Float64",
+            HugValue::Bool(_) => "Bool",
+            HugValue::Char(_) => "Char",
+            HugValue::String(_) => "String",
+            HugValue::Function(_) | HugValue::ExternalFunction(_) => "Function",
+            HugValue::Struct(value) => &value.name,
+            HugValue::Enum(value) => &value.name,
+            HugValue::Void => "Void",
+        }
+    }
+
+    /// Compares two values of the same type, `None` if they can't be compared.
+    pub fn compare(&self, other: &HugValue) -> Option<Ordering> {
+        match (self, other) {
+            (HugValue::Int8(a), HugValue::Int8(b)) => a.partial_cmp(b),
+            (HugValue::Int16(a), HugValue::Int16(b)) => a.partial_cmp(b),
+            (HugValue::Int32(a), HugValue::Int32(b)) => a.partial_cmp(b),
+            (HugValue::Int64(a), HugValue::Int64(b)) => a.partial_cmp(b),
+            (HugValue::Int128(a), HugValue::Int128(b)) => a.partial_cmp(b),
+            (HugValue::UInt8(a), HugValue::UInt8(b)) => a.partial_cmp(b),
+            (HugValue::UInt16(a), HugValue::UInt16(b)) => a.partial_cmp(b),
+            (HugValue::UInt32(a), HugValue::UInt32(b)) => a.partial_cmp(b),
+            (HugValue::UInt64(a), HugValue::UInt64(b)) => a.partial_cmp(b),
+            (HugValue::UInt128(a), HugValue::UInt128(b)) => a.partial_cmp(b),
+            (HugValue::Float32(a), HugValue::Float32(b)) => a.partial_cmp(b),
+            (HugValue::Float64(a), HugValue::Float64(b)) => a.partial_cmp(b),
+            (HugValue::Bool(a), HugValue::Bool(b)) => a.partial_cmp(b),
+            (HugValue::Char(a), HugValue::Char(b)) => a.partial_cmp(b),
+            (HugValue::String(a), HugValue::String(b)) => a.partial_cmp(b),
+            (HugValue::Void, HugValue::Void) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer as an `i128`, `None` for other types and `UInt128`s that don't fit.
+    pub fn as_integer(&self) -> Option<i128> {
+        match *self {
+            HugValue::Int8(v) => Some(v as i128),
+            HugValue::Int16(v) => Some(v as i128),
+            HugValue::Int32(v) => Some(v as i128),
+            HugValue::Int64(v) => Some(v as i128),
+            HugValue::Int128(v) => Some(v),
+            HugValue::UInt8(v) => Some(v as i128),
+            HugValue::UInt16(v) => Some(v as i128),
+            HugValue::UInt32(v) => Some(v as i128),
+            HugValue::UInt64(v) => Some(v as i128),
+            HugValue::UInt128(v) => i128::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    /// An integer of the same type as `self` holding `value`, `None` if it doesn't fit.
+    pub fn with_integer(&self, value: i128) -> Option<HugValue> {
+        Some(match self {
+            HugValue::Int8(_) => HugValue::Int8(value.try_into().ok()?),
+            HugValue::Int16(_) => HugValue::Int16(value.try_into().ok()?),
+            HugValue::Int32(_) => HugValue::Int32(value.try_into().ok()?),
+            HugValue::Int64(_) => HugValue::Int64(value.try_into().ok()?),
+            HugValue::Int128(_) => HugValue::Int128(value),
+            HugValue::UInt8(_) => HugValue::UInt8(value.try_into().ok()?),
+            HugValue::UInt16(_) => HugValue::UInt16(value.try_into().ok()?),
+            HugValue::UInt32(_) => HugValue::UInt32(value.try_into().ok()?),
+            HugValue::UInt64(_) => HugValue::UInt64(value.try_into().ok()?),
+            HugValue::UInt128(_) => HugValue::UInt128(value.try_into().ok()?),
+            _ => return None,
+        })
+    }
+}
+
+impl Display for HugValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HugValue::Int8(v) => write!(f, "{}", v),
+            HugValue::Int16(v) => write!(f, "{}", v),
+            HugValue::Int32(v) => write!(f, "{}", v),
+            HugValue::Int64(v) => write!(f, "{}", v),
+            HugValue::Int128(v) => write!(f, "{}", v),
+            HugValue::UInt8(v) => write!(f, "{}", v),
+            HugValue::UInt16(v) => write!(f, "{}", v),
+            HugValue::UInt32(v) => write!(f, "{}", v),
+            HugValue::UInt64(v) => write!(f, "{}", v),
+            HugValue::UInt128(v) => write!(f, "{}", v),
+            HugValue::Float32(v) => write!(f, "{}", v),
+            HugValue::Float64(v) => write!(f, "{}", v),
+            HugValue::Bool(v) => write!(f, "{}", v),
+            HugValue::Char(v) => write!(f, "{}", v),
+            HugValue::String(v) => write!(f, "{}", v),
+            HugValue::Function(_) => write!(f, "<function>"),
+            HugValue::ExternalFunction(_) => write!(f, "<external function>"),
+            HugValue::Struct(v) => write!(f, "{}", v),
+            HugValue::Enum(v) => write!(f, "{}", v),
+            HugValue::Void => write!(f, "()"),
+        }
+    }
 }